@@ -0,0 +1,45 @@
+use calc::compile::compile;
+use calc::context::Context;
+use calc::parser::Parser;
+use calc::scanner::Scanner;
+use calc::value::Value;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const EXPR: &str = "3x^2 - 2x + sin(x) / (1 + x^2) + sqrt(|x|)";
+
+fn inputs() -> Vec<[f64; 1]> {
+    (0..1000).map(|i| [i as f64 * 0.01 - 5.0]).collect()
+}
+
+fn interpret(c: &mut Criterion) {
+    let rows = inputs();
+    c.bench_function("interpret", |b| {
+        b.iter(|| {
+            let mut context = Context::new();
+            for row in rows.iter() {
+                context.set("x", Value::Number(row[0]));
+                let mut parser = Parser::with_context(Scanner::new(EXPR), context);
+                black_box(parser.next());
+                context = parser.into_context();
+            }
+        })
+    });
+}
+
+fn compiled(c: &mut Criterion) {
+    let rows = inputs();
+    let mut expr = compile(EXPR).unwrap();
+    c.bench_function("compiled eval", |b| {
+        b.iter(|| {
+            for row in rows.iter() {
+                black_box(expr.eval(row).unwrap());
+            }
+        })
+    });
+    c.bench_function("compiled eval_many", |b| {
+        b.iter(|| black_box(expr.eval_many(&rows).unwrap()))
+    });
+}
+
+criterion_group!(benches, interpret, compiled);
+criterion_main!(benches);
@@ -0,0 +1,184 @@
+use crate::context::Angle;
+use crate::scanner::{Command, Function};
+use crate::symbolic::{self, Notation};
+use crate::table::Style;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Operator::Add => write!(f, "+"),
+            Operator::Sub => write!(f, "-"),
+            Operator::Mul => write!(f, "*"),
+            Operator::Div => write!(f, "/"),
+            Operator::Mod => write!(f, "%"),
+            Operator::Pow => write!(f, "^"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Node {
+    Number(f64),
+    Date(f64),
+    // [a, b, ...], the items are numbers or rows
+    Matrix(Vec<Expr>),
+    // A value with a time unit, the number is the length of the unit in seconds
+    Duration(Box<Expr>, f64),
+    // An amount of money, 120 EUR
+    Money(Box<Expr>, String),
+    // expr in unit (or currency)
+    Convert(Box<Expr>, String),
+    Variable(String),
+    Assign(String, Box<Expr>),
+    // const g = 9.81, which cannot be assigned again
+    Const(String, Box<Expr>),
+    // a, b = 3, 4 where all values are evaluated before any is assigned
+    Unpack(Vec<String>, Vec<Expr>),
+    Negate(Box<Expr>),
+    Factorial(Box<Expr>),
+    DoubleFactorial(Box<Expr>),
+    Degrees(Box<Expr>),
+    // x% is x / 100, except on the right side of + and - where it is relative to the left side
+    Percent(Box<Expr>),
+    // Set (or just show) the angle unit
+    Mode(Option<Angle>),
+    // List or remove variables and functions: vars, who, del x y and clear
+    Workspace(Command, Vec<String>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    // A call of a user defined function, with positional and then keyword arguments
+    Apply(String, Vec<Expr>, Vec<(String, Expr)>),
+    // f(x, n=2) = expr, the parameters have optional defaults
+    Define(String, Vec<(String, Option<Expr>)>, Box<Expr>),
+    // solve lhs = rhs, optionally with the name of the unknown
+    Equation(Box<Expr>, Box<Expr>, Option<String>),
+    // simplify expr, the result is printed in the given notation
+    Simplify(Box<Expr>, Notation),
+    // Tabulate an expression over variables, with start, stop and step for each variable
+    Table(Box<Expr>, Vec<String>, Vec<Expr>, Style),
+}
+
+// A parsed expression, the span is the byte range in the input it was parsed from
+#[derive(PartialEq, Debug, Clone)]
+pub struct Expr {
+    pub node: Node,
+    pub span: (usize, usize),
+}
+
+impl Expr {
+    pub fn new(node: Node, span: (usize, usize)) -> Expr {
+        Expr { node, span }
+    }
+
+    pub fn binary(op: Operator, left: Expr, right: Expr) -> Expr {
+        let span = (left.span.0, right.span.1);
+        Expr::new(Node::Binary(op, Box::new(left), Box::new(right)), span)
+    }
+
+    pub fn children(&self) -> Vec<&Expr> {
+        match self.node {
+            Node::Number(_)
+            | Node::Date(_)
+            | Node::Variable(_)
+            | Node::Mode(_)
+            | Node::Workspace(..) => Vec::new(),
+            Node::Matrix(ref items) | Node::Call(_, ref items) | Node::Unpack(_, ref items) => {
+                items.iter().collect()
+            }
+            Node::Duration(ref e, _)
+            | Node::Money(ref e, _)
+            | Node::Convert(ref e, _)
+            | Node::Assign(_, ref e)
+            | Node::Const(_, ref e)
+            | Node::Negate(ref e)
+            | Node::Factorial(ref e)
+            | Node::DoubleFactorial(ref e)
+            | Node::Degrees(ref e)
+            | Node::Percent(ref e)
+            | Node::Simplify(ref e, _) => vec![e],
+            Node::Binary(_, ref l, ref r) | Node::Equation(ref l, ref r, _) => vec![l, r],
+            Node::Table(ref e, _, ref bounds, _) => {
+                std::iter::once(&**e).chain(bounds.iter()).collect()
+            }
+            Node::Apply(_, ref args, ref keywords) => {
+                args.iter().chain(keywords.iter().map(|k| &k.1)).collect()
+            }
+            Node::Define(_, ref params, ref body) => params
+                .iter()
+                .filter_map(|p| p.1.as_ref())
+                .chain(std::iter::once(&**body))
+                .collect(),
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self.node {
+            Node::Number(_)
+            | Node::Date(_)
+            | Node::Variable(_)
+            | Node::Mode(_)
+            | Node::Workspace(..) => Vec::new(),
+            Node::Matrix(ref mut items)
+            | Node::Call(_, ref mut items)
+            | Node::Unpack(_, ref mut items) => items.iter_mut().collect(),
+            Node::Duration(ref mut e, _)
+            | Node::Money(ref mut e, _)
+            | Node::Convert(ref mut e, _)
+            | Node::Assign(_, ref mut e)
+            | Node::Const(_, ref mut e)
+            | Node::Negate(ref mut e)
+            | Node::Factorial(ref mut e)
+            | Node::DoubleFactorial(ref mut e)
+            | Node::Degrees(ref mut e)
+            | Node::Percent(ref mut e)
+            | Node::Simplify(ref mut e, _) => vec![e],
+            Node::Binary(_, ref mut l, ref mut r) | Node::Equation(ref mut l, ref mut r, _) => {
+                vec![l, r]
+            }
+            Node::Table(ref mut e, _, ref mut bounds, _) => {
+                std::iter::once(&mut **e).chain(bounds.iter_mut()).collect()
+            }
+            Node::Apply(_, ref mut args, ref mut keywords) => args
+                .iter_mut()
+                .chain(keywords.iter_mut().map(|k| &mut k.1))
+                .collect(),
+            Node::Define(_, ref mut params, ref mut body) => params
+                .iter_mut()
+                .filter_map(|p| p.1.as_mut())
+                .chain(std::iter::once(&mut **body))
+                .collect(),
+        }
+    }
+
+    // Names of the variables used in the expression, in order of first appearance
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut stack = vec![self];
+        while let Option::Some(e) = stack.pop() {
+            if let Node::Variable(ref name) = e.node {
+                if !names.contains(&name.as_str()) {
+                    names.push(name.as_str());
+                }
+            }
+            stack.extend(e.children().into_iter().rev());
+        }
+        names
+    }
+}
+
+// Infix notation that the parser accepts
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", symbolic::format(self, Notation::Plain))
+    }
+}
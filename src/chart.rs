@@ -0,0 +1,645 @@
+// Charts of functions, parametric curves and polar curves, written as SVG or PNG files.
+// The chart is laid out once as a list of shapes that are then drawn in either format.
+
+use crate::context::Context;
+use crate::font;
+use crate::plot::{self, function, steps, value_at};
+use crate::table::Range;
+
+type Rgb = (u8, u8, u8);
+
+const COLORS: &[Rgb] = &[
+    (31, 119, 180),
+    (214, 39, 40),
+    (44, 160, 44),
+    (255, 127, 14),
+    (148, 103, 189),
+    (140, 86, 75),
+];
+const BACKGROUND: Rgb = (255, 255, 255);
+const GRID: Rgb = (225, 225, 225);
+const AXIS: Rgb = (110, 110, 110);
+const TEXT: Rgb = (40, 40, 40);
+// Number of points along every curve
+const SAMPLES: usize = 1000;
+// Width of a character in SVG (12px monospace) and the scale of the bitmap font in PNG
+const SVG_CHAR: f64 = 7.2;
+const PNG_SCALE: usize = 2;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Curve {
+    // y as a function of x
+    Graph(String),
+    // (x(t), y(t))
+    Parametric(String, String),
+    // r = f(t)
+    Polar(String),
+}
+
+impl Curve {
+    // "(cos t, sin t)" is parametric, "r = 1 + cos t" is polar and anything else is a graph
+    pub fn parse(s: &str) -> Curve {
+        let s = s.trim();
+        if let Option::Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            if let Option::Some(i) = top_level_comma(inner) {
+                return Curve::Parametric(
+                    inner[..i].trim().to_string(),
+                    inner[i + 1..].trim().to_string(),
+                );
+            }
+        }
+        match s.split_once('=') {
+            Option::Some((name, f)) if name.trim() == "r" => Curve::Polar(f.trim().to_string()),
+            _ => Curve::Graph(s.to_string()),
+        }
+    }
+
+    pub fn is_graph(&self) -> bool {
+        matches!(self, Curve::Graph(_))
+    }
+}
+
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Curve::Graph(ref y) => write!(f, "{}", y),
+            Curve::Parametric(ref x, ref y) => write!(f, "({}, {})", x, y),
+            Curve::Polar(ref r) => write!(f, "r = {}", r),
+        }
+    }
+}
+
+// The comma between the two functions of a parametric curve
+fn top_level_comma(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth == 0 => return Option::None,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => return Option::Some(i),
+            _ => {}
+        }
+    }
+    Option::None
+}
+
+// Points along a curve, None where it is undefined
+#[derive(PartialEq, Debug, Clone)]
+pub struct Line {
+    pub name: String,
+    pub points: Vec<Option<(f64, f64)>>,
+    // The range of x for graphs of functions
+    pub domain: Option<(f64, f64)>,
+}
+
+// Evaluate the curve over the range of the variable (x for graphs, t for the others)
+pub fn sample(curve: &Curve, t: &Range, context: &Context) -> Result<Line, String> {
+    let ts = steps(t.start, t.stop, SAMPLES);
+    let (points, domain) = match *curve {
+        Curve::Graph(ref y) => {
+            let mut f = function(y, &t.name, context)?;
+            let points = ts.map(|x| value_at(&mut f, x).map(|y| (x, y))).collect();
+            (points, Option::Some((t.start, t.stop)))
+        }
+        Curve::Parametric(ref x, ref y) => {
+            let mut fx = function(x, &t.name, context)?;
+            let mut fy = function(y, &t.name, context)?;
+            let points = ts
+                .map(|s| Option::Some((value_at(&mut fx, s)?, value_at(&mut fy, s)?)))
+                .collect();
+            (points, Option::None)
+        }
+        Curve::Polar(ref r) => {
+            let mut f = function(r, &t.name, context)?;
+            let angle = context.angle();
+            let points = ts
+                .map(|s| {
+                    let (r, a) = (value_at(&mut f, s)?, angle.to_radians(s));
+                    Option::Some((r * a.cos(), r * a.sin()))
+                })
+                .collect();
+            (points, Option::None)
+        }
+    };
+    Result::Ok(Line {
+        name: curve.to_string(),
+        points,
+        domain,
+    })
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Chart {
+    // Size in pixels
+    pub width: usize,
+    pub height: usize,
+    // The visible ranges, chosen from the curves when None
+    pub x: Option<(f64, f64)>,
+    pub y: Option<(f64, f64)>,
+    // Names of the x and y axes
+    pub labels: (String, String),
+}
+
+impl Default for Chart {
+    fn default() -> Self {
+        Chart {
+            width: 800,
+            height: 500,
+            x: Option::None,
+            y: Option::None,
+            labels: ("x".to_string(), "y".to_string()),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Shape {
+    Rect {
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+        fill: Option<Rgb>,
+        stroke: Option<Rgb>,
+    },
+    Line {
+        points: Vec<(f64, f64)>,
+        color: Rgb,
+        width: f64,
+    },
+    // The position is the vertical center of the text
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        anchor: Anchor,
+        color: Rgb,
+    },
+}
+
+impl Chart {
+    pub fn svg(&self, lines: &[Line]) -> String {
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+             viewBox=\"0 0 {0} {1}\" font-family=\"monospace\" font-size=\"12\">\n",
+            self.width, self.height
+        );
+        for shape in self.layout(lines, SVG_CHAR) {
+            let s = match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    w,
+                    h,
+                    fill,
+                    stroke,
+                } => format!(
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"{}\"/>",
+                    x,
+                    y,
+                    w,
+                    h,
+                    fill.map_or("none".to_string(), hex),
+                    stroke.map_or("none".to_string(), hex)
+                ),
+                Shape::Line {
+                    points,
+                    color,
+                    width,
+                } => {
+                    let points: Vec<String> = points
+                        .iter()
+                        .map(|&(x, y)| format!("{:.1},{:.1}", x, y))
+                        .collect();
+                    format!(
+                        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" \
+                         stroke-linejoin=\"round\"/>",
+                        points.join(" "),
+                        hex(color),
+                        width
+                    )
+                }
+                Shape::Text {
+                    x,
+                    y,
+                    text,
+                    anchor,
+                    color,
+                } => format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"{}\" dominant-baseline=\"central\" \
+                     fill=\"{}\">{}</text>",
+                    x,
+                    y,
+                    match anchor {
+                        Anchor::Start => "start",
+                        Anchor::Middle => "middle",
+                        Anchor::End => "end",
+                    },
+                    hex(color),
+                    escape(&text)
+                ),
+            };
+            out.push_str(&s);
+            out.push('\n');
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    pub fn png(&self, lines: &[Line]) -> Result<Vec<u8>, String> {
+        let char_width = ((font::WIDTH + 1) * PNG_SCALE) as f64;
+        let mut canvas = Canvas::new(self.width, self.height);
+        for shape in self.layout(lines, char_width) {
+            match shape {
+                Shape::Rect {
+                    x,
+                    y,
+                    w,
+                    h,
+                    fill,
+                    stroke,
+                } => {
+                    if let Option::Some(c) = fill {
+                        canvas.fill(x, y, w, h, c);
+                    }
+                    if let Option::Some(c) = stroke {
+                        let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h), (x, y)];
+                        for pair in corners.windows(2) {
+                            canvas.line(pair[0], pair[1], c, 1.0);
+                        }
+                    }
+                }
+                Shape::Line {
+                    points,
+                    color,
+                    width,
+                } => {
+                    for pair in points.windows(2) {
+                        canvas.line(pair[0], pair[1], color, width);
+                    }
+                }
+                Shape::Text {
+                    x,
+                    y,
+                    text,
+                    anchor,
+                    color,
+                } => canvas.text(x, y, &text, anchor, color),
+            }
+        }
+        canvas.encode()
+    }
+
+    fn layout(&self, lines: &[Line], char_width: f64) -> Vec<Shape> {
+        let (w, h) = (self.width as f64, self.height as f64);
+        let (left, right, top, bottom) = (70.0, w - 20.0, 30.0, h - 50.0);
+        let (x0, x1, y0, y1) = self.ranges(lines, right - left, bottom - top);
+        let px = |x: f64| left + (x - x0) / (x1 - x0) * (right - left);
+        let py = |y: f64| bottom - (y - y0) / (y1 - y0) * (bottom - top);
+        let mut shapes = vec![Shape::Rect {
+            x: 0.0,
+            y: 0.0,
+            w,
+            h,
+            fill: Option::Some(BACKGROUND),
+            stroke: Option::None,
+        }];
+        let line = |points: Vec<(f64, f64)>, color: Rgb, width: f64| Shape::Line {
+            points,
+            color,
+            width,
+        };
+        let text = |x: f64, y: f64, text: String, anchor: Anchor| Shape::Text {
+            x,
+            y,
+            text,
+            anchor,
+            color: TEXT,
+        };
+        // Gridlines with the numbers on the axes
+        for (x, s) in ticks(x0, x1) {
+            shapes.push(line(vec![(px(x), top), (px(x), bottom)], GRID, 1.0));
+            shapes.push(text(px(x), bottom + 14.0, s, Anchor::Middle));
+        }
+        for (y, s) in ticks(y0, y1) {
+            shapes.push(line(vec![(left, py(y)), (right, py(y))], GRID, 1.0));
+            shapes.push(text(left - 8.0, py(y), s, Anchor::End));
+        }
+        if x0 <= 0.0 && 0.0 <= x1 {
+            shapes.push(line(vec![(px(0.0), top), (px(0.0), bottom)], AXIS, 1.0));
+        }
+        if y0 <= 0.0 && 0.0 <= y1 {
+            shapes.push(line(vec![(left, py(0.0)), (right, py(0.0))], AXIS, 1.0));
+        }
+        shapes.push(Shape::Rect {
+            x: left,
+            y: top,
+            w: right - left,
+            h: bottom - top,
+            fill: Option::None,
+            stroke: Option::Some(AXIS),
+        });
+        shapes.push(text(
+            right,
+            bottom + 34.0,
+            self.labels.0.clone(),
+            Anchor::End,
+        ));
+        shapes.push(text(left, top - 14.0, self.labels.1.clone(), Anchor::Start));
+        for (i, l) in lines.iter().enumerate() {
+            for run in runs(l, (x0, x1, y0, y1)) {
+                let points = run.iter().map(|&(x, y)| (px(x), py(y))).collect();
+                shapes.push(line(points, COLORS[i % COLORS.len()], 2.0));
+            }
+        }
+        // The legend is in the top right corner
+        if !lines.is_empty() {
+            let longest = lines.iter().map(|l| l.name.chars().count()).max();
+            let lw = longest.unwrap_or(0) as f64 * char_width + 44.0;
+            let lh = lines.len() as f64 * 18.0 + 8.0;
+            let (lx, ly) = (right - lw - 8.0, top + 8.0);
+            shapes.push(Shape::Rect {
+                x: lx,
+                y: ly,
+                w: lw,
+                h: lh,
+                fill: Option::Some(BACKGROUND),
+                stroke: Option::Some(GRID),
+            });
+            for (i, l) in lines.iter().enumerate() {
+                let y = ly + 13.0 + i as f64 * 18.0;
+                let color = COLORS[i % COLORS.len()];
+                shapes.push(line(vec![(lx + 8.0, y), (lx + 28.0, y)], color, 2.0));
+                shapes.push(text(lx + 36.0, y, l.name.clone(), Anchor::Start));
+            }
+        }
+        shapes
+    }
+
+    // The visible (x0, x1, y0, y1) for a plot area of the given size
+    fn ranges(&self, lines: &[Line], width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let points = || {
+            lines
+                .iter()
+                .flat_map(|l| l.points.iter().filter_map(|&p| p))
+        };
+        let curves = || lines.iter().filter(|l| l.domain.is_none());
+        let graphs = lines.iter().any(|l| l.domain.is_some());
+        let (mut x0, mut x1) = match self.x {
+            Option::Some(x) => x,
+            Option::None => {
+                let xs: Vec<f64> = curves()
+                    .flat_map(|l| l.points.iter().filter_map(|&p| p.map(|p| p.0)))
+                    .collect();
+                let mut x = if xs.is_empty() {
+                    (f64::INFINITY, f64::NEG_INFINITY)
+                } else {
+                    pad(plot::range(xs))
+                };
+                for d in lines.iter().filter_map(|l| l.domain) {
+                    x = (x.0.min(d.0), x.1.max(d.1));
+                }
+                if x.0 < x.1 {
+                    x
+                } else {
+                    (-1.0, 1.0)
+                }
+            }
+        };
+        let (mut y0, mut y1) = match self.y {
+            Option::Some(y) => y,
+            Option::None => pad(plot::range(points().map(|p| p.1).collect())),
+        };
+        // Circles should look round when there are no graphs of functions
+        if !graphs && self.x.is_none() && self.y.is_none() {
+            let (ux, uy) = ((x1 - x0) / width, (y1 - y0) / height);
+            if ux > uy {
+                let extra = (ux * height - (y1 - y0)) / 2.0;
+                y0 -= extra;
+                y1 += extra;
+            } else {
+                let extra = (uy * width - (x1 - x0)) / 2.0;
+                x0 -= extra;
+                x1 += extra;
+            }
+        }
+        (x0, x1, y0, y1)
+    }
+}
+
+// Leave some space around the curves
+fn pad((a, b): (f64, f64)) -> (f64, f64) {
+    let m = (b - a) * 0.05;
+    (a - m, b + m)
+}
+
+// Round numbers between a and b with about eight steps, and their labels
+fn ticks(a: f64, b: f64) -> Vec<(f64, String)> {
+    let raw = (b - a) / 8.0;
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|&s| s >= raw)
+        .unwrap_or(10.0 * magnitude);
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let (first, last) = ((a / step).ceil() as i64, (b / step).floor() as i64);
+    (first..=last)
+        .map(|i| {
+            let x = i as f64 * step;
+            let s = format!("{:.*}", decimals, x);
+            (
+                x,
+                if s.parse::<f64>() == Result::Ok(0.0) {
+                    "0".to_string()
+                } else {
+                    s
+                },
+            )
+        })
+        .collect()
+}
+
+// The visible parts of a line, split where it is undefined or where a graph jumps over a pole
+fn runs(line: &Line, bounds: (f64, f64, f64, f64)) -> Vec<Vec<(f64, f64)>> {
+    let (_, _, y0, y1) = bounds;
+    let mut runs = Vec::new();
+    let mut run: Vec<(f64, f64)> = Vec::new();
+    let mut flush = |run: &mut Vec<(f64, f64)>| {
+        if run.len() >= 2 {
+            runs.push(std::mem::take(run));
+        }
+        run.clear();
+    };
+    for pair in line.points.windows(2) {
+        let (a, b) = match (pair[0], pair[1]) {
+            (Option::Some(a), Option::Some(b)) => (a, b),
+            _ => {
+                flush(&mut run);
+                continue;
+            }
+        };
+        let pole = (a.1 > y1 && b.1 < y0) || (a.1 < y0 && b.1 > y1);
+        match clip(a, b, bounds) {
+            Option::Some((c, d)) if !(pole && line.domain.is_some()) => {
+                if run.last() != Option::Some(&c) {
+                    flush(&mut run);
+                    run.push(c);
+                }
+                run.push(d);
+            }
+            _ => flush(&mut run),
+        }
+    }
+    flush(&mut run);
+    runs
+}
+
+// The part of the segment from a to b inside the bounds (Liang-Barsky)
+fn clip(
+    a: (f64, f64),
+    b: (f64, f64),
+    (x0, x1, y0, y1): (f64, f64, f64, f64),
+) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for &(p, q) in [
+        (-dx, a.0 - x0),
+        (dx, x1 - a.0),
+        (-dy, a.1 - y0),
+        (dy, y1 - a.1),
+    ]
+    .iter()
+    {
+        if p == 0.0 {
+            if q < 0.0 {
+                return Option::None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return Option::None;
+    }
+    // Unclipped ends are kept exactly, so that the next segment continues the same run
+    let at = |t: f64, end: (f64, f64)| {
+        if t == 0.0 || t == 1.0 {
+            end
+        } else {
+            (a.0 + t * dx, a.1 + t * dy)
+        }
+    };
+    Option::Some((at(t0, a), at(t1, b)))
+}
+
+fn hex((r, g, b): Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// RGB pixels for PNG output
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, (r, g, b): Rgb) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            let i = (y as usize * self.width + x as usize) * 3;
+            self.pixels[i..i + 3].copy_from_slice(&[r, g, b]);
+        }
+    }
+
+    fn fill(&mut self, x: f64, y: f64, w: f64, h: f64, color: Rgb) {
+        let (x0, y0) = (x.round() as i64, y.round() as i64);
+        for py in y0..(y + h).round() as i64 {
+            for px in x0..(x + w).round() as i64 {
+                self.set(px, py, color);
+            }
+        }
+    }
+
+    // A square brush of the width is moved along the line
+    fn line(&mut self, a: (f64, f64), b: (f64, f64), color: Rgb, width: f64) {
+        let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil().max(1.0) as usize;
+        let size = width.round().max(1.0) as i64;
+        for i in 0..=steps {
+            let t = i as f64 / steps as f64;
+            let x = (a.0 + (b.0 - a.0) * t).round() as i64 - size / 2;
+            let y = (a.1 + (b.1 - a.1) * t).round() as i64 - size / 2;
+            for dy in 0..size {
+                for dx in 0..size {
+                    self.set(x + dx, y + dy, color);
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, x: f64, y: f64, s: &str, anchor: Anchor, color: Rgb) {
+        let advance = (font::WIDTH + 1) * PNG_SCALE;
+        let width = (s.chars().count() * advance) as f64;
+        let left = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - width / 2.0,
+            Anchor::End => x - width,
+        };
+        // The capital letters are seven pixels tall
+        let top = (y - 3.5 * PNG_SCALE as f64).round() as i64;
+        for (i, c) in s.chars().enumerate() {
+            let cx = left.round() as i64 + (i * advance) as i64;
+            for col in 0..font::WIDTH {
+                for row in 0..font::HEIGHT {
+                    if !font::pixel(c, col, row) {
+                        continue;
+                    }
+                    for d in 0..PNG_SCALE * PNG_SCALE {
+                        let px = cx + (col * PNG_SCALE + d % PNG_SCALE) as i64;
+                        let py = top + (row * PNG_SCALE + d / PNG_SCALE) as i64;
+                        self.set(px, py, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let res = encoder
+            .write_header()
+            .and_then(|mut w| w.write_image_data(&self.pixels));
+        match res {
+            Result::Ok(()) => Result::Ok(out),
+            Result::Err(e) => Result::Err(format!("Cannot encode the PNG image: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::IsTerminal;
+
+use crate::scanner::{Scanner, Token};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+const UNDERLINE_RED: &str = "\x1b[4;31m";
+
+// Colors are used when stdout is a terminal and NO_COLOR is not set (https://no-color.org)
+pub fn enabled() -> bool {
+    match std::env::var_os("NO_COLOR") {
+        Option::Some(ref v) if !v.is_empty() => false,
+        _ => std::io::stdout().is_terminal(),
+    }
+}
+
+pub fn bold(s: &str) -> String {
+    format!("{}{}{}", BOLD, s, RESET)
+}
+
+pub fn red(s: &str) -> String {
+    format!("{}{}{}", RED, s, RESET)
+}
+
+// A different color for each line in a plot
+pub fn series(s: &str, index: usize) -> String {
+    let colors = [CYAN, MAGENTA, GREEN, YELLOW, RED];
+    format!("{}{}{}", colors[index % colors.len()], s, RESET)
+}
+
+fn token_color(t: &Token) -> Option<&'static str> {
+    match *t {
+        Token::Number(_) | Token::Date(_) => Option::Some(CYAN),
+        Token::Function(_) | Token::Command(_) => Option::Some(MAGENTA),
+        Token::Text(_) => Option::Some(GREEN),
+        Token::Unknown => Option::Some(RED),
+        Token::END => Option::None,
+        _ => Option::Some(YELLOW),
+    }
+}
+
+// Syntax highlight an expression, optionally underlining a byte range in red
+pub fn highlight(input: &str, error: Option<(usize, usize)>) -> String {
+    let mut out = String::with_capacity(input.len() * 2);
+    let mut sc = Scanner::new(input);
+    let mut last = 0;
+    loop {
+        let t = sc.next();
+        if t == Token::END {
+            break;
+        }
+        let (start, end) = sc.span();
+        out.push_str(&input[last..start]);
+        let text = input[start..end].trim_end();
+        let color = match error {
+            Option::Some((a, b)) if start < b && a < start + text.len().max(1) => UNDERLINE_RED,
+            _ => token_color(&t).unwrap_or(RESET),
+        };
+        out.push_str(color);
+        out.push_str(text);
+        out.push_str(RESET);
+        last = start + text.len();
+    }
+    out.push_str(&input[last..]);
+    out
+}
+
+// Colored version of `Scanner::print_pos`
+pub fn print_pos(sc: &Scanner) -> String {
+    print_span(sc.input(), sc.current(), sc.span())
+}
+
+pub fn print_span(input: &str, token: Token, span: (usize, usize)) -> String {
+    // Only the line where the span starts, for input with several lines
+    let (input, (start, end), position) = match Scanner::line_at(input, span.0) {
+        Option::Some((line, first, text)) => {
+            let last = first + text.len();
+            let start = span.0.min(last);
+            let end = span.1.clamp(start, last);
+            (
+                text,
+                (start - first, end - first),
+                format!("Line: {}", line),
+            )
+        }
+        Option::None => (input, span, format!("Position: {}", span.0)),
+    };
+    let end = input[start..end].trim_end().len() + start;
+    let pad = input[..start].chars().count();
+    let width = input[start..end].chars().count().max(1);
+    format!(
+        "{}\n{}\n{}\n{:pad$}{}",
+        token,
+        position,
+        highlight(input, Option::Some((start, end))),
+        "",
+        red(&format!("^{}", "~".repeat(width - 1))),
+        pad = pad
+    )
+}
@@ -0,0 +1,309 @@
+// Compile an expression once and evaluate it many times with different inputs.
+// The inputs are the variables that are not defined in the context, in the order they
+// first appear. Parts of the expression that do not depend on the inputs are evaluated
+// during compilation, and the rest is turned into instructions for a small stack machine.
+
+use crate::ast::{Expr, Node, Operator};
+use crate::context::{finite, integer, Context, Error};
+use crate::math;
+use crate::parser::Parser;
+use crate::scanner::{Function, Scanner};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Op {
+    Const(f64),
+    Input(usize),
+    Neg,
+    Binary(Operator),
+    // a ± a * b, for a percentage on the right side of + and -
+    Percent(Operator),
+    Factorial,
+    // The span of the operand, which must be an integer
+    DoubleFactorial((usize, usize)),
+    Degrees,
+    // A function and the number of arguments
+    Call(Function, usize),
+}
+
+pub struct CompiledExpr {
+    input: String,
+    names: Vec<String>,
+    ops: Vec<Op>,
+    // The part of the input that errors from the corresponding op point to
+    spans: Vec<(usize, usize)>,
+    context: Context,
+    stack: Vec<f64>,
+}
+
+pub fn compile(input: &str) -> Result<CompiledExpr, String> {
+    compile_with(input, Context::new())
+}
+
+// Variables (and the angle mode) of the context are fixed at compile time
+pub fn compile_with(input: &str, context: Context) -> Result<CompiledExpr, String> {
+    let mut parser = Parser::with_context(Scanner::new(input), context);
+    let expr = match parser.parse() {
+        Option::Some(Result::Ok(e)) => e,
+        Option::Some(Result::Err(e)) => return Result::Err(e),
+        Option::None => return Result::Err("Error: Nothing to compile".to_string()),
+    };
+    if let Option::Some(next) = parser.parse() {
+        let span = match next {
+            Result::Ok(e) => e.span,
+            Result::Err(_) => (input.len(), input.len()),
+        };
+        let e = Error::new("Only a single expression can be compiled", span);
+        return Result::Err(format_error(input, &e));
+    }
+    let context = parser.into_context();
+    let expr = match context.inline(&expr) {
+        Result::Ok(e) => e,
+        Result::Err(e) => return Result::Err(format_error(input, &e)),
+    };
+    let names = expr
+        .variables()
+        .into_iter()
+        .filter(|&name| context.get(name).is_none())
+        .map(|name| name.to_string())
+        .collect();
+    let mut compiled = CompiledExpr {
+        input: input.to_string(),
+        names,
+        ops: Vec::new(),
+        spans: Vec::new(),
+        context,
+        stack: Vec::new(),
+    };
+    match statement(&expr).and_then(|_| compiled.emit(&expr)) {
+        Result::Ok(()) => Result::Ok(compiled),
+        Result::Err(e) => Result::Err(format_error(input, &e)),
+    }
+}
+
+impl CompiledExpr {
+    // The names of the input slots
+    pub fn inputs(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    // Evaluate with one value for every input slot
+    pub fn eval(&mut self, inputs: &[f64]) -> Result<f64, String> {
+        if inputs.len() != self.names.len() {
+            return Result::Err(format!(
+                "Error: Expected {} inputs ({}), got {}",
+                self.names.len(),
+                self.names.join(", "),
+                inputs.len()
+            ));
+        }
+        match self.run(inputs) {
+            Result::Ok(x) => Result::Ok(x),
+            Result::Err(e) => Result::Err(format_error(&self.input, &e)),
+        }
+    }
+
+    // Evaluate once for every row of inputs
+    pub fn eval_many<R: AsRef<[f64]>>(&mut self, rows: &[R]) -> Result<Vec<f64>, String> {
+        let mut results = Vec::with_capacity(rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            match self.eval(row.as_ref()) {
+                Result::Ok(x) => results.push(x),
+                Result::Err(e) => return Result::Err(format!("Row {}: {}", i + 1, e)),
+            }
+        }
+        Result::Ok(results)
+    }
+
+    fn push(&mut self, op: Op, span: (usize, usize)) {
+        self.ops.push(op);
+        self.spans.push(span);
+    }
+
+    // Whether the expression can be evaluated during compilation
+    fn constant(&self, e: &Expr) -> bool {
+        match e.node {
+            Node::Variable(ref name) => self.context.get(name).is_some(),
+            Node::Call(f, _) if f.is_random() => false,
+            _ => e.children().into_iter().all(|c| self.constant(c)),
+        }
+    }
+
+    fn emit(&mut self, e: &Expr) -> Result<(), Error> {
+        if self.constant(e) {
+            let x = self.context.number(e)?;
+            self.push(Op::Const(x), e.span);
+            return Result::Ok(());
+        }
+        match e.node {
+            Node::Variable(ref name) => {
+                let slot = self.slot(name).expect("Unknown variables are inputs");
+                self.push(Op::Input(slot), e.span);
+            }
+            Node::Negate(ref x) => {
+                self.emit(x)?;
+                self.push(Op::Neg, e.span);
+            }
+            Node::Factorial(ref x) => {
+                self.emit(x)?;
+                self.push(Op::Factorial, e.span);
+            }
+            Node::DoubleFactorial(ref x) => {
+                self.emit(x)?;
+                self.push(Op::DoubleFactorial(x.span), e.span);
+            }
+            Node::Degrees(ref x) => {
+                self.emit(x)?;
+                self.push(Op::Degrees, e.span);
+            }
+            Node::Percent(ref x) => {
+                self.emit(x)?;
+                self.push(Op::Const(100.0), e.span);
+                self.push(Op::Binary(Operator::Div), e.span);
+            }
+            Node::Binary(op, ref l, ref r) => {
+                self.emit(l)?;
+                self.emit(r)?;
+                match (op, &r.node) {
+                    (Operator::Add | Operator::Sub, Node::Percent(_)) => {
+                        self.push(Op::Percent(op), r.span)
+                    }
+                    _ => self.push(Op::Binary(op), r.span),
+                }
+            }
+            Node::Call(f, ref args) if compilable(f) => {
+                for a in args {
+                    self.emit(a)?;
+                }
+                self.push(Op::Call(f, args.len()), e.span);
+            }
+            _ => {
+                return Result::Err(Error::new(
+                    "Only numbers and functions of numbers can be compiled",
+                    e.span,
+                ))
+            }
+        }
+        Result::Ok(())
+    }
+
+    fn run(&mut self, inputs: &[f64]) -> Result<f64, Error> {
+        let CompiledExpr {
+            ops,
+            spans,
+            context,
+            stack,
+            ..
+        } = self;
+        stack.clear();
+        for (&op, &span) in ops.iter().zip(spans.iter()) {
+            let x = match op {
+                Op::Const(x) => x,
+                Op::Input(i) => inputs[i],
+                Op::Neg => -pop(stack),
+                Op::Binary(op) => {
+                    let b = pop(stack);
+                    let a = pop(stack);
+                    match op {
+                        Operator::Add => a + b,
+                        Operator::Sub => a - b,
+                        Operator::Mul => a * b,
+                        Operator::Div | Operator::Mod if b == 0.0 => {
+                            return Result::Err(Error::new("Division by zero", span))
+                        }
+                        Operator::Div => a / b,
+                        Operator::Mod => a % b,
+                        Operator::Pow => a.powf(b),
+                    }
+                }
+                Op::Percent(op) => {
+                    let b = pop(stack);
+                    let a = pop(stack);
+                    if op == Operator::Add {
+                        a + a * b
+                    } else {
+                        a - a * b
+                    }
+                }
+                Op::Factorial => {
+                    let v = pop(stack);
+                    if v < 0.0 && v.fract() == 0.0 {
+                        return Result::Err(Error::new(
+                            "Factorial is undefined for negative integers",
+                            span,
+                        ));
+                    }
+                    finite(math::gamma(v + 1.0), span)?
+                }
+                Op::DoubleFactorial(operand) => {
+                    let v = integer(pop(stack), operand)?;
+                    finite(math::double_factorial(v), span)?
+                }
+                Op::Degrees => pop(stack) / 360.0 * context.angle().turn(),
+                Op::Call(f, n) => {
+                    let args = stack.len() - n;
+                    let x = context.call(f, &stack[args..], span)?;
+                    stack.truncate(args);
+                    x
+                }
+            };
+            stack.push(x);
+        }
+        Result::Ok(pop(stack))
+    }
+}
+
+// Statements would change the context when evaluated during compilation
+fn statement(e: &Expr) -> Result<(), Error> {
+    match e.node {
+        Node::Assign(..)
+        | Node::Const(..)
+        | Node::Unpack(..)
+        | Node::Define(..)
+        | Node::Mode(_)
+        | Node::Workspace(..)
+        | Node::Equation(..)
+        | Node::Simplify(..) => Result::Err(Error::new(
+            "Assignments and commands cannot be compiled",
+            e.span,
+        )),
+        _ => e.children().into_iter().try_for_each(statement),
+    }
+}
+
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().expect("The compiled expression is balanced")
+}
+
+// Functions that take and return plain numbers
+fn compilable(f: Function) -> bool {
+    !matches!(
+        f,
+        Function::Now
+            | Function::Today
+            | Function::Factor
+            | Function::Transpose
+            | Function::Det
+            | Function::Inv
+            | Function::Rank
+            | Function::Trace
+            | Function::Solve
+            | Function::Eig
+            | Function::Identity
+            | Function::Zeros
+            | Function::Simplify
+            | Function::Table
+    )
+}
+
+fn format_error(input: &str, e: &Error) -> String {
+    let token = Scanner::new(input).token_at(e.span.0);
+    format!(
+        "Error: {}\n{}",
+        e.message,
+        Scanner::print_at(input, token, e.span.0)
+    )
+}
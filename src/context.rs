@@ -0,0 +1,1378 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::distributions::Uniform;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::ast::{Expr, Node, Operator};
+use crate::currency::{self, Rates};
+use crate::math;
+use crate::matrix::Matrix;
+use crate::scanner::{Command, Function, KEYWORDS};
+use crate::solve;
+use crate::suggest;
+use crate::symbolic;
+use crate::table::{self, Range};
+use crate::time;
+use crate::value::{Format, Value};
+
+// An error from evaluating an expression, the span points to the offending part of the input
+#[derive(PartialEq, Debug, Clone)]
+pub struct Error {
+    pub message: String,
+    pub span: (usize, usize),
+}
+
+impl Error {
+    pub fn new(message: &str, span: (usize, usize)) -> Error {
+        Error {
+            message: message.to_string(),
+            span,
+        }
+    }
+}
+
+// Unit used for the input of trigonometric functions and the output of their inverses
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Angle {
+    Radians,
+    Degrees,
+    Gradians,
+}
+
+impl Angle {
+    pub fn from_name(name: &str) -> Option<Angle> {
+        match name.to_lowercase().as_ref() {
+            "rad" | "radian" | "radians" => Option::Some(Angle::Radians),
+            "deg" | "degree" | "degrees" => Option::Some(Angle::Degrees),
+            "grad" | "gradian" | "gradians" | "gon" => Option::Some(Angle::Gradians),
+            _ => Option::None,
+        }
+    }
+
+    // Size of a full turn in this unit
+    pub fn turn(self) -> f64 {
+        match self {
+            Angle::Radians => std::f64::consts::PI * 2.0,
+            Angle::Degrees => 360.0,
+            Angle::Gradians => 400.0,
+        }
+    }
+
+    pub fn to_radians(self, x: f64) -> f64 {
+        match self {
+            Angle::Radians => x,
+            _ => x / self.turn() * std::f64::consts::PI * 2.0,
+        }
+    }
+
+    pub fn from_radians(self, x: f64) -> f64 {
+        match self {
+            Angle::Radians => x,
+            _ => x / (std::f64::consts::PI * 2.0) * self.turn(),
+        }
+    }
+}
+
+impl std::fmt::Display for Angle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Angle::Radians => write!(f, "rad"),
+            Angle::Degrees => write!(f, "deg"),
+            Angle::Gradians => write!(f, "grad"),
+        }
+    }
+}
+
+// How deep calls of user defined functions may nest, which also stops endless recursion
+const MAX_DEPTH: usize = 50;
+// Rolling more dice than this would take too long
+const MAX_DICE: f64 = 1e6;
+// Random integers are drawn as i64
+const MAX_INT: f64 = i64::MAX as f64;
+// Largest number of elements in a created matrix, 1000x1000
+const MAX_ELEMENTS: usize = 1_000_000;
+
+// A function defined with f(x, n=2) = expr
+#[derive(PartialEq, Debug, Clone)]
+pub struct UserFunction {
+    pub params: Vec<(String, Option<Expr>)>,
+    pub body: Expr,
+}
+
+impl UserFunction {
+    // The number of parameters without defaults
+    pub fn min(&self) -> usize {
+        self.params.iter().take_while(|p| p.1.is_none()).count()
+    }
+
+    pub fn syntax(&self, name: &str) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(p, d)| match *d {
+                Option::Some(ref d) => format!("{}={}", p, d),
+                Option::None => p.clone(),
+            })
+            .collect();
+        format!("{}({})", name, params.join(", "))
+    }
+}
+
+// Variables, settings and random number generator used when evaluating expressions
+#[derive(Clone)]
+pub struct Context {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, UserFunction>,
+    // Variables defined with const, which cannot be assigned again
+    constants: HashSet<String>,
+    rng: StdRng,
+    angle: Angle,
+    // Numbers in text results, such as from solve, are written with this
+    format: Format,
+    rates: Rates,
+    depth: usize,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            constants: HashSet::new(),
+            rng: StdRng::from_entropy(),
+            angle: Angle::Radians,
+            format: Format::default(),
+            rates: Rates::default(),
+            depth: 0,
+        }
+    }
+
+    pub fn seeded(seed: u64) -> Context {
+        Context {
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            constants: HashSet::new(),
+            rng: StdRng::seed_from_u64(seed),
+            angle: Angle::Radians,
+            format: Format::default(),
+            rates: Rates::default(),
+            depth: 0,
+        }
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn angle(&self) -> Angle {
+        self.angle
+    }
+
+    pub fn set_angle(&mut self, angle: Angle) {
+        self.angle = angle;
+    }
+
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    pub fn rates(&self) -> &Rates {
+        &self.rates
+    }
+
+    pub fn set_rates(&mut self, rates: Rates) {
+        self.rates = rates;
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    // Returns the previous value
+    pub fn set(&mut self, name: &str, value: Value) -> Option<Value> {
+        self.variables.insert(name.to_string(), value)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.variables.remove(name)
+    }
+
+    // Sorted by name
+    pub fn variables(&self) -> Vec<(&str, &Value)> {
+        let mut variables: Vec<(&str, &Value)> = self
+            .variables
+            .iter()
+            .map(|(k, v)| (k.as_str(), v))
+            .collect();
+        variables.sort_by(|a, b| a.0.cmp(b.0));
+        variables
+    }
+
+    // Sorted by name
+    pub fn functions(&self) -> Vec<(&str, &UserFunction)> {
+        let mut functions: Vec<(&str, &UserFunction)> = self
+            .functions
+            .iter()
+            .map(|(k, f)| (k.as_str(), f))
+            .collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        functions
+    }
+
+    // Remove a variable (also a constant) or function, returns whether there was one
+    pub fn delete(&mut self, name: &str) -> bool {
+        self.constants.remove(name);
+        let variable = self.variables.remove(name).is_some();
+        self.functions.remove(name).is_some() || variable
+    }
+
+    // Remove all variables and functions, but keep the settings
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+        self.constants.clear();
+    }
+
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+
+    fn assignable(&self, name: &str, span: (usize, usize)) -> Result<(), Error> {
+        if self.is_constant(name) {
+            let msg = format!("Cannot assign to the constant '{}'", name);
+            return Result::Err(Error::new(&msg, span));
+        }
+        Result::Ok(())
+    }
+
+    pub fn function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    // A function replaces any variable with the same name (and the other way around)
+    pub fn define(&mut self, name: &str, function: UserFunction) -> Option<UserFunction> {
+        self.variables.remove(name);
+        self.functions.insert(name.to_string(), function)
+    }
+
+    // Error message for an unknown name, with suggestions for what might have been meant
+    pub fn unknown(&self, name: &str) -> String {
+        if let Option::Some(f) = self.functions.get(name) {
+            return format!("'{}' is a function, call it as {}", name, f.syntax(name));
+        }
+        let hints = suggest::suggest_defined(
+            name,
+            self.variables
+                .keys()
+                .chain(self.functions.keys())
+                .map(|k| k.as_str()),
+            KEYWORDS.iter().copied(),
+        );
+        suggest::message("Unknown variable or constant", name, &hints)
+    }
+
+    pub fn eval(&mut self, expr: &Expr) -> Result<Value, Error> {
+        match expr.node {
+            Node::Number(x) => Result::Ok(Value::Number(x)),
+            Node::Date(x) => Result::Ok(Value::Date(x)),
+            Node::Matrix(ref items) => self.matrix(items, expr.span),
+            Node::Duration(ref e, size) => Result::Ok(Value::Duration(self.number(e)? * size)),
+            Node::Money(ref e, ref code) => Result::Ok(Value::Money(self.number(e)?, code.clone())),
+            Node::Convert(ref e, ref unit) => match self.eval(e)? {
+                Value::Money(x, ref code) if currency::is_code(unit) => {
+                    match self.rates.convert(x, code, unit) {
+                        Result::Ok(y) => Result::Ok(Value::Money(currency::round(y), unit.clone())),
+                        Result::Err(msg) => Result::Err(Error::new(&msg, expr.span)),
+                    }
+                }
+                Value::Duration(x) => match time::unit(unit) {
+                    Option::Some(size) => Result::Ok(Value::Number(x / size)),
+                    Option::None => {
+                        Result::Err(Error::new(&format!("Unknown unit '{}'", unit), expr.span))
+                    }
+                },
+                v => Result::Err(Error::new(
+                    &format!("Cannot convert a {} to {}", v.kind(), unit),
+                    e.span,
+                )),
+            },
+            Node::Variable(ref name) => match self.variables.get(name) {
+                Option::Some(v) => Result::Ok(v.clone()),
+                Option::None => Result::Err(Error::new(&self.unknown(name), expr.span)),
+            },
+            Node::Assign(ref name, ref e) => self.assign(name, e, false, expr.span),
+            Node::Const(ref name, ref e) => self.assign(name, e, true, expr.span),
+            Node::Unpack(ref names, ref values) => self.unpack(names, values, expr.span),
+            Node::Define(ref name, ref params, ref body) => {
+                self.assignable(name, expr.span)?;
+                let function = UserFunction {
+                    params: params.clone(),
+                    body: (**body).clone(),
+                };
+                self.define(name, function);
+                Result::Ok(Value::Text(self.format.written(&expr.to_string())))
+            }
+            Node::Apply(ref name, ref args, ref keywords) => {
+                self.apply(name, args, keywords, expr.span)
+            }
+            Node::Negate(ref e) => match self.eval(e)? {
+                Value::Duration(x) => Result::Ok(Value::Duration(-x)),
+                Value::Money(x, code) => Result::Ok(Value::Money(-x, code)),
+                Value::Matrix(m) => Result::Ok(Value::Matrix(m.map(|x| -x))),
+                _ => Result::Ok(Value::Number(-self.number(e)?)),
+            },
+            Node::Degrees(ref e) => {
+                let v = self.number(e)?;
+                Result::Ok(Value::Number(v / 360.0 * self.angle.turn()))
+            }
+            Node::Mode(angle) => {
+                if let Option::Some(a) = angle {
+                    self.angle = a;
+                }
+                Result::Ok(Value::Text(format!("mode {}", self.angle)))
+            }
+            Node::Workspace(command, ref names) => self.workspace(command, names, expr.span),
+            Node::Factorial(ref e) => {
+                let v = self.number(e)?;
+                if v < 0.0 && v.fract() == 0.0 {
+                    Result::Err(Error::new(
+                        "Factorial is undefined for negative integers",
+                        expr.span,
+                    ))
+                } else {
+                    finite(math::gamma(v + 1.0), expr.span).map(Value::Number)
+                }
+            }
+            Node::DoubleFactorial(ref e) => {
+                let v = integer(self.number(e)?, e.span)?;
+                finite(math::double_factorial(v), expr.span).map(Value::Number)
+            }
+            Node::Percent(ref e) => Result::Ok(Value::Number(self.number(e)? / 100.0)),
+            Node::Binary(op, ref l, ref r) => {
+                let a = self.eval(l)?;
+                let b = self.eval(r)?;
+                let (v1, v2) = match (a.as_number(), b.as_number()) {
+                    (Option::Some(v1), Option::Some(v2)) => (v1, v2),
+                    _ => return self.arithmetic(op, a, b, l, r, expr.span),
+                };
+                let v = match op {
+                    // 100 + 15% == 115
+                    Operator::Add | Operator::Sub if matches!(r.node, Node::Percent(_)) => {
+                        if op == Operator::Add {
+                            v1 + v1 * v2
+                        } else {
+                            v1 - v1 * v2
+                        }
+                    }
+                    Operator::Add => v1 + v2,
+                    Operator::Sub => v1 - v2,
+                    Operator::Mul => v1 * v2,
+                    Operator::Div | Operator::Mod if v2 == 0.0 => {
+                        return Result::Err(Error::new("Division by zero", r.span))
+                    }
+                    Operator::Div => v1 / v2,
+                    Operator::Mod => v1 % v2,
+                    Operator::Pow => v1.powf(v2),
+                };
+                Result::Ok(Value::Number(v))
+            }
+            Node::Equation(ref l, ref r, ref var) => {
+                solve::solve(self, l, r, var.as_deref(), expr.span)
+            }
+            Node::Simplify(ref e, notation) => {
+                let e = symbolic::simplify(self, &self.inline(e)?);
+                let text = symbolic::format(&e, notation);
+                Result::Ok(Value::Text(self.format.written(&text)))
+            }
+            Node::Table(ref e, ref names, ref bounds, style) => {
+                let mut ranges = Vec::with_capacity(names.len());
+                for (name, b) in names.iter().zip(bounds.chunks(3)) {
+                    let (start, stop) = (self.number(&b[0])?, self.number(&b[1])?);
+                    match Range::new(name, start, stop, self.number(&b[2])?) {
+                        Result::Ok(r) => ranges.push(r),
+                        Result::Err(msg) => return Result::Err(Error::new(&msg, expr.span)),
+                    }
+                }
+                match table::tabulate(self, e, &ranges) {
+                    Result::Ok(t) => Result::Ok(Value::Text(t.format(style, &self.format))),
+                    Result::Err(msg) => Result::Err(Error::new(&msg, expr.span)),
+                }
+            }
+            Node::Call(Function::Now, _) => Result::Ok(Value::Date(time::now())),
+            Node::Call(Function::Today, _) => Result::Ok(Value::Date(time::today())),
+            Node::Call(
+                f @ (Function::Transpose
+                | Function::Det
+                | Function::Inv
+                | Function::Rank
+                | Function::Trace
+                | Function::Solve
+                | Function::Eig
+                | Function::Identity
+                | Function::Zeros),
+                ref args,
+            ) => self.linear(f, args),
+            Node::Call(Function::Factor, ref args) => {
+                let n = integer(self.number(&args[0])?, args[0].span)?;
+                if n == 0 {
+                    Result::Err(Error::new("Cannot factor zero", args[0].span))
+                } else {
+                    Result::Ok(Value::Factors(math::factorize(n)))
+                }
+            }
+            Node::Call(f, ref args) => {
+                let mut values = Vec::with_capacity(args.len());
+                for a in args {
+                    values.push(self.number(a)?);
+                }
+                Result::Ok(Value::Number(self.call(f, &values, expr.span)?))
+            }
+        }
+    }
+
+    fn workspace(
+        &mut self,
+        command: Command,
+        names: &[String],
+        span: (usize, usize),
+    ) -> Result<Value, Error> {
+        let text = match command {
+            Command::Del => {
+                for (i, name) in names.iter().enumerate() {
+                    if names[..i].contains(name) {
+                        let msg = format!("'{}' is listed more than once", name);
+                        return Result::Err(Error::new(&msg, span));
+                    }
+                    if !self.variables.contains_key(name) && self.function(name).is_none() {
+                        // Built-in names cannot be deleted, so only defined ones are suggested
+                        let defined = self.variables.keys().chain(self.functions.keys());
+                        let hints = suggest::suggest(name, defined.map(|k| k.as_str()));
+                        let msg = suggest::message("Unknown variable or constant", name, &hints);
+                        return Result::Err(Error::new(&msg, span));
+                    }
+                }
+                for name in names {
+                    self.delete(name);
+                }
+                format!("Deleted {}", names.join(", "))
+            }
+            Command::Clear => {
+                self.clear();
+                "Deleted all variables and functions".to_string()
+            }
+            _ if self.variables.is_empty() && self.functions.is_empty() => {
+                "No variables or functions are defined".to_string()
+            }
+            Command::Who => {
+                let mut names: Vec<&str> = self.variables().into_iter().map(|v| v.0).collect();
+                names.extend(self.functions().into_iter().map(|f| f.0));
+                names.sort_unstable();
+                names.join(" ")
+            }
+            _ => {
+                let mut lines: Vec<(&str, String)> = self
+                    .variables()
+                    .into_iter()
+                    .map(|(name, v)| {
+                        let prefix = if self.is_constant(name) { "const " } else { "" };
+                        let v = format!("{} ({})", v.format(&self.format), v.kind());
+                        let line = format!("{}{} = {}", prefix, name, v);
+                        (name, line)
+                    })
+                    .collect();
+                lines.extend(self.functions().into_iter().map(|(name, f)| {
+                    let body = format!("{} = {}", f.syntax(name), f.body);
+                    (name, format!("{} (function)", self.format.written(&body)))
+                }));
+                lines.sort_by(|a, b| a.0.cmp(b.0));
+                let lines: Vec<String> = lines.into_iter().map(|l| l.1).collect();
+                lines.join("\n")
+            }
+        };
+        Result::Ok(Value::Text(text))
+    }
+
+    fn assign(
+        &mut self,
+        name: &str,
+        e: &Expr,
+        constant: bool,
+        span: (usize, usize),
+    ) -> Result<Value, Error> {
+        self.assignable(name, span)?;
+        let v = self.eval(e)?;
+        self.functions.remove(name);
+        self.variables.insert(name.to_string(), v.clone());
+        if constant {
+            self.constants.insert(name.to_string());
+        }
+        Result::Ok(v)
+    }
+
+    // All values are evaluated before any is assigned, so a, b = b, a swaps them
+    fn unpack(
+        &mut self,
+        names: &[String],
+        values: &[Expr],
+        span: (usize, usize),
+    ) -> Result<Value, Error> {
+        let mut results = Vec::with_capacity(values.len());
+        for (name, e) in names.iter().zip(values) {
+            self.assignable(name, span)?;
+            results.push(self.eval(e)?);
+        }
+        let mut text = Vec::with_capacity(names.len());
+        for (name, v) in names.iter().zip(results) {
+            text.push(format!("{} = {}", name, v.format(&self.format)));
+            self.functions.remove(name);
+            self.variables.insert(name.clone(), v);
+        }
+        Result::Ok(Value::Text(text.join(self.format.separator())))
+    }
+
+    // Arguments are evaluated in the scope of the caller, defaults in the scope of the function
+    fn apply(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        keywords: &[(String, Expr)],
+        span: (usize, usize),
+    ) -> Result<Value, Error> {
+        let function = self.user_function(name, span)?.clone();
+        let mut values = Vec::with_capacity(args.len() + keywords.len());
+        for a in args {
+            values.push(self.eval(a)?);
+        }
+        let mut named = Vec::with_capacity(keywords.len());
+        for (k, a) in keywords {
+            named.push((k, self.eval(a)?));
+        }
+        self.depth += 1;
+        let mut saved = Vec::with_capacity(function.params.len());
+        let mut result = Result::Ok(Value::Number(0.0));
+        for (i, (param, default)) in function.params.iter().enumerate() {
+            let value = if i < values.len() {
+                values[i].clone()
+            } else if let Option::Some(v) = named.iter().find(|n| n.0 == param) {
+                v.1.clone()
+            } else if let Option::Some(ref d) = *default {
+                match self.eval(d) {
+                    Result::Ok(v) => v,
+                    Result::Err(e) => {
+                        result = Result::Err(e);
+                        break;
+                    }
+                }
+            } else {
+                let syntax = function.syntax(name);
+                let msg = format!("{} is missing the argument '{}' ({})", name, param, syntax);
+                result = Result::Err(Error::new(&msg, span));
+                break;
+            };
+            saved.push((param, self.set(param, value)));
+        }
+        if result.is_ok() {
+            result = self.eval(&function.body);
+        }
+        for (param, old) in saved.into_iter().rev() {
+            match old {
+                Option::Some(v) => self.set(param, v),
+                Option::None => self.remove(param),
+            };
+        }
+        self.depth -= 1;
+        // The body might come from another input, so errors point to the call
+        result.map_err(|e| Error::new(&e.message, span))
+    }
+
+    fn user_function(&self, name: &str, span: (usize, usize)) -> Result<&UserFunction, Error> {
+        if self.depth >= MAX_DEPTH {
+            let msg = format!("Too many nested calls of {}", name);
+            return Result::Err(Error::new(&msg, span));
+        }
+        match self.functions.get(name) {
+            Option::Some(f) => Result::Ok(f),
+            Option::None => Result::Err(Error::new(&format!("Unknown function '{}'", name), span)),
+        }
+    }
+
+    // Replace calls of user defined functions with their bodies, for simplifying and compiling
+    pub(crate) fn inline(&self, expr: &Expr) -> Result<Expr, Error> {
+        self.inline_(expr, 0)
+    }
+
+    fn inline_(&self, expr: &Expr, depth: usize) -> Result<Expr, Error> {
+        if let Node::Apply(ref name, ref args, ref keywords) = expr.node {
+            if depth >= MAX_DEPTH {
+                let msg = format!("Too many nested calls of {}", name);
+                return Result::Err(Error::new(&msg, expr.span));
+            }
+            let function = self.user_function(name, expr.span)?;
+            let mut bound: Vec<(&str, Expr)> = Vec::with_capacity(function.params.len());
+            for (i, (param, default)) in function.params.iter().enumerate() {
+                let e = if i < args.len() {
+                    self.inline_(&args[i], depth)?
+                } else if let Option::Some(k) = keywords.iter().find(|k| k.0 == *param) {
+                    self.inline_(&k.1, depth)?
+                } else if let Option::Some(ref d) = *default {
+                    substitute(d, &bound, expr.span)
+                } else {
+                    let syntax = function.syntax(name);
+                    let msg = format!("{} is missing the argument '{}' ({})", name, param, syntax);
+                    return Result::Err(Error::new(&msg, expr.span));
+                };
+                bound.push((param, e));
+            }
+            return self.inline_(&substitute(&function.body, &bound, expr.span), depth + 1);
+        }
+        let mut e = expr.clone();
+        for c in e.children_mut() {
+            *c = self.inline_(c, depth)?;
+        }
+        Result::Ok(e)
+    }
+
+    // Evaluate an expression that must result in a number
+    pub fn number(&mut self, expr: &Expr) -> Result<f64, Error> {
+        match self.eval(expr)?.as_number() {
+            Option::Some(x) => Result::Ok(x),
+            Option::None => Result::Err(Error::new("Expected a number", expr.span)),
+        }
+    }
+
+    pub(crate) fn call(
+        &mut self,
+        f: Function,
+        v: &[f64],
+        span: (usize, usize),
+    ) -> Result<f64, Error> {
+        // Left out arguments get the defaults of the function
+        let filled;
+        let v = match f.signature().fill(v) {
+            Option::Some(all) => {
+                filled = all;
+                &filled[..]
+            }
+            Option::None => v,
+        };
+        match f {
+            Function::Log if v.len() == 2 => Result::Ok(v[0].log(v[1])),
+            Function::Log | Function::Ln => Result::Ok(v[0].ln()),
+            Function::Exp => Result::Ok(std::f64::consts::E.powf(v[0])),
+            Function::Abs => Result::Ok(v[0].abs()),
+            Function::Sqrt => {
+                if v[0] < 0.0 {
+                    Result::Err(Error::new(
+                        &format!("Cannot handle negative values ({})", v[0]),
+                        span,
+                    ))
+                } else {
+                    Result::Ok(v[0].sqrt())
+                }
+            }
+            Function::Cos | Function::Sin | Function::Tan => self.trigonometric(f, v[0], span),
+            Function::Asin | Function::Acos => {
+                if (-1.0..=1.0).contains(&v[0]) {
+                    if f == Function::Asin {
+                        Result::Ok(self.angle.from_radians(v[0].asin()))
+                    } else {
+                        Result::Ok(self.angle.from_radians(v[0].acos()))
+                    }
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (-1 <= {} <= 1)", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Atan => Result::Ok(self.angle.from_radians(v[0].atan())),
+            Function::Atan2 => Result::Ok(self.angle.from_radians(v[0].atan2(v[1]))),
+            Function::Sum => Result::Ok(v.iter().sum()),
+            Function::Mean => Result::Ok(v.iter().sum::<f64>() / v.len() as f64),
+            Function::Product => Result::Ok(v.iter().product()),
+            Function::Rnd => Result::Ok(self.rng.gen::<f64>()),
+            Function::Seed => {
+                if v[0] < 0.0 || v[0].fract() != 0.0 {
+                    Result::Err(Error::new("Seed must be a non-negative integer", span))
+                } else {
+                    self.seed(v[0] as u64);
+                    Result::Ok(v[0])
+                }
+            }
+            Function::RandInt => {
+                let (a, b) = (v[0].ceil(), v[1].floor());
+                if a > b {
+                    Result::Err(Error::new(
+                        &format!("No integers in range ({} <= x <= {})", v[0], v[1]),
+                        span,
+                    ))
+                } else if a < -MAX_INT || b >= MAX_INT {
+                    Result::Err(Error::new(
+                        "The bounds of rand_int must be between -2^63 and 2^63",
+                        span,
+                    ))
+                } else {
+                    let range = Uniform::new_inclusive(a as i64, b as i64);
+                    Result::Ok(self.rng.sample(range) as f64)
+                }
+            }
+            Function::Uniform => Result::Ok(v[0] + (v[1] - v[0]) * self.rng.gen::<f64>()),
+            Function::Normal => {
+                if v[1] < 0.0 {
+                    Result::Err(Error::new(
+                        &format!("Standard deviation must be positive ({})", v[1]),
+                        span,
+                    ))
+                } else {
+                    // Box-Muller transform
+                    let u1 = 1.0 - self.rng.gen::<f64>();
+                    let u2 = self.rng.gen::<f64>();
+                    let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::PI * 2.0 * u2).cos();
+                    Result::Ok(v[0] + v[1] * z)
+                }
+            }
+            Function::Exponential => {
+                if v[0] <= 0.0 {
+                    Result::Err(Error::new(
+                        &format!("Rate must be larger than zero ({})", v[0]),
+                        span,
+                    ))
+                } else {
+                    Result::Ok(-(1.0 - self.rng.gen::<f64>()).ln() / v[0])
+                }
+            }
+            Function::Dice => {
+                if v[0] < 1.0 || v[0].fract() != 0.0 || v[1] < 1.0 || v[1].fract() != 0.0 {
+                    Result::Err(Error::new(
+                        "The number of dice and sides must be positive integers",
+                        span,
+                    ))
+                } else if v[0] > MAX_DICE || v[1] >= MAX_INT {
+                    Result::Err(Error::new(
+                        &format!(
+                            "At most {} dice with less than 2^63 sides can be rolled",
+                            MAX_DICE
+                        ),
+                        span,
+                    ))
+                } else {
+                    let sides = Uniform::new_inclusive(1, v[1] as i64);
+                    let mut total = v[2];
+                    for _ in 0..(v[0] as u64) {
+                        total += self.rng.sample(sides) as f64;
+                    }
+                    Result::Ok(total)
+                }
+            }
+            Function::NCr => finite(
+                math::binomial(integer(v[0], span)?, integer(v[1], span)?),
+                span,
+            ),
+            Function::NPr => finite(
+                math::permutations(integer(v[0], span)?, integer(v[1], span)?),
+                span,
+            ),
+            Function::Gcd | Function::Lcm => {
+                let mut r = integer(v[0], span)?;
+                for x in v[1..].iter() {
+                    let x = integer(*x, span)?;
+                    r = if f == Function::Gcd {
+                        math::gcd(r, x)
+                    } else {
+                        match math::lcm(r, x) {
+                            Option::Some(m) => m,
+                            Option::None => {
+                                return Result::Err(Error::new("Result is too large", span))
+                            }
+                        }
+                    };
+                }
+                Result::Ok(r as f64)
+            }
+            Function::IsPrime => {
+                let prime = v[0] >= 0.0
+                    && v[0].fract() == 0.0
+                    && v[0] < u64::MAX as f64
+                    && math::is_prime(v[0] as u64);
+                Result::Ok(if prime { 1.0 } else { 0.0 })
+            }
+            Function::Fib => finite(math::fib(integer(v[0], span)?), span),
+            Function::PowMod => {
+                let m = integer(v[2], span)?;
+                if m == 0 {
+                    Result::Err(Error::new("Modulus must be larger than zero", span))
+                } else {
+                    Result::Ok(math::powmod(integer(v[0], span)?, integer(v[1], span)?, m) as f64)
+                }
+            }
+            Function::Factor => Result::Err(Error::new("Factorization is not a number", span)),
+            Function::Now | Function::Today => {
+                Result::Err(Error::new("A date is not a number", span))
+            }
+            Function::Transpose
+            | Function::Det
+            | Function::Inv
+            | Function::Rank
+            | Function::Trace
+            | Function::Solve
+            | Function::Eig
+            | Function::Identity
+            | Function::Zeros => Result::Err(Error::new("Expected a matrix", span)),
+            // Only parsed as Node::Simplify
+            Function::Simplify => Result::Err(Error::new("Syntax: simplify expr", span)),
+            // Only parsed as Node::Table
+            Function::Table => Result::Err(Error::new(
+                "Syntax: table(expr, x, start, stop, step)",
+                span,
+            )),
+            Function::Sinh => Result::Ok(v[0].sinh()),
+            Function::Cosh => Result::Ok(v[0].cosh()),
+            Function::Tanh => Result::Ok(v[0].tanh()),
+            Function::Asinh => Result::Ok(v[0].asinh()),
+            Function::Acosh => {
+                if v[0] >= 1.0 {
+                    Result::Ok(v[0].acosh())
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (1 <= {})", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Atanh => {
+                if v[0] > -1.0 && v[0] < 1.0 {
+                    Result::Ok(v[0].atanh())
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (-1 < {} < 1)", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Sec | Function::Csc | Function::Cot => {
+                let (d, n) = match f {
+                    Function::Sec => (self.trigonometric(Function::Cos, v[0], span)?, 1.0),
+                    Function::Csc => (self.trigonometric(Function::Sin, v[0], span)?, 1.0),
+                    _ => (
+                        self.trigonometric(Function::Sin, v[0], span)?,
+                        self.trigonometric(Function::Cos, v[0], span)?,
+                    ),
+                };
+                if d == 0.0 {
+                    Result::Err(Error::new(
+                        &format!("{} is undefined for {} {}", f, v[0], self.angle),
+                        span,
+                    ))
+                } else {
+                    Result::Ok(n / d)
+                }
+            }
+            Function::Log2 | Function::Log10 => {
+                if v[0] > 0.0 {
+                    if f == Function::Log2 {
+                        Result::Ok(v[0].log2())
+                    } else {
+                        Result::Ok(v[0].log10())
+                    }
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (0 < {})", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Cbrt => Result::Ok(v[0].cbrt()),
+            Function::NthRoot => {
+                let n = v[1];
+                if n == 0.0 {
+                    Result::Err(Error::new("Cannot take the zeroth root", span))
+                } else if v[0] >= 0.0 {
+                    Result::Ok(v[0].powf(1.0 / n))
+                } else if n.fract() == 0.0 && n % 2.0 != 0.0 {
+                    Result::Ok(-(-v[0]).powf(1.0 / n))
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Cannot take an even root of negative values ({})", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Floor => Result::Ok(v[0].floor()),
+            Function::Ceil => Result::Ok(v[0].ceil()),
+            Function::Trunc => Result::Ok(v[0].trunc()),
+            Function::Round => {
+                // 10^309 is infinite, and no f64 has digits that far
+                if v[1].fract() != 0.0 || v[1].abs() > 308.0 {
+                    Result::Err(Error::new(
+                        &format!(
+                            "The number of digits must be an integer from -308 to 308 ({})",
+                            v[1]
+                        ),
+                        span,
+                    ))
+                } else {
+                    let scale = 10f64.powi(v[1] as i32);
+                    match (v[0] * scale).round() / scale {
+                        // Large numbers already have fewer digits than asked for
+                        x if x.is_finite() => Result::Ok(x),
+                        _ => Result::Ok(v[0]),
+                    }
+                }
+            }
+            Function::Sign => Result::Ok(if v[0] == 0.0 { 0.0 } else { v[0].signum() }),
+            Function::Hypot => Result::Ok(v[0].hypot(v[1])),
+            Function::Gamma | Function::LGamma => {
+                if v[0] <= 0.0 && v[0].fract() == 0.0 {
+                    Result::Err(Error::new(
+                        &format!("{} is undefined for non-positive integers ({})", f, v[0]),
+                        span,
+                    ))
+                } else if f == Function::Gamma {
+                    finite(math::gamma(v[0]), span)
+                } else {
+                    finite(math::lgamma(v[0]), span)
+                }
+            }
+            Function::Beta => {
+                let b = math::beta(v[0], v[1]);
+                if b.is_nan()
+                    || v[0] <= 0.0 && v[0].fract() == 0.0
+                    || v[1] <= 0.0 && v[1].fract() == 0.0
+                {
+                    Result::Err(Error::new(
+                        &format!("beta is undefined for ({}, {})", v[0], v[1]),
+                        span,
+                    ))
+                } else {
+                    finite(b, span)
+                }
+            }
+            Function::Erf => Result::Ok(math::erf(v[0])),
+            Function::Erfc => Result::Ok(math::erfc(v[0])),
+            Function::ErfInv => {
+                if v[0] > -1.0 && v[0] < 1.0 {
+                    Result::Ok(math::erfinv(v[0]))
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (-1 < {} < 1)", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::BesselJ0 => Result::Ok(math::bessel_j0(v[0])),
+            Function::BesselJ1 => Result::Ok(math::bessel_j1(v[0])),
+            Function::BesselJn => {
+                if v[0].fract() != 0.0 || v[0].abs() > i32::MAX as f64 {
+                    Result::Err(Error::new(
+                        &format!("The order must be an integer ({})", v[0]),
+                        span,
+                    ))
+                } else {
+                    Result::Ok(math::bessel_jn(v[0] as i32, v[1]))
+                }
+            }
+            Function::BesselY0 => {
+                if v[0] > 0.0 {
+                    Result::Ok(math::bessel_y0(v[0]))
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (0 < {})", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Zeta => {
+                if v[0] == 1.0 {
+                    Result::Err(Error::new("zeta has a pole at 1", span))
+                } else {
+                    finite(math::zeta(v[0]), span)
+                }
+            }
+            Function::LambertW => {
+                if v[0] >= -(-1f64).exp() {
+                    Result::Ok(math::lambertw(v[0]))
+                } else {
+                    Result::Err(Error::new(
+                        &format!("Value outside range (-1/e <= {})", v[0]),
+                        span,
+                    ))
+                }
+            }
+            Function::Pv | Function::Fv | Function::Pmt | Function::Nper => {
+                let r = match f {
+                    Function::Pv => math::pv(v[0], v[1], v[2], v[3]),
+                    Function::Fv => math::fv(v[0], v[1], v[2], v[3]),
+                    Function::Pmt => math::pmt(v[0], v[1], v[2], v[3]),
+                    _ => math::nper(v[0], v[1], v[2], v[3]),
+                };
+                if r.is_finite() {
+                    Result::Ok(r)
+                } else {
+                    Result::Err(Error::new(&format!("{} has no solution", f), span))
+                }
+            }
+            Function::Rate | Function::Irr => {
+                let r = if f == Function::Rate {
+                    math::rate(v[0], v[1], v[2], v[3])
+                } else {
+                    math::irr(v)
+                };
+                match r {
+                    Option::Some(r) => Result::Ok(r),
+                    Option::None => Result::Err(Error::new(
+                        "Could not find an interest rate (check the signs of the payments)",
+                        span,
+                    )),
+                }
+            }
+            Function::Npv => Result::Ok(math::npv(v[0], &v[1..])),
+            Function::Compound => {
+                let times = v[3];
+                if times <= 0.0 {
+                    Result::Err(Error::new(
+                        &format!("Compounding must happen at least once per year ({})", times),
+                        span,
+                    ))
+                } else {
+                    finite(math::compound(v[0], v[1], v[2], times), span)
+                }
+            }
+            Function::Clamp => {
+                if v[1] > v[2] {
+                    Result::Err(Error::new(
+                        &format!("Minimum is larger than maximum ({} > {})", v[1], v[2]),
+                        span,
+                    ))
+                } else {
+                    Result::Ok(v[0].clamp(v[1], v[2]))
+                }
+            }
+        }
+    }
+}
+
+impl Context {
+    // Multiples of a quarter turn are exact when not using radians, so that sin 180 == 0 in degrees
+    fn trigonometric(&self, f: Function, x: f64, span: (usize, usize)) -> Result<f64, Error> {
+        let quarter = x / self.angle.turn() * 4.0;
+        if self.angle != Angle::Radians && quarter.fract() == 0.0 {
+            let (sin, cos) = match quarter.rem_euclid(4.0) as u8 {
+                0 => (0.0, 1.0),
+                1 => (1.0, 0.0),
+                2 => (0.0, -1.0),
+                _ => (-1.0, 0.0),
+            };
+            return match f {
+                Function::Sin => Result::Ok(sin),
+                Function::Cos => Result::Ok(cos),
+                _ if cos == 0.0 => Result::Err(Error::new(
+                    &format!("Tangent is undefined for {} {}", x, self.angle),
+                    span,
+                )),
+                _ => Result::Ok(sin / cos),
+            };
+        }
+        let x = self.angle.to_radians(x);
+        match f {
+            Function::Sin => Result::Ok(x.sin()),
+            Function::Cos => Result::Ok(x.cos()),
+            _ => Result::Ok(x.tan()),
+        }
+    }
+
+    // Build a matrix from a list of numbers (a row) or a list of rows
+    fn matrix(&mut self, items: &[Expr], span: (usize, usize)) -> Result<Value, Error> {
+        let mut rows: Vec<Vec<f64>> = Vec::with_capacity(items.len());
+        let mut numbers = Vec::new();
+        for item in items {
+            match self.eval(item)? {
+                Value::Matrix(m) if m.rows() == 1 => rows.push(m.row(0).to_vec()),
+                v => match v.as_number() {
+                    Option::Some(x) => numbers.push(x),
+                    Option::None => {
+                        return Result::Err(Error::new(
+                            &format!("Cannot put a {} in a matrix", v.kind()),
+                            item.span,
+                        ))
+                    }
+                },
+            }
+        }
+        if !numbers.is_empty() {
+            if !rows.is_empty() {
+                return Result::Err(Error::new("Cannot mix numbers and rows in a matrix", span));
+            }
+            rows.push(numbers);
+        }
+        match Matrix::from_rows(&rows) {
+            Option::Some(m) => Result::Ok(Value::Matrix(m)),
+            Option::None => Result::Err(Error::new("All rows must have the same length", span)),
+        }
+    }
+
+    // Evaluate an expression that must result in a matrix, numbers are 1x1 matrices
+    fn as_matrix(&mut self, expr: &Expr) -> Result<Matrix, Error> {
+        match self.eval(expr)? {
+            Value::Matrix(m) => Result::Ok(m),
+            v => match v.as_number() {
+                Option::Some(x) => Result::Ok(Matrix::new(1, 1, vec![x])),
+                Option::None => Result::Err(Error::new("Expected a matrix", expr.span)),
+            },
+        }
+    }
+
+    fn linear(&mut self, f: Function, args: &[Expr]) -> Result<Value, Error> {
+        let size = |x: f64, span| -> Result<usize, Error> {
+            match integer(x, span)? {
+                0 => Result::Err(Error::new("A matrix must have at least one row", span)),
+                n if n > MAX_ELEMENTS as u64 => {
+                    Result::Err(Error::new("Matrix is too large", span))
+                }
+                n => Result::Ok(n as usize),
+            }
+        };
+        if let Function::Identity | Function::Zeros = f {
+            let rows = size(self.number(&args[0])?, args[0].span)?;
+            let cols = match args.get(1) {
+                Option::Some(a) => size(self.number(a)?, a.span)?,
+                Option::None => rows,
+            };
+            if rows.saturating_mul(cols) > MAX_ELEMENTS {
+                let span = (args[0].span.0, args[args.len() - 1].span.1);
+                return Result::Err(Error::new("Matrix is too large", span));
+            }
+            return Result::Ok(Value::Matrix(if f == Function::Identity {
+                Matrix::identity(rows)
+            } else {
+                Matrix::zeros(rows, cols)
+            }));
+        }
+        let m = self.as_matrix(&args[0])?;
+        let square = || {
+            Error::new(
+                &format!("{} needs a square matrix ({}x{})", f, m.rows(), m.cols()),
+                args[0].span,
+            )
+        };
+        let singular = || Error::new("The matrix is singular", args[0].span);
+        match f {
+            Function::Transpose => Result::Ok(Value::Matrix(m.transpose())),
+            Function::Rank => Result::Ok(Value::Number(m.rank() as f64)),
+            Function::Det => m.det().map(Value::Number).ok_or_else(square),
+            Function::Trace => m.trace().map(Value::Number).ok_or_else(square),
+            Function::Inv if !m.is_square() => Result::Err(square()),
+            Function::Inv => m.inv().map(Value::Matrix).ok_or_else(singular),
+            Function::Eig if !m.is_square() => Result::Err(square()),
+            Function::Eig => match m.eigenvalues() {
+                Option::Some(v) => Result::Ok(Value::Matrix(Matrix::new(1, v.len(), v))),
+                Option::None => Result::Err(Error::new(
+                    "Eigenvalues are only supported for symmetric matrices",
+                    args[0].span,
+                )),
+            },
+            _ => {
+                if !m.is_square() {
+                    return Result::Err(square());
+                }
+                // A row vector is treated as a column, and the solution is returned as a row
+                let b = self.as_matrix(&args[1])?;
+                let row = b.rows() == 1 && m.rows() > 1;
+                let b = if row { b.transpose() } else { b };
+                if b.rows() != m.rows() {
+                    return Result::Err(Error::new(
+                        &format!("Expected {} values ({}x{})", m.rows(), b.rows(), b.cols()),
+                        args[1].span,
+                    ));
+                }
+                let x = m.solve(&b).ok_or_else(singular)?;
+                Result::Ok(Value::Matrix(if row { x.transpose() } else { x }))
+            }
+        }
+    }
+
+    // Arithmetic with dates, durations, money and matrices
+    fn arithmetic(
+        &self,
+        op: Operator,
+        a: Value,
+        b: Value,
+        l: &Expr,
+        r: &Expr,
+        span: (usize, usize),
+    ) -> Result<Value, Error> {
+        if let Value::Text(_) = a {
+            return Result::Err(Error::new("Expected a number", l.span));
+        }
+        if let Value::Text(_) = b {
+            return Result::Err(Error::new("Expected a number", r.span));
+        }
+        // Amounts in different currencies are converted to the currency of the left side
+        let exchange = |y: f64, from: &str, to: &str| match self.rates.convert(y, from, to) {
+            Result::Ok(y) => Result::Ok(y),
+            Result::Err(msg) => Result::Err(Error::new(&msg, r.span)),
+        };
+        let v = match (op, &a, &b, a.as_number(), b.as_number()) {
+            (Operator::Sub, &Value::Date(x), &Value::Date(y), _, _) => Value::Duration(x - y),
+            (Operator::Add, &Value::Date(x), &Value::Duration(y), _, _)
+            | (Operator::Add, &Value::Duration(y), &Value::Date(x), _, _) => Value::Date(x + y),
+            (Operator::Sub, &Value::Date(x), &Value::Duration(y), _, _) => Value::Date(x - y),
+            (Operator::Add, &Value::Duration(x), &Value::Duration(y), _, _) => {
+                Value::Duration(x + y)
+            }
+            (Operator::Sub, &Value::Duration(x), &Value::Duration(y), _, _) => {
+                Value::Duration(x - y)
+            }
+            (Operator::Mul, &Value::Duration(d), _, _, Option::Some(n))
+            | (Operator::Mul, _, &Value::Duration(d), Option::Some(n), _) => Value::Duration(d * n),
+            (Operator::Div, &Value::Duration(_), &Value::Duration(n), _, _)
+            | (Operator::Div, &Value::Duration(_), _, _, Option::Some(n))
+            | (Operator::Div, &Value::Money(..), &Value::Money(n, _), _, _)
+            | (Operator::Div, &Value::Money(..), _, _, Option::Some(n))
+                if n == 0.0 =>
+            {
+                return Result::Err(Error::new("Division by zero", r.span))
+            }
+            (Operator::Div, &Value::Duration(x), &Value::Duration(y), _, _) => Value::Number(x / y),
+            (Operator::Div, &Value::Duration(d), _, _, Option::Some(n)) => Value::Duration(d / n),
+            (Operator::Add, &Value::Money(x, ref c), &Value::Money(y, ref d), _, _) => {
+                Value::Money(x + exchange(y, d, c)?, c.clone())
+            }
+            (Operator::Sub, &Value::Money(x, ref c), &Value::Money(y, ref d), _, _) => {
+                Value::Money(x - exchange(y, d, c)?, c.clone())
+            }
+            // 100 EUR + 15%
+            (Operator::Add, &Value::Money(x, ref c), _, _, Option::Some(p))
+                if matches!(r.node, Node::Percent(_)) =>
+            {
+                Value::Money(x + x * p, c.clone())
+            }
+            (Operator::Sub, &Value::Money(x, ref c), _, _, Option::Some(p))
+                if matches!(r.node, Node::Percent(_)) =>
+            {
+                Value::Money(x - x * p, c.clone())
+            }
+            (Operator::Mul, &Value::Money(x, ref c), _, _, Option::Some(n))
+            | (Operator::Mul, _, &Value::Money(x, ref c), Option::Some(n), _) => {
+                Value::Money(x * n, c.clone())
+            }
+            (Operator::Div, &Value::Money(x, ref c), &Value::Money(y, ref d), _, _) => {
+                Value::Number(x / exchange(y, d, c)?)
+            }
+            (Operator::Div, &Value::Money(x, ref c), _, _, Option::Some(n)) => {
+                Value::Money(x / n, c.clone())
+            }
+            (Operator::Mul, &Value::Matrix(ref m), _, _, Option::Some(n))
+            | (Operator::Mul, _, &Value::Matrix(ref m), Option::Some(n), _) => {
+                Value::Matrix(m.map(|x| x * n))
+            }
+            (Operator::Div, Value::Matrix(m), _, _, Option::Some(n)) => {
+                Value::Matrix(m.map(|x| x / n))
+            }
+            (Operator::Pow, Value::Matrix(m), _, _, Option::Some(n)) => {
+                if n.fract() != 0.0 || n.abs() > 1e9 {
+                    return Result::Err(Error::new(
+                        &format!("Matrix powers must be integers ({})", n),
+                        r.span,
+                    ));
+                }
+                match m.pow(n as i64) {
+                    Option::Some(p) => Value::Matrix(p),
+                    Option::None if m.is_square() => {
+                        return Result::Err(Error::new("The matrix is singular", l.span))
+                    }
+                    Option::None => {
+                        return Result::Err(Error::new(
+                            "Matrix powers need a square matrix",
+                            l.span,
+                        ))
+                    }
+                }
+            }
+            (
+                Operator::Add | Operator::Sub | Operator::Mul,
+                Value::Matrix(x),
+                Value::Matrix(y),
+                _,
+                _,
+            ) => {
+                let m = match op {
+                    Operator::Add => x.zip(y, |a, b| a + b),
+                    Operator::Sub => x.zip(y, |a, b| a - b),
+                    // A row vector is treated as a column, as in solve, and the result is a row
+                    _ if y.rows() == 1 && x.cols() == y.cols() && x.cols() > 1 => {
+                        x.mul(&y.transpose()).map(|m| m.transpose())
+                    }
+                    _ => x.mul(y),
+                };
+                match m {
+                    Option::Some(m) => Value::Matrix(m),
+                    Option::None => {
+                        return Result::Err(Error::new(
+                            &format!(
+                                "Matrix dimensions do not match ({}x{} {} {}x{})",
+                                x.rows(),
+                                x.cols(),
+                                op,
+                                y.rows(),
+                                y.cols()
+                            ),
+                            span,
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Result::Err(Error::new(
+                    &format!("Cannot compute {} {} {}", a.kind(), op, b.kind()),
+                    span,
+                ))
+            }
+        };
+        Result::Ok(v)
+    }
+}
+
+// Replace the parameters in the body of a function, the rest points to the call
+fn substitute(e: &Expr, bound: &[(&str, Expr)], span: (usize, usize)) -> Expr {
+    if let Node::Variable(ref name) = e.node {
+        if let Option::Some(b) = bound.iter().find(|b| b.0 == name) {
+            return b.1.clone();
+        }
+    }
+    let mut e = e.clone();
+    e.span = span;
+    for c in e.children_mut() {
+        *c = substitute(c, bound, span);
+    }
+    e
+}
+
+pub(crate) fn integer(x: f64, span: (usize, usize)) -> Result<u64, Error> {
+    if x >= 0.0 && x.fract() == 0.0 && x < u64::MAX as f64 {
+        Result::Ok(x as u64)
+    } else {
+        Result::Err(Error::new(
+            &format!("Expected a non-negative integer ({})", x),
+            span,
+        ))
+    }
+}
+
+pub(crate) fn finite(x: f64, span: (usize, usize)) -> Result<f64, Error> {
+    if x.is_finite() {
+        Result::Ok(x)
+    } else {
+        Result::Err(Error::new("Result is too large", span))
+    }
+}
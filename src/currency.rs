@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Exchange rates read from a TOML file that the user keeps up to date, e.g:
+//   base = "EUR"
+//   [rates]
+//   USD = 1.08     # 1 EUR = 1.08 USD
+//   SEK = 11.21
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Rates {
+    base: String,
+    rates: HashMap<String, f64>,
+}
+
+impl Rates {
+    pub fn parse(input: &str) -> Result<Rates, String> {
+        let table = match input.parse::<toml::Table>() {
+            Result::Ok(t) => t,
+            Result::Err(e) => return Result::Err(format!("Invalid exchange rate file: {}", e)),
+        };
+        let base = match table.get("base") {
+            Option::Some(toml::Value::String(s)) if is_code(s) => s.clone(),
+            _ => {
+                return Result::Err(
+                    "The exchange rate file needs a base currency (base = \"EUR\")".to_string(),
+                )
+            }
+        };
+        let mut rates = HashMap::new();
+        if let Option::Some(toml::Value::Table(t)) = table.get("rates") {
+            for (code, rate) in t {
+                let rate = match *rate {
+                    toml::Value::Float(x) => x,
+                    toml::Value::Integer(x) => x as f64,
+                    _ => f64::NAN,
+                };
+                if !(is_code(code) && rate > 0.0 && rate.is_finite()) {
+                    return Result::Err(format!("Invalid exchange rate for '{}'", code));
+                }
+                rates.insert(code.clone(), rate);
+            }
+        }
+        Result::Ok(Rates { base, rates })
+    }
+
+    pub fn load(path: &Path) -> Result<Rates, String> {
+        match std::fs::read_to_string(path) {
+            Result::Ok(s) => Rates::parse(&s),
+            Result::Err(e) => Result::Err(format!("Cannot read {}: {}", path.display(), e)),
+        }
+    }
+
+    // $CALC_RATES or ~/.config/calc/rates.toml
+    pub fn default_path() -> Option<PathBuf> {
+        if let Option::Some(p) = std::env::var_os("CALC_RATES") {
+            return Option::Some(PathBuf::from(p));
+        }
+        let config = match std::env::var_os("XDG_CONFIG_HOME") {
+            Option::Some(p) if !p.is_empty() => PathBuf::from(p),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Option::Some(config.join("calc").join("rates.toml"))
+    }
+
+    // Units of the currency per unit of the base currency
+    pub fn rate(&self, code: &str) -> Option<f64> {
+        if code == self.base {
+            Option::Some(1.0)
+        } else {
+            self.rates.get(code).copied()
+        }
+    }
+
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Result<f64, String> {
+        if from == to {
+            return Result::Ok(amount);
+        }
+        if self.base.is_empty() {
+            return Result::Err("No exchange rates have been loaded".to_string());
+        }
+        let missing = |code: &str| format!("No exchange rate for '{}'", code);
+        let a = self.rate(from).ok_or_else(|| missing(from))?;
+        let b = self.rate(to).ok_or_else(|| missing(to))?;
+        Result::Ok(amount / a * b)
+    }
+}
+
+// Currencies are written with their three letter code, such as EUR or USD
+pub fn is_code(s: &str) -> bool {
+    s.len() == 3 && s.bytes().all(|c| c.is_ascii_uppercase())
+}
+
+// Round to whole cents, half away from zero. The first rounding removes binary
+// representation errors so that 1.005 is rounded like the decimal number it looks like.
+pub fn round(x: f64) -> f64 {
+    ((x * 1e8).round() / 1e6).round() / 100.0
+}
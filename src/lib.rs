@@ -1,22 +1,184 @@
+pub mod ast;
+pub mod chart;
+pub mod color;
+pub mod compile;
+pub mod context;
+pub mod currency;
+pub mod font;
+pub mod math;
+pub mod matrix;
 pub mod parser;
+pub mod plot;
 pub mod scanner;
+pub mod solve;
+pub mod suggest;
+pub mod symbolic;
+pub mod table;
+pub mod time;
+pub mod value;
 
+use chart::{Chart, Curve};
+use context::Context;
 use parser::Parser;
+use plot::Plot;
+use table::{Range, Style};
+use value::{Format, Locale};
 
 pub fn calculate<'a>(input: &'a str) -> Parser<'a> {
     Parser::new(input)
 }
 
 pub fn calculate_print(input: &str) {
-    for res in Parser::new(input) {
+    print(Parser::new(input));
+}
+
+pub fn print(parser: Parser) {
+    print_formatted(parser, &Format::default());
+}
+
+pub fn print_formatted(mut parser: Parser, format: &Format) {
+    let colored = color::enabled();
+    parser.set_color(colored);
+    while let Option::Some(res) = parser.next() {
+        for w in parser.take_warnings() {
+            println!("{}", w);
+        }
         match res {
             Result::Err(s) => {
                 println!("{}", s);
                 break;
             }
             Result::Ok(v) => {
-                println!("{}", v);
+                if colored {
+                    println!("{}", color::bold(&v.format(format)));
+                } else {
+                    println!("{}", v.format(format));
+                }
+            }
+        }
+    }
+}
+
+// Tabulate the last expression over the ranges, earlier expressions can set variables
+pub fn print_sweep(mut parser: Parser, ranges: &[Range], style: Style, format: &Format) {
+    parser.set_color(color::enabled());
+    let names: Vec<&str> = ranges.iter().map(|r| r.name.as_str()).collect();
+    parser.set_variables(&names);
+    let mut last = Option::None;
+    while let Option::Some(res) = parser.parse() {
+        for w in parser.take_warnings() {
+            println!("{}", w);
+        }
+        let expr = match res {
+            Result::Ok(e) => e,
+            Result::Err(s) => {
+                println!("{}", s);
+                return;
+            }
+        };
+        if let Option::Some(e) = last.replace(expr) {
+            if let Result::Err(s) = parser.eval(&e) {
+                println!("{}", s);
+                return;
+            }
+        }
+    }
+    let expr = match last {
+        Option::Some(e) => e,
+        Option::None => return,
+    };
+    match table::tabulate(parser.context_mut(), &expr, ranges) {
+        Result::Ok(t) => println!("{}", t.format(style, format)),
+        Result::Err(s) => println!("Error: {}", s),
+    }
+}
+
+// calc plot "sin x" "cos x" x=-10:10 y=-2:2, where the first range is the variable and
+// the second one the visible range of values
+// Draw in the terminal, or to an SVG or PNG file when there is an output
+pub fn print_plot(
+    args: &[String],
+    mut plot: Plot,
+    mut chart: Chart,
+    output: Option<&str>,
+    context: &Context,
+    locale: &Locale,
+) {
+    let mut functions = Vec::new();
+    let mut ranges = Vec::new();
+    for a in args {
+        if a.contains('=') && a.contains(':') {
+            match Range::interval(a, locale) {
+                Result::Ok(r) => ranges.push(r),
+                Result::Err(e) => {
+                    println!("Error: {}", e);
+                    return;
+                }
+            }
+        } else {
+            functions.push(Curve::parse(a));
+        }
+    }
+    if functions.is_empty() || ranges.len() > 2 {
+        println!("Error: Syntax: plot \"f(x)\" \"(x(t), y(t))\" \"r = f(t)\" ... [x=start:stop] [y=min:max]");
+        return;
+    }
+    let graphs = functions.iter().all(Curve::is_graph);
+    // Parametric and polar curves go around once by default
+    let x = match ranges.first() {
+        Option::Some(r) => r.clone(),
+        Option::None if graphs => Range::parse("x=-10:10").unwrap(),
+        Option::None => Range::new("t", 0.0, context.angle().turn(), 1.0).unwrap(),
+    };
+    if let Option::Some(y) = ranges.get(1) {
+        plot.y = Option::Some((y.start, y.stop));
+    }
+    if x.start >= x.stop || plot.y.is_some_and(|(a, b)| a >= b) {
+        println!("Error: The ranges of a plot must go from a smaller to a larger value");
+        return;
+    }
+    let path = match output {
+        Option::Some(path) => path,
+        Option::None if graphs => {
+            let names: Vec<String> = functions.iter().map(|f| f.to_string()).collect();
+            let names: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+            match plot.plot(&names, &x, context) {
+                Result::Ok(s) => println!("{}", s),
+                Result::Err(e) => println!("{}", e),
+            }
+            return;
+        }
+        Option::None => {
+            println!("Error: Parametric and polar curves can only be drawn to a file (--output chart.svg)");
+            return;
+        }
+    };
+    let mut lines = Vec::with_capacity(functions.len());
+    for f in functions.iter() {
+        match chart::sample(f, &x, context) {
+            Result::Ok(l) => lines.push(l),
+            Result::Err(e) => {
+                println!("{}", e);
+                return;
             }
         }
     }
+    chart.y = plot.y;
+    if graphs {
+        chart.labels.0 = x.name.clone();
+    }
+    let data = match path.rsplit('.').next().map(|e| e.to_lowercase()).as_deref() {
+        Option::Some("svg") => Result::Ok(chart.svg(&lines).into_bytes()),
+        Option::Some("png") => chart.png(&lines),
+        _ => Result::Err(format!(
+            "Error: Unknown image format of '{}' (expected .svg or .png)",
+            path
+        )),
+    };
+    match data.and_then(|d| {
+        std::fs::write(path, d).map_err(|e| format!("Error: Cannot write '{}': {}", path, e))
+    }) {
+        Result::Ok(()) => println!("Saved the chart to {}", path),
+        Result::Err(e) => println!("{}", e),
+    }
 }
@@ -1,25 +1,225 @@
 use std::env;
+use std::path::PathBuf;
+
+use calc::chart::Chart;
+use calc::context::{Angle, Context};
+use calc::currency::Rates;
+use calc::parser::Parser;
+use calc::plot::{Plot, MAX_HEIGHT, MAX_WIDTH};
+use calc::scanner::Scanner;
+use calc::table::{Range, Style};
+use calc::value::{Format, Locale};
+
+// Limit on the size of images, 4096x4096
+const MAX_PIXELS: usize = 1 << 24;
 
 fn main() {
-    let combine: String = combine_args();
-    if combine == "" {
+    let mut seed: Option<u64> = Option::None;
+    let mut angle = Angle::Radians;
+    let mut rates: Option<PathBuf> = Option::None;
+    let mut locale = Locale::default();
+    let mut group = false;
+    let mut sweep: Vec<String> = Vec::new();
+    let mut style = Style::Text;
+    let mut strict = false;
+    let mut plot = Plot::default();
+    let mut chart = Chart::default();
+    let mut size: Option<(usize, usize)> = Option::None;
+    let mut output: Option<String> = Option::None;
+    let mut words: Vec<String> = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Option::Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--seed" => match args.next().and_then(|s| s.parse::<u64>().ok()) {
+                Option::Some(s) => seed = Option::Some(s),
+                Option::None => {
+                    println!("Error: --seed expects a non-negative integer");
+                    return;
+                }
+            },
+            "--rad" => angle = Angle::Radians,
+            "--deg" => angle = Angle::Degrees,
+            "--grad" => angle = Angle::Gradians,
+            "--group" => group = true,
+            "--locale" => match args.next().as_deref().and_then(Locale::from_name) {
+                Option::Some(l) => locale = l,
+                Option::None => {
+                    println!("Error: --locale expects a known language, such as fi or en_US");
+                    return;
+                }
+            },
+            "--sweep" => match args.next() {
+                Option::Some(r) if sweep.len() < 2 => sweep.push(r),
+                _ => {
+                    println!("Error: --sweep expects a range (x=0:10:0.5), at most twice");
+                    return;
+                }
+            },
+            "--strict" => strict = true,
+            "--csv" => style = Style::Csv,
+            "--json" => style = Style::Json,
+            "--ascii" => plot.ascii = true,
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Option::Some(s) => size = Option::Some(s),
+                Option::None => {
+                    println!("Error: --size expects the size of a plot, such as 80x24");
+                    return;
+                }
+            },
+            "--output" => match args.next() {
+                Option::Some(path) => output = Option::Some(path),
+                Option::None => {
+                    println!("Error: --output expects a file, such as chart.svg");
+                    return;
+                }
+            },
+            "--rates" => match args.next() {
+                Option::Some(path) => rates = Option::Some(PathBuf::from(path)),
+                Option::None => {
+                    println!("Error: --rates expects a file");
+                    return;
+                }
+            },
+            _ => words.push(arg),
+        }
+    }
+    let format = Format::with_locale(&locale, group);
+    // The ranges are parsed once the locale is known, x=0:1:0,5 with a decimal comma
+    let mut ranges = Vec::with_capacity(sweep.len());
+    for s in sweep {
+        match Range::parse_with(&s, &locale) {
+            Result::Ok(r) => ranges.push(r),
+            Result::Err(e) => {
+                println!("Error: {}", e);
+                return;
+            }
+        }
+    }
+    // A script file, with one expression per line
+    let combine: String = match words.as_slice() {
+        [path] if path.ends_with(".calc") => match std::fs::read_to_string(path) {
+            Result::Ok(s) => s,
+            Result::Err(e) => {
+                println!("Error: Cannot read '{}': {}", path, e);
+                return;
+            }
+        },
+        _ => words.join(" "),
+    };
+    if combine.is_empty() {
         print_help();
     } else {
-        calc::calculate_print(&combine);
+        let mut context = match seed {
+            Option::Some(s) => Context::seeded(s),
+            Option::None => Context::new(),
+        };
+        context.set_angle(angle);
+        context.set_format(format);
+        // The default exchange rate file is optional, but an explicitly given one is not
+        let path = match rates {
+            Option::Some(p) => Option::Some(p),
+            Option::None => Rates::default_path().filter(|p| p.exists()),
+        };
+        if let Option::Some(path) = path {
+            match Rates::load(&path) {
+                Result::Ok(r) => context.set_rates(r),
+                Result::Err(e) => {
+                    println!("Error: {}", e);
+                    return;
+                }
+            }
+        }
+        if words[0] == "plot" {
+            // The size is in characters in the terminal and in pixels for files
+            match (size, &output) {
+                (Option::Some((w, h)), Option::Some(_))
+                    if w < 200 || h < 150 || w.checked_mul(h).is_none_or(|p| p > MAX_PIXELS) =>
+                {
+                    println!(
+                        "Error: The size of a chart must be from 200x150 to {} pixels",
+                        MAX_PIXELS
+                    );
+                    return;
+                }
+                (Option::Some((w, h)), Option::Some(_)) => {
+                    chart.width = w;
+                    chart.height = h;
+                }
+                (Option::Some((w, h)), Option::None) if w > MAX_WIDTH || h > MAX_HEIGHT => {
+                    println!(
+                        "Error: The size of a plot in the terminal can be at most {}x{}",
+                        MAX_WIDTH, MAX_HEIGHT
+                    );
+                    return;
+                }
+                (Option::Some((w, h)), Option::None) => {
+                    plot.width = w;
+                    plot.height = h;
+                }
+                _ => {}
+            }
+            plot.color = calc::color::enabled();
+            calc::print_plot(
+                &words[1..],
+                plot,
+                chart,
+                output.as_deref(),
+                &context,
+                &locale,
+            );
+            return;
+        }
+        let mut parser = Parser::with_context(Scanner::with_locale(&combine, &locale), context);
+        parser.set_strict(strict);
+        if ranges.is_empty() {
+            calc::print_formatted(parser, &format);
+        } else {
+            calc::print_sweep(parser, &ranges, style, &format);
+        }
     }
 }
 
-fn combine_args() -> String {
-    let args: Vec<String> = env::args().collect();
-    return args[1..].join(" ");
-}
-
 fn print_help() {
     let exe = env::args().next().unwrap();
     println!("Commandline calculator written in Rust.");
-    println!("");
+    println!();
     println!("Try running it with something to calculate!");
     println!("Example:    {} 2 + 2 - cos pi", exe);
-    println!("");
+    println!("Script:     {} budget.calc", exe);
+    println!();
+    println!("Options:");
+    println!("  --seed N    Seed the random number generator");
+    println!("  --deg       Use degrees for trigonometric functions (also --rad, --grad)");
+    println!("  --group     Group digits by thousands (1,234,567.5)");
+    println!("  --locale L  Decimal comma and ; between values for languages such as fi or de");
+    println!("  --strict    Require explicit operators (2x is allowed, but not 2 3 or sin x y)");
+    println!("  --rates F   Exchange rates for currencies (default: ~/.config/calc/rates.toml)");
+    println!(
+        "  --sweep R   Tabulate the expression over a range, such as x=0:10:0.5 (up to twice)"
+    );
+    println!("  --csv       Print the --sweep table as CSV (also --json)");
+    println!();
+    println!(
+        "Plotting:   {} plot \"sin x / x\" \"cos x\" x=-10:10 [y=-1:1]",
+        exe
+    );
+    println!("  --ascii     Draw with ASCII characters instead of braille dots");
+    println!("  --size WxH  Size of the plot in characters (default 72x20)");
+    println!(
+        "  --output F  Save the plot as an SVG or PNG image (size in pixels, default 800x500)"
+    );
+    println!("              Also parametric \"(cos t, sin t)\" and polar \"r = 1 + cos t\" curves");
+    println!();
     println!("For more information see: https://github.com/Aggrathon/RustCalculator");
 }
+
+// "80x24" into (80, 24)
+fn parse_size(s: &str) -> Option<(usize, usize)> {
+    let (w, h) = s.split_once('x')?;
+    let (w, h) = (w.parse::<usize>().ok()?, h.parse::<usize>().ok()?);
+    if w >= 2 && h >= 2 {
+        Option::Some((w, h))
+    } else {
+        Option::None
+    }
+}
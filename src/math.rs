@@ -0,0 +1,426 @@
+// Integer, special and financial functions that are not part of the standard library
+
+// The gamma function, exact for positive integers
+pub fn gamma(x: f64) -> f64 {
+    if x.fract() == 0.0 {
+        if x <= 0.0 {
+            return f64::NAN;
+        }
+        if x <= 171.0 {
+            let mut r = 1.0;
+            let mut i = 2.0;
+            while i < x {
+                r *= i;
+                i += 1.0;
+            }
+            return r;
+        }
+        return f64::INFINITY;
+    }
+    libm::tgamma(x)
+}
+
+// The natural logarithm of the absolute value of the gamma function
+pub fn lgamma(x: f64) -> f64 {
+    if x <= 0.0 && x.fract() == 0.0 {
+        return f64::INFINITY;
+    }
+    libm::lgamma(x)
+}
+
+pub fn beta(a: f64, b: f64) -> f64 {
+    if a > 0.0 && b > 0.0 {
+        (lgamma(a) + lgamma(b) - lgamma(a + b)).exp()
+    } else {
+        gamma(a) * gamma(b) / gamma(a + b)
+    }
+}
+
+pub fn erf(x: f64) -> f64 {
+    libm::erf(x)
+}
+
+pub fn erfc(x: f64) -> f64 {
+    libm::erfc(x)
+}
+
+// Inverse of the error function, refined from an initial guess with Halley's method
+pub fn erfinv(y: f64) -> f64 {
+    if y <= -1.0 || y >= 1.0 {
+        return if y == 1.0 {
+            f64::INFINITY
+        } else if y == -1.0 {
+            f64::NEG_INFINITY
+        } else {
+            f64::NAN
+        };
+    }
+    if y == 0.0 {
+        return 0.0;
+    }
+    // Winitzki's approximation, accurate to about 2e-3
+    let a = 0.147;
+    let l = (1.0 - y * y).ln();
+    let t = 2.0 / (std::f64::consts::PI * a) + l / 2.0;
+    let mut x = y.signum() * ((t * t - l / a).sqrt() - t).sqrt();
+    for _ in 0..4 {
+        let err = if x.abs() < 1.0 {
+            erf(x) - y
+        } else {
+            y.signum() * ((1.0 - y.abs()) - erfc(x.abs()))
+        };
+        let d = 2.0 / std::f64::consts::PI.sqrt() * (-x * x).exp();
+        x -= err / (d + x * err);
+    }
+    x
+}
+
+pub fn bessel_j0(x: f64) -> f64 {
+    libm::j0(x)
+}
+
+pub fn bessel_j1(x: f64) -> f64 {
+    libm::j1(x)
+}
+
+pub fn bessel_jn(n: i32, x: f64) -> f64 {
+    libm::jn(n, x)
+}
+
+pub fn bessel_y0(x: f64) -> f64 {
+    libm::y0(x)
+}
+
+// Riemann zeta function, using Borwein's algorithm for the alternating zeta function
+// and the functional equation for negative values
+pub fn zeta(s: f64) -> f64 {
+    if s == 1.0 {
+        return f64::NAN;
+    }
+    if s < 0.0 {
+        if s % 2.0 == 0.0 {
+            return 0.0;
+        }
+        let pi = std::f64::consts::PI;
+        return 2f64.powf(s)
+            * pi.powf(s - 1.0)
+            * (pi * s / 2.0).sin()
+            * gamma(1.0 - s)
+            * zeta(1.0 - s);
+    }
+    if s >= 60.0 {
+        return 1.0;
+    }
+    const N: usize = 40;
+    let mut d = [0.0; N + 1];
+    let mut term = 1.0;
+    let mut sum = term;
+    d[0] = sum;
+    for (i, di) in d.iter_mut().enumerate().skip(1) {
+        let i = i as f64;
+        let n = N as f64;
+        term *= 4.0 * (n + i - 1.0) * (n - i + 1.0) / ((2.0 * i - 1.0) * (2.0 * i));
+        sum += term;
+        *di = sum;
+    }
+    let mut eta = 0.0;
+    for k in 0..N {
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        eta += sign * (d[k] - d[N]) / (k as f64 + 1.0).powf(s);
+    }
+    eta /= -d[N];
+    eta / -(std::f64::consts::LN_2 * (1.0 - s)).exp_m1()
+}
+
+// Principal branch of the Lambert W function (solves w * e^w = x)
+pub fn lambertw(x: f64) -> f64 {
+    let branch = -(-1f64).exp();
+    if x < branch {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return 0.0;
+    }
+    let mut w = if x < -0.25 {
+        // Series expansion around the branch point
+        let p = (2.0 * (std::f64::consts::E * x + 1.0)).max(0.0).sqrt();
+        -1.0 + p - p * p / 3.0 + 11.0 / 72.0 * p * p * p
+    } else if x < 3.0 {
+        x.ln_1p() * 0.8
+    } else {
+        let l = x.ln();
+        l - l.ln()
+    };
+    if (w + 1.0).abs() < 1e-9 {
+        return w;
+    }
+    for _ in 0..64 {
+        let ew = w.exp();
+        let f = w * ew - x;
+        let step = f / (ew * (w + 1.0) - (w + 2.0) * f / (2.0 * w + 2.0));
+        w -= step;
+        if step.abs() <= 1e-15 * w.abs().max(1e-300) {
+            break;
+        }
+    }
+    w
+}
+
+// n!! = n * (n-2) * (n-4) * ...
+pub fn double_factorial(n: u64) -> f64 {
+    let mut r = 1.0;
+    let mut i = n;
+    // Stop once the result has overflowed, so that huge n does not take forever
+    while i > 1 && r < f64::INFINITY {
+        r *= i as f64;
+        i -= 2;
+    }
+    r
+}
+
+pub fn binomial(n: u64, k: u64) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let k = k.min(n - k);
+    let mut r = 1.0;
+    for i in 0..k {
+        r = r * (n - i) as f64 / (i + 1) as f64;
+        if r == f64::INFINITY {
+            break;
+        }
+    }
+    r.round()
+}
+
+pub fn permutations(n: u64, k: u64) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let mut r = 1.0;
+    for i in (n - k + 1)..=n {
+        r *= i as f64;
+        if r == f64::INFINITY {
+            break;
+        }
+    }
+    r
+}
+
+pub fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// None if the result does not fit in 64 bits
+pub fn lcm(a: u64, b: u64) -> Option<u64> {
+    if a == 0 || b == 0 {
+        Option::Some(0)
+    } else {
+        (a / gcd(a, b)).checked_mul(b)
+    }
+}
+
+pub fn fib(n: u64) -> f64 {
+    // fib(1477) is larger than f64::MAX
+    if n > 1476 {
+        return f64::INFINITY;
+    }
+    let (mut a, mut b) = (0.0, 1.0);
+    for _ in 0..n {
+        let c = a + b;
+        a = b;
+        b = c;
+    }
+    a
+}
+
+fn mulmod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+pub fn powmod(base: u64, exp: u64, m: u64) -> u64 {
+    let mut r = 1 % m;
+    let mut b = base % m;
+    let mut e = exp;
+    while e > 0 {
+        if e & 1 == 1 {
+            r = mulmod(r, b, m);
+        }
+        b = mulmod(b, b, m);
+        e >>= 1;
+    }
+    r
+}
+
+// Deterministic Miller-Rabin for all 64 bit integers
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37].iter() {
+        if n.is_multiple_of(*p) {
+            return n == *p;
+        }
+    }
+    let mut d = n - 1;
+    let mut s = 0;
+    while d.is_multiple_of(2) {
+        d /= 2;
+        s += 1;
+    }
+    'witness: for a in [2u64, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37].iter() {
+        let mut x = powmod(*a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mulmod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+// Pollard's rho, finds a non-trivial divisor of a composite number
+fn pollard_rho(n: u64) -> u64 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+    let mut c = 1;
+    loop {
+        let f = |x: u64| (mulmod(x, x, n) + c) % n;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+        c += 1;
+    }
+}
+
+// Prime factorization as (prime, exponent) pairs in increasing order
+pub fn factorize(n: u64) -> Vec<(u64, u32)> {
+    let mut primes = Vec::new();
+    let mut stack = vec![n];
+    while let Option::Some(m) = stack.pop() {
+        if m == 1 {
+            continue;
+        }
+        if is_prime(m) {
+            primes.push(m);
+        } else {
+            let d = pollard_rho(m);
+            stack.push(d);
+            stack.push(m / d);
+        }
+    }
+    primes.sort_unstable();
+    let mut factors: Vec<(u64, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Option::Some(&mut (q, ref mut e)) if q == p => *e += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+// Time value of money, with the usual sign convention: money paid out is negative.
+// All functions solve pv * (1+r)^n + pmt * ((1+r)^n - 1) / r + fv = 0 for one of the variables.
+
+// Growth of a single payment over n periods
+fn growth(rate: f64, n: f64) -> f64 {
+    (1.0 + rate).powf(n)
+}
+
+// Future value of a series of payments of one per period
+fn annuity(rate: f64, n: f64) -> f64 {
+    if rate == 0.0 {
+        n
+    } else {
+        (growth(rate, n) - 1.0) / rate
+    }
+}
+
+pub fn fv(rate: f64, n: f64, pmt: f64, pv: f64) -> f64 {
+    -(pv * growth(rate, n) + pmt * annuity(rate, n))
+}
+
+pub fn pv(rate: f64, n: f64, pmt: f64, fv: f64) -> f64 {
+    -(fv + pmt * annuity(rate, n)) / growth(rate, n)
+}
+
+pub fn pmt(rate: f64, n: f64, pv: f64, fv: f64) -> f64 {
+    -(pv * growth(rate, n) + fv) / annuity(rate, n)
+}
+
+pub fn nper(rate: f64, pmt: f64, pv: f64, fv: f64) -> f64 {
+    if rate == 0.0 {
+        -(pv + fv) / pmt
+    } else {
+        ((pmt - fv * rate) / (pmt + pv * rate)).ln() / rate.ln_1p()
+    }
+}
+
+// The interest rate per period, None if the iteration does not converge
+pub fn rate(n: f64, pmt: f64, pv: f64, fv: f64) -> Option<f64> {
+    newton(0.1, |r| pv * growth(r, n) + pmt * annuity(r, n) + fv)
+}
+
+// Net present value of cash flows at the end of each period
+pub fn npv(rate: f64, flows: &[f64]) -> f64 {
+    flows
+        .iter()
+        .enumerate()
+        .map(|(i, v)| v / growth(rate, i as f64 + 1.0))
+        .sum()
+}
+
+// Internal rate of return, the first cash flow is at time zero
+pub fn irr(flows: &[f64]) -> Option<f64> {
+    newton(0.1, |r| {
+        flows
+            .iter()
+            .enumerate()
+            .map(|(i, v)| v / growth(r, i as f64))
+            .sum()
+    })
+}
+
+// Compound interest compounded a number of times per year
+pub fn compound(principal: f64, rate: f64, years: f64, times: f64) -> f64 {
+    principal * growth(rate / times, years * times)
+}
+
+// Newton's method with a numerical derivative, for finding interest rates (above -100%)
+fn newton<F: Fn(f64) -> f64>(guess: f64, f: F) -> Option<f64> {
+    let mut x = guess;
+    for _ in 0..100 {
+        let y = f(x);
+        let h = 1e-7 * x.abs().max(1e-3);
+        let d = (f(x + h) - f(x - h)) / (2.0 * h);
+        if !y.is_finite() || !d.is_finite() || d == 0.0 {
+            return Option::None;
+        }
+        let mut next = x - y / d;
+        if next <= -1.0 {
+            next = (x - 1.0) / 2.0;
+        }
+        if (next - x).abs() <= 1e-12 * next.abs().max(1.0) {
+            return Option::Some(next);
+        }
+        x = next;
+    }
+    Option::None
+}
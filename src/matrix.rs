@@ -0,0 +1,289 @@
+// Dense matrices of floats, stored row by row.
+// Operations return None when the shapes do not match or when the matrix is singular.
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, cols: usize, data: Vec<f64>) -> Matrix {
+        assert_eq!(rows * cols, data.len());
+        Matrix { rows, cols, data }
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix::new(rows, cols, vec![0.0; rows * cols])
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        let mut m = Matrix::zeros(n, n);
+        for i in 0..n {
+            m.data[i * n + i] = 1.0;
+        }
+        m
+    }
+
+    // None if the rows have different lengths
+    pub fn from_rows(rows: &[Vec<f64>]) -> Option<Matrix> {
+        let cols = rows.first().map_or(0, |r| r.len());
+        if rows.iter().any(|r| r.len() != cols) {
+            return Option::None;
+        }
+        Option::Some(Matrix::new(rows.len(), cols, rows.concat()))
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row * self.cols + col]
+    }
+
+    pub fn row(&self, row: usize) -> &[f64] {
+        &self.data[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for c in 0..self.cols {
+            for r in 0..self.rows {
+                data.push(self.get(r, c));
+            }
+        }
+        Matrix::new(self.cols, self.rows, data)
+    }
+
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Matrix {
+        Matrix::new(
+            self.rows,
+            self.cols,
+            self.data.iter().map(|&x| f(x)).collect(),
+        )
+    }
+
+    // Elementwise combination of two matrices with the same shape
+    pub fn zip<F: Fn(f64, f64) -> f64>(&self, other: &Matrix, f: F) -> Option<Matrix> {
+        if self.rows != other.rows || self.cols != other.cols {
+            return Option::None;
+        }
+        let data = self.data.iter().zip(other.data.iter());
+        Option::Some(Matrix::new(
+            self.rows,
+            self.cols,
+            data.map(|(&a, &b)| f(a, b)).collect(),
+        ))
+    }
+
+    pub fn mul(&self, other: &Matrix) -> Option<Matrix> {
+        if self.cols != other.rows {
+            return Option::None;
+        }
+        let mut m = Matrix::zeros(self.rows, other.cols);
+        for r in 0..self.rows {
+            for c in 0..other.cols {
+                m.data[r * other.cols + c] = (0..self.cols)
+                    .map(|k| self.get(r, k) * other.get(k, c))
+                    .sum();
+            }
+        }
+        Option::Some(m)
+    }
+
+    // Integer powers, negative powers use the inverse
+    pub fn pow(&self, n: i64) -> Option<Matrix> {
+        if !self.is_square() {
+            return Option::None;
+        }
+        let mut base = if n < 0 { self.inv()? } else { self.clone() };
+        let mut n = n.unsigned_abs();
+        let mut result = Matrix::identity(self.rows);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.mul(&base)?;
+            }
+            base = base.mul(&base)?;
+            n >>= 1;
+        }
+        Option::Some(result)
+    }
+
+    pub fn trace(&self) -> Option<f64> {
+        if self.is_square() {
+            Option::Some((0..self.rows).map(|i| self.get(i, i)).sum())
+        } else {
+            Option::None
+        }
+    }
+
+    // Values smaller than this are treated as zero during elimination
+    fn tolerance(&self) -> f64 {
+        let max = self.data.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+        max * self.rows.max(self.cols) as f64 * f64::EPSILON * 16.0
+    }
+
+    // Reduce to row echelon form with partial pivoting, returns the pivot columns and
+    // the number of row swaps
+    fn eliminate(&mut self, cols: usize) -> (Vec<usize>, usize) {
+        let tol = self.tolerance();
+        let mut pivots = Vec::new();
+        let mut swaps = 0;
+        let mut row = 0;
+        for col in 0..cols {
+            if row == self.rows {
+                break;
+            }
+            let best = (row..self.rows)
+                .max_by(|&a, &b| self.get(a, col).abs().total_cmp(&self.get(b, col).abs()))
+                .unwrap_or(row);
+            if self.get(best, col).abs() <= tol {
+                continue;
+            }
+            if best != row {
+                for c in 0..self.cols {
+                    self.data.swap(best * self.cols + c, row * self.cols + c);
+                }
+                swaps += 1;
+            }
+            for r in (row + 1)..self.rows {
+                let factor = self.get(r, col) / self.get(row, col);
+                if factor != 0.0 {
+                    for c in col..self.cols {
+                        self.data[r * self.cols + c] -= factor * self.get(row, c);
+                    }
+                }
+            }
+            pivots.push(col);
+            row += 1;
+        }
+        (pivots, swaps)
+    }
+
+    pub fn det(&self) -> Option<f64> {
+        if !self.is_square() {
+            return Option::None;
+        }
+        let mut m = self.clone();
+        let (pivots, swaps) = m.eliminate(self.cols);
+        if pivots.len() < self.rows {
+            return Option::Some(0.0);
+        }
+        let det: f64 = (0..self.rows).map(|i| m.get(i, i)).product();
+        Option::Some(if swaps % 2 == 0 { det } else { -det })
+    }
+
+    pub fn rank(&self) -> usize {
+        self.clone().eliminate(self.cols).0.len()
+    }
+
+    // Solve self * x = b, None if self is singular or the shapes do not match
+    pub fn solve(&self, b: &Matrix) -> Option<Matrix> {
+        if !self.is_square() || b.rows != self.rows {
+            return Option::None;
+        }
+        let n = self.rows;
+        let mut aug = Matrix::zeros(n, n + b.cols);
+        for r in 0..n {
+            for c in 0..n {
+                aug.data[r * aug.cols + c] = self.get(r, c);
+            }
+            for c in 0..b.cols {
+                aug.data[r * aug.cols + n + c] = b.get(r, c);
+            }
+        }
+        let (pivots, _) = aug.eliminate(n);
+        if pivots.len() < n {
+            return Option::None;
+        }
+        // Back substitution
+        let mut x = Matrix::zeros(n, b.cols);
+        for c in 0..b.cols {
+            for r in (0..n).rev() {
+                let sum: f64 = ((r + 1)..n).map(|k| aug.get(r, k) * x.get(k, c)).sum();
+                x.data[r * b.cols + c] = (aug.get(r, n + c) - sum) / aug.get(r, r);
+            }
+        }
+        Option::Some(x)
+    }
+
+    pub fn inv(&self) -> Option<Matrix> {
+        self.solve(&Matrix::identity(self.rows))
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        let tol = self.tolerance().max(f64::EPSILON);
+        self.is_square()
+            && (0..self.rows)
+                .all(|r| (0..r).all(|c| (self.get(r, c) - self.get(c, r)).abs() <= tol))
+    }
+
+    // Eigenvalues of a symmetric matrix in increasing order, using Jacobi rotations
+    pub fn eigenvalues(&self) -> Option<Vec<f64>> {
+        if !self.is_symmetric() {
+            return Option::None;
+        }
+        let n = self.rows;
+        let mut a = self.clone();
+        for _ in 0..100 {
+            let off: f64 = (0..n)
+                .flat_map(|r| (0..n).filter(move |&c| c != r).map(move |c| (r, c)))
+                .map(|(r, c)| a.get(r, c).powi(2))
+                .sum();
+            if off <= f64::EPSILON * f64::EPSILON {
+                break;
+            }
+            for p in 0..n {
+                for q in (p + 1)..n {
+                    let apq = a.get(p, q);
+                    if apq == 0.0 {
+                        continue;
+                    }
+                    let theta = (a.get(q, q) - a.get(p, p)) / (2.0 * apq);
+                    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                    let c = 1.0 / (t * t + 1.0).sqrt();
+                    let s = t * c;
+                    for k in 0..n {
+                        let (akp, akq) = (a.get(k, p), a.get(k, q));
+                        a.data[k * n + p] = c * akp - s * akq;
+                        a.data[k * n + q] = s * akp + c * akq;
+                    }
+                    for k in 0..n {
+                        let (apk, aqk) = (a.get(p, k), a.get(q, k));
+                        a.data[p * n + k] = c * apk - s * aqk;
+                        a.data[q * n + k] = s * apk + c * aqk;
+                    }
+                }
+            }
+        }
+        let mut values: Vec<f64> = (0..n).map(|i| a.get(i, i)).collect();
+        values.sort_by(f64::total_cmp);
+        Option::Some(values)
+    }
+}
+
+// Row vectors are written [1, 2, 3] and other matrices [[1, 2], [3, 4]]
+impl std::fmt::Display for Matrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let row = |r: usize| {
+            let items: Vec<String> = self.row(r).iter().map(|x| x.to_string()).collect();
+            format!("[{}]", items.join(", "))
+        };
+        if self.rows == 1 {
+            write!(f, "{}", row(0))
+        } else {
+            let rows: Vec<String> = (0..self.rows).map(row).collect();
+            write!(f, "[{}]", rows.join(", "))
+        }
+    }
+}
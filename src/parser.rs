@@ -1,11 +1,30 @@
 use std;
 
-use crate::scanner::{Function, Scanner, Token};
+use crate::ast::{Expr, Node, Operator};
+use crate::color;
+use crate::context::{Angle, Context, Error};
+use crate::currency;
+use crate::scanner::{self, Command, Function, Param, Scanner, Token};
+use crate::suggest;
+use crate::symbolic::Notation;
+use crate::table::Style;
+use crate::time;
+use crate::value::Value;
 
 /*
 ___Pattern table___
+Statement
+    mode Unit
+    const Text = Expr
+    vars                    // every variable and function with its value
+    who                     // only the names
+    del Text ...
+    clear
+    Text, Text, ... = Expr, Expr, ...   // as many values as names
+    Expr
 Expr
     Term Expr'
+    Term Expr' in Unit      // conversion
     , Expr
 Expr'
     + Term Expr'
@@ -17,20 +36,41 @@ Term'
     * Factor Term'
     / Factor Term'
     % Factor Term'
-    f/(/n/t Factor Term     // implicit multiplication
+    % Term'                 // percent, when % is not followed by a value
+    % of Factor Term'       // percent of
+    f/(/n/t Factor Term     // implicit multiplication (only 2x and 3(x+1) when strict)
+    Duration Duration Term' // 3h 25min == 3h + 25min
     empty
+Operand
+    Func
+    Number Factor           // a coefficient, sin 2x == sin(2x)
 Factor
     Func Factor'
 Factor'
     ^ Func Factor'
     ! Factor'
+    !! Factor'
     empty
 Func
-    f1 Func
-    f2 (Expr, Expr)
-    f3 (Expr, ...)          // aggregation
+    f                       // without parameters, such as rnd
+    f Operand               // a single argument, such as sin x
+    f ()
+    f (Expr, ...)           // checked against the parameters, such as round(x, digits=0)
+    f (Expr, ..., Text = Expr, ...)     // keyword arguments, such as log(8, base=2)
+    solve Expr = Expr       // equation, the = is not an assignment
+    solve Expr = Expr for Text
+    simplify Expr
+    simplify Expr in Notation   // latex or unicode
+    table(Expr, Text, Expr, Expr, Expr)         // tabulate over start, stop and step
+    table(Expr, Text, Expr, Expr, Expr, ...) in Style  // a second variable, csv or json
+    dice("NdM+K")
     Value
+    Value °                 // degrees
+    Value Unit              // duration, such as 90 days
+    Value Currency          // money, such as 120 EUR
 Value
+    Date                    // 2026-10-18 or 2026-10-18T12:30
+    [ Expr, ... ]           // matrix, [[1, 2], [3, 4]]
     ( Expr )
     | Expr |
     - Number
@@ -39,41 +79,288 @@ Value
 Id
     Text
     Text = Expr
+    Text += Expr            // also -=, *=, /=, %= and ^=
+    Text (Text, ..., Text = Expr, ...) = Expr   // user defined function with defaults
+    Text (Expr, ..., Text = Expr, ...)          // call of a user defined function
 */
 
+// Positional and then keyword arguments of a call
+type Arguments = (Vec<Expr>, Vec<(String, Expr)>);
+
 pub struct Parser<'a> {
     scanner: Scanner<'a>,
-    lexiographic_table: std::collections::HashMap<&'a str, f64>,
+    context: Context,
+    color: bool,
+    // Inside solve and simplify, where names are unknowns and = is not an assignment
+    symbolic: bool,
+    // Only allow implicit multiplication for coefficients such as 2x
+    strict: bool,
+    // Parameters of the function that is being defined
+    locals: Vec<String>,
+    // Implicit multiplications that strict mode would reject
+    warnings: Vec<String>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Parser<'a> {
-        Parser {
-            scanner: Scanner::new(input),
-            lexiographic_table: std::collections::HashMap::new(),
-        }
+        Parser::from(Scanner::new(input))
     }
 
     pub fn from(input: Scanner<'a>) -> Parser<'a> {
+        Parser::with_context(input, Context::new())
+    }
+
+    pub fn with_context(input: Scanner<'a>, context: Context) -> Parser<'a> {
         Parser {
             scanner: input,
-            lexiographic_table: std::collections::HashMap::new(),
+            context,
+            color: false,
+            symbolic: false,
+            strict: false,
+            locals: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    pub fn into_context(self) -> Context {
+        self.context
+    }
+
+    // Render error diagnostics with ANSI colors
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    // Reject implicit multiplication, except a number directly followed by a name or
+    // parenthesis (2x, 3(x+1)). Juxtaposition such as 2 3, e pi or sin x y is an error.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    // Warnings about the expressions parsed so far, such as 2 3 read as 2 * 3
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // Names that are given values later, such as the variables of --sweep, and are not
+    // read as units (3s over s=0:2 is 3 * s)
+    pub fn set_variables(&mut self, names: &[&str]) {
+        self.locals = names.iter().map(|s| s.to_string()).collect();
+    }
+
+    // Parse the next expression without evaluating it
+    pub fn parse(&mut self) -> Option<Result<Expr, String>> {
+        while self.scanner.peek() == Token::Comma {
+            self.scanner.next();
+        }
+        let res = match self.scanner.peek() {
+            Token::END => return Option::None,
+            Token::Command(_) => self.command(),
+            Token::Text(_) if self.unpacking() => self.unpack(),
+            _ => self.expr(),
+        };
+        // An expression ends at anything it does not recognize, which would otherwise be lost
+        let res = match res {
+            Result::Ok(_) if self.scanner.peek() == Token::Unknown => {
+                self.scanner.next();
+                self.error("Unrecognized input")
+            }
+            _ => res,
+        };
+        match res {
+            Result::Ok(e) => Option::Some(Result::Ok(e)),
+            Result::Err(e) => {
+                loop {
+                    if let Token::END | Token::Comma = self.scanner.next() {
+                        break;
+                    }
+                }
+                Option::Some(Result::Err(e))
+            }
+        }
+    }
+
+    fn command(&mut self) -> Result<Expr, String> {
+        let cmd = self.scanner.next();
+        let start = self.scanner.span().0;
+        match cmd {
+            Token::Command(Command::Mode) => match self.scanner.peek() {
+                Token::Comma | Token::END => Result::Ok(self.node(Node::Mode(Option::None), start)),
+                t => {
+                    self.scanner.next();
+                    match t {
+                        Token::Text(s) if Angle::from_name(s).is_some() => {
+                            Result::Ok(self.node(Node::Mode(Angle::from_name(s)), start))
+                        }
+                        _ => self.error("Syntax: mode rad|deg|grad"),
+                    }
+                }
+            },
+            Token::Command(Command::Const) => {
+                let syntax = "Syntax: const name = expr";
+                let name = match self.scanner.next() {
+                    Token::Text(s) => s.to_string(),
+                    _ => return self.error(syntax),
+                };
+                self.expect(Token::Equals, syntax)?;
+                let v = self.expr()?;
+                Result::Ok(self.node(Node::Const(name, Box::new(v)), start))
+            }
+            Token::Command(c @ (Command::Vars | Command::Who | Command::Clear)) => {
+                Result::Ok(self.node(Node::Workspace(c, Vec::new()), start))
+            }
+            Token::Command(Command::Del) => {
+                let mut names = Vec::new();
+                while let Token::Text(s) = self.scanner.peek() {
+                    self.scanner.next();
+                    names.push(s.to_string());
+                }
+                if names.is_empty() {
+                    self.scanner.next();
+                    return self.error("Syntax: del name ...");
+                }
+                Result::Ok(self.node(Node::Workspace(Command::Del, names), start))
+            }
+            _ => self.error("Expected a command"),
+        }
+    }
+
+    // Whether the statement starts with a, b = which is not the same as a and then b = ...
+    // Without enough values a known name is an expression of its own, as in y = 1, y, x = 5
+    fn unpacking(&self) -> bool {
+        let mut ahead = self.scanner.clone();
+        let mut names = Vec::new();
+        loop {
+            match (ahead.next(), ahead.next()) {
+                (Token::Text(s), Token::Comma) => names.push(s),
+                (Token::Text(s), Token::Equals) if !names.is_empty() => {
+                    names.push(s);
+                    break;
+                }
+                _ => return false,
+            }
+        }
+        let (mut depth, mut values) = (0, 1);
+        loop {
+            match ahead.next() {
+                Token::Lparen | Token::Lbracket => depth += 1,
+                Token::Rparen | Token::Rbracket => depth -= 1,
+                Token::Comma if depth == 0 && newline(&ahead, ahead.span()) => break,
+                Token::Comma if depth == 0 => values += 1,
+                Token::END => break,
+                _ => {}
+            }
+        }
+        values >= names.len()
+            || (self.context.get(names[0]).is_none() && !self.locals.iter().any(|l| l == names[0]))
+    }
+
+    // a, b = 3, 4 takes the values up to the end of the line
+    fn unpack(&mut self) -> Result<Expr, String> {
+        let start = self.scanner.peek_span().0;
+        let mut names: Vec<String> = Vec::new();
+        while let Token::Text(s) = self.scanner.next() {
+            if names.iter().any(|n| n == s) {
+                return self.error(&format!("'{}' is assigned more than once", s));
+            }
+            names.push(s.to_string());
+            if self.scanner.next() == Token::Equals {
+                break;
+            }
+        }
+        let mut values = Vec::with_capacity(names.len());
+        loop {
+            values.push(self.expr()?);
+            let next = self.scanner.peek_span();
+            if self.scanner.peek() != Token::Comma || newline(&self.scanner, next) {
+                break;
+            }
+            self.scanner.next();
+        }
+        if values.len() != names.len() {
+            let msg = format!(
+                "Expected {} values for {}, got {}",
+                names.len(),
+                names.join(", "),
+                values.len()
+            );
+            let span = (start, self.scanner.span().1);
+            return Result::Err(self.format_error(&Error::new(&msg, span)));
+        }
+        Result::Ok(self.node(Node::Unpack(names, values), start))
+    }
+
+    // Evaluate an expression in the context of the parser
+    pub fn eval(&mut self, expr: &Expr) -> Result<Value, String> {
+        match self.context.eval(expr) {
+            Result::Ok(v) => Result::Ok(v),
+            Result::Err(e) => Result::Err(self.format_error(&e)),
+        }
+    }
+
+    fn format_error(&self, e: &Error) -> String {
+        let input = self.scanner.input();
+        let token = self.scanner.token_at(e.span.0);
+        if self.color {
+            format!(
+                "{} {}\n{}",
+                color::red("Error:"),
+                e.message,
+                color::print_span(input, token, e.span)
+            )
+        } else {
+            format!(
+                "Error: {}\n{}",
+                e.message,
+                Scanner::print_at(input, token, e.span.0)
+            )
         }
     }
 
-    fn error(self: &mut Self, error: &str) -> Result<f64, String> {
-        Result::Err(format!("Error: {}\n{}", error, self.scanner.print_pos()))
+    fn error<T>(&mut self, error: &str) -> Result<T, String> {
+        let unknown;
+        let error = match self.scanner.current() {
+            Token::Unknown => {
+                let (start, end) = self.scanner.span();
+                unknown = unrecognized(&self.scanner.input()[start..end]);
+                &unknown
+            }
+            _ => error,
+        };
+        if self.color {
+            Result::Err(format!(
+                "{} {}\n{}",
+                color::red("Error:"),
+                error,
+                color::print_pos(&self.scanner)
+            ))
+        } else {
+            Result::Err(format!("Error: {}\n{}", error, self.scanner.print_pos()))
+        }
     }
 
-    fn expect(self: &mut Self, t2: Token, reason: &str) -> Result<f64, String> {
+    fn expect(&mut self, t2: Token, reason: &str) -> Result<(), String> {
         if self.scanner.next() == t2 {
-            Result::Ok(0.0)
+            Result::Ok(())
         } else {
             self.error(reason)
         }
     }
 
-    fn expr(self: &mut Self) -> Result<f64, String> {
+    // Finish a node that started at the given byte index and ends with the current token
+    fn node(&self, node: Node, start: usize) -> Expr {
+        Expr::new(node, (start, self.scanner.span().1))
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Comma => {
                 self.scanner.next();
@@ -81,272 +368,824 @@ impl<'a> Parser<'a> {
             }
             _ => {
                 let v = self.term()?;
-                self.expr_(v)
+                let v = self.expr_(v)?;
+                self.convert(v)
             }
         }
     }
 
-    fn expr_(self: &mut Self, v: f64) -> Result<f64, String> {
+    fn convert(&mut self, v: Expr) -> Result<Expr, String> {
+        if self.scanner.peek() != Token::Text("in") {
+            return Result::Ok(v);
+        }
+        self.scanner.next();
+        match self.scanner.next() {
+            Token::Text(unit) => {
+                let start = v.span.0;
+                Result::Ok(self.node(Node::Convert(Box::new(v), unit.to_string()), start))
+            }
+            _ => self.error("Syntax: x in unit"),
+        }
+    }
+
+    fn expr_(&mut self, v: Expr) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Addition => {
                 self.scanner.next();
-                let v = v + self.term()?;
+                let v = Expr::binary(Operator::Add, v, self.term()?);
                 self.expr_(v)
             }
             Token::Subtraction => {
                 self.scanner.next();
-                let v = v - self.term()?;
+                let v = Expr::binary(Operator::Sub, v, self.term()?);
                 self.expr_(v)
             }
             _ => Result::Ok(v),
         }
     }
 
-    fn term(self: &mut Self) -> Result<f64, String> {
+    fn term(&mut self) -> Result<Expr, String> {
         let v = self.factor()?;
         self.term_(v)
     }
 
-    fn term_(self: &mut Self, v: f64) -> Result<f64, String> {
+    fn term_(&mut self, v: Expr) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Multiplication => {
                 self.scanner.next();
-                let v = v * self.factor()?;
+                let v = Expr::binary(Operator::Mul, v, self.factor()?);
                 self.term_(v)
             }
             Token::Division => {
                 self.scanner.next();
-                let v2 = self.factor()?;
-                if v2 == 0.0 {
-                    self.error("Division by zero")
-                } else {
-                    self.term_(v / v2)
-                }
+                let v = Expr::binary(Operator::Div, v, self.factor()?);
+                self.term_(v)
             }
             Token::Modulo => {
                 self.scanner.next();
-                let v2 = self.factor()?;
-                if v2 == 0.0 {
-                    self.error("Division by zero")
-                } else {
-                    self.term_(v % v2)
+                // Without a value after it the % is a percent sign
+                let percent = self.node(Node::Percent(Box::new(v.clone())), v.span.0);
+                match self.scanner.peek() {
+                    Token::Text("of") => {
+                        self.scanner.next();
+                        let v = Expr::binary(Operator::Mul, percent, self.factor()?);
+                        self.term_(v)
+                    }
+                    Token::Number(_)
+                    | Token::Function(_)
+                    | Token::Lparen
+                    | Token::Bar
+                    | Token::Text(_) => {
+                        let v = Expr::binary(Operator::Mod, v, self.factor()?);
+                        self.term_(v)
+                    }
+                    // 5 % -3 is a modulo, but 5% - 3 subtracts from a percent
+                    Token::Subtraction if self.negative() => {
+                        let v = Expr::binary(Operator::Mod, v, self.factor()?);
+                        self.term_(v)
+                    }
+                    _ => self.term_(percent),
                 }
             }
-            Token::Function(_) | Token::Lparen | Token::Number(_) | Token::Text(_) => {
-                let v = v * self.factor()?;
+            Token::Text("in") => Result::Ok(v),
+            Token::Text("for") if self.symbolic => Result::Ok(v),
+            Token::Function(_)
+            | Token::Lparen
+            | Token::Number(_)
+            | Token::Date(_)
+            | Token::Lbracket
+            | Token::Text(_) => {
+                let next = self.scanner.peek_span();
+                let r = self.factor()?;
+                let v = if is_duration(&v) && matches!(r.node, Node::Duration(..)) {
+                    Expr::binary(Operator::Add, v, r)
+                } else {
+                    if !self.coefficient(&v, next) {
+                        if self.strict {
+                            return Result::Err(self.implicit(&v, &r));
+                        }
+                        let text = self.scanner.input()[v.span.0..r.span.1].trim();
+                        let (l, r) = self.operands(&v, &r);
+                        self.warnings
+                            .push(format!("Warning: '{}' is read as '{} * {}'", text, l, r));
+                    }
+                    Expr::binary(Operator::Mul, v, r)
+                };
                 self.term_(v)
             }
             _ => Result::Ok(v),
         }
     }
 
-    fn factor(self: &mut Self) -> Result<f64, String> {
+    // Whether the term ends with a number literal right before the next token, as in 2x
+    fn coefficient(&self, v: &Expr, next: (usize, usize)) -> bool {
+        let input = self.scanner.input();
+        match v.node {
+            Node::Number(_) => {
+                let number =
+                    input[v.span.0..v.span.1].starts_with(|c: char| c.is_ascii_digit() || c == '.');
+                let name = input[next.0..].starts_with(|c: char| !c.is_ascii_digit() && c != '.');
+                number && name && v.span.1 == next.0
+            }
+            // Not after a division, 1/2x could mean 1/(2x)
+            Node::Negate(ref x) | Node::Binary(Operator::Mul, _, ref x) => {
+                self.coefficient(x, next)
+            }
+            _ => false,
+        }
+    }
+
+    // Show where the multiplication would have been inserted
+    fn implicit(&self, l: &Expr, r: &Expr) -> String {
+        let (l, r_text) = self.operands(l, r);
+        let e = Error::new(
+            &format!(
+                "Implicit multiplication is not allowed in strict mode, write '{} * {}' or use another operator",
+                l, r_text
+            ),
+            r.span,
+        );
+        self.format_error(&e)
+    }
+
+    fn operands(&self, l: &Expr, r: &Expr) -> (&'a str, &'a str) {
+        let input = self.scanner.input();
+        (
+            input[l.span.0..l.span.1].trim(),
+            input[r.span.0..r.span.1].trim(),
+        )
+    }
+
+    fn factor(&mut self) -> Result<Expr, String> {
         let v = self.func()?;
         self.factor_(v)
     }
 
-    fn factor_(self: &mut Self, v: f64) -> Result<f64, String> {
+    fn factor_(&mut self, v: Expr) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Power => {
                 self.scanner.next();
-                let v = v.powf(self.func()?);
+                let v = Expr::binary(Operator::Pow, v, self.func()?);
                 self.factor_(v)
             }
             Token::Factorial => {
                 self.scanner.next();
-                if v < 0.0 {
-                    self.error("Factorial must be positive")
-                } else {
-                    let mut r: f64 = 1.0;
-                    let mut v: f64 = v.floor();
-                    while v > 1.0 {
-                        r *= v;
-                        v -= 1.0;
+                let start = v.span.0;
+                let v = self.node(Node::Factorial(Box::new(v)), start);
+                self.factor_(v)
+            }
+            Token::DoubleFactorial => {
+                self.scanner.next();
+                let start = v.span.0;
+                let v = self.node(Node::DoubleFactorial(Box::new(v)), start);
+                self.factor_(v)
+            }
+
+            _ => Result::Ok(v),
+        }
+    }
+
+    // Parse a parenthesised, comma separated list of arguments
+    fn arguments(&mut self, syntax: &str) -> Result<Vec<Expr>, String> {
+        self.expect(Token::Lparen, syntax)?;
+        self.argument_list(syntax)
+    }
+
+    fn argument_list(&mut self, syntax: &str) -> Result<Vec<Expr>, String> {
+        let mut args = Vec::new();
+        loop {
+            args.push(self.expr()?);
+            if self.scanner.peek() == Token::Rparen {
+                self.expect(Token::Rparen, syntax)?;
+                break;
+            }
+            self.expect(Token::Comma, syntax)?;
+        }
+        Result::Ok(args)
+    }
+
+    // Arguments where name=expr gives a parameter by name, after the positional arguments
+    fn keyword_list(
+        &mut self,
+        name: &str,
+        params: &[&str],
+        syntax: &str,
+    ) -> Result<Arguments, String> {
+        let mut args = Vec::new();
+        let mut keywords: Vec<(String, Expr)> = Vec::new();
+        loop {
+            match self.keyword() {
+                Option::Some(k) => {
+                    self.scanner.next();
+                    match params.iter().position(|&p| p == k) {
+                        Option::None => {
+                            let hints = suggest::suggest(k, params.iter().copied());
+                            let problem = format!("{} has no parameter", name);
+                            return self.error(&suggest::message(&problem, k, &hints));
+                        }
+                        Option::Some(i) if i < args.len() || keywords.iter().any(|a| a.0 == k) => {
+                            return self.error(&format!("{} got the argument '{}' twice", name, k));
+                        }
+                        Option::Some(_) => {}
                     }
-                    self.factor_(r)
+                    self.scanner.next();
+                    keywords.push((k.to_string(), self.expr()?));
                 }
+                Option::None if !keywords.is_empty() => {
+                    let e = self.expr()?;
+                    let msg = "Positional arguments must come before keyword arguments";
+                    return Result::Err(self.format_error(&Error::new(msg, e.span)));
+                }
+                Option::None => args.push(self.expr()?),
             }
-            _ => Result::Ok(v),
+            if self.scanner.peek() == Token::Rparen {
+                self.scanner.next();
+                break;
+            }
+            self.expect(Token::Comma, syntax)?;
         }
+        Result::Ok((args, keywords))
     }
 
-    fn func(self: &mut Self) -> Result<f64, String> {
-        match self.scanner.peek() {
-            Token::Function(ref f) => {
+    // A name followed by =, which inside a call is a keyword argument and not an assignment
+    // (parameters such as fv share the name of a function)
+    fn keyword(&self) -> Option<&'a str> {
+        if let Token::Text(_) | Token::Function(_) = self.scanner.peek() {
+            let mut ahead = self.scanner.clone();
+            ahead.next();
+            if ahead.peek() == Token::Equals {
+                let (start, end) = self.scanner.peek_span();
+                return Option::Some(&self.scanner.input()[start..end]);
+            }
+        }
+        Option::None
+    }
+
+    // Any function can be called with parentheses, and functions of one value also without
+    fn call(&mut self, f: Function, start: usize) -> Result<Expr, String> {
+        let signature = f.signature();
+        let args = if self.scanner.peek() == Token::Lparen {
+            self.scanner.next();
+            if self.scanner.peek() == Token::Rparen {
                 self.scanner.next();
-                match *f {
-                    Function::Log => {
-                        self.expect(Token::Lparen, "Syntax: log(x,y)")?;
-                        let v1 = self.expr()?;
-                        match self.scanner.peek() {
-                            Token::Comma => {
-                                self.scanner.next();
-                                let v2 = self.expr()?;
-                                self.expect(Token::Rparen, "Syntax: log(x,y)")?;
-                                Result::Ok(v1.log(v2))
-                            }
-                            Token::Rparen => {
-                                self.scanner.next();
-                                Result::Ok(v1.ln())
-                            }
-                            _ => self.error("Syntax: log(x,y)"),
-                        }
+                Vec::new()
+            } else {
+                // The parameters of variadic functions have no names to give
+                let params: Vec<&str> = match signature.variadic {
+                    true => Vec::new(),
+                    false => signature.params.iter().map(|p| p.name()).collect(),
+                };
+                let syntax = format!("Syntax: {}", f.syntax());
+                let (args, keywords) = self.keyword_list(&f.to_string(), &params, &syntax)?;
+                self.positional(f, args, keywords, start)?
+            }
+        } else if signature.max() == 0 {
+            Vec::new()
+        } else if signature.min() <= 1 {
+            vec![self.operand()?]
+        } else {
+            return self.error(&format!("Syntax: {}", f.syntax()));
+        };
+        self.arity(f, args, start)
+    }
+
+    // Put keyword arguments in the order of the parameters, filling in skipped defaults
+    fn positional(
+        &mut self,
+        f: Function,
+        mut args: Vec<Expr>,
+        mut keywords: Vec<(String, Expr)>,
+        start: usize,
+    ) -> Result<Vec<Expr>, String> {
+        let params = f.signature().params;
+        while !keywords.is_empty() {
+            let param = params[args.len()];
+            match keywords.iter().position(|k| k.0 == param.name()) {
+                Option::Some(i) => args.push(keywords.remove(i).1),
+                Option::None => match param {
+                    Param::Default(_, x) => args.push(self.node(Node::Number(x), start)),
+                    _ => {
+                        let msg = format!(
+                            "{} is missing the argument '{}' ({})",
+                            f,
+                            param.name(),
+                            f.syntax()
+                        );
+                        let span = (start, self.scanner.span().1);
+                        return Result::Err(self.format_error(&Error::new(&msg, span)));
                     }
-                    Function::Atan2 => {
-                        self.expect(Token::Lparen, "Syntax: atan2(y,x)")?;
-                        let v1 = self.expr()?;
-                        self.expect(Token::Comma, "Syntax: atan2(y,x)")?;
-                        let v2 = self.expr()?;
-                        self.expect(Token::Rparen, "Syntax: atan2(y,x)")?;
-                        Result::Ok(v1.atan2(v2))
+                },
+            }
+        }
+        Result::Ok(args)
+    }
+
+    fn arity(&mut self, f: Function, args: Vec<Expr>, start: usize) -> Result<Expr, String> {
+        let count = args.len();
+        let call = self.node(Node::Call(f, args), start);
+        match f.check_arity(count) {
+            Result::Ok(()) => Result::Ok(call),
+            Result::Err(msg) => Result::Err(self.format_error(&Error::new(&msg, call.span))),
+        }
+    }
+
+    // The operand of a function without parentheses, where a coefficient binds to the
+    // following name (sin 2x is sin(2x), but sin 2 x is sin(2) * x)
+    fn operand(&mut self) -> Result<Expr, String> {
+        let mut v = self.func()?;
+        while matches!(
+            self.scanner.peek(),
+            Token::Text(_) | Token::Function(_) | Token::Lparen | Token::Number(_)
+        ) && self.coefficient(&v, self.scanner.peek_span())
+        {
+            let r = self.factor()?;
+            v = Expr::binary(Operator::Mul, v, r);
+        }
+        Result::Ok(v)
+    }
+
+    fn func(&mut self) -> Result<Expr, String> {
+        match self.scanner.peek() {
+            Token::Function(f) => {
+                self.scanner.next();
+                let start = self.scanner.span().0;
+                match f {
+                    Function::Solve | Function::Simplify => {
+                        let symbolic = self.symbolic;
+                        self.symbolic = true;
+                        let res = if f == Function::Solve {
+                            self.solve(start)
+                        } else {
+                            self.simplify(start)
+                        };
+                        self.symbolic = symbolic;
+                        res
                     }
-                    Function::Sum => {
-                        let mut v: f64 = 0.0;
-                        self.expect(Token::Lparen, "Syntax: sum(x,y,...)")?;
-                        loop {
-                            v += self.expr()?;
-                            if self.scanner.peek() == Token::Rparen {
-                                self.expect(Token::Rparen, "Syntax: sum(x,y,...)")?;
-                                break;
-                            }
-                            self.expect(Token::Comma, "Syntax: sum(x,y,...)")?;
-                        }
-                        Result::Ok(v)
+                    Function::Table => self.table(start),
+                    Function::Dice => self.dice(start),
+                    _ => self.call(f, start),
+                }
+            }
+            _ => {
+                let v = self.value()?;
+                let start = v.span.0;
+                match self.scanner.peek() {
+                    Token::Degree => {
+                        self.scanner.next();
+                        Result::Ok(self.node(Node::Degrees(Box::new(v)), start))
                     }
-                    Function::Mean => {
-                        let mut v: f64 = 0.0;
-                        let mut c: f64 = 0.0;
-                        self.expect(Token::Lparen, "Syntax: mean(x,y,...)")?;
-                        loop {
-                            v += self.expr()?;
-                            c += 1.0;
-                            if self.scanner.peek() == Token::Rparen {
-                                self.expect(Token::Rparen, "Syntax: mean(x,y,...)")?;
-                                break;
+                    // Variables take precedence over units, so that "2 h" can still mean 2 * h
+                    // Currencies are three capital letters, such as "120 EUR"
+                    // In solve and simplify any name can be the unknown, so there are no units
+                    Token::Text(s)
+                        if self.context.get(s).is_none() && !self.locals.iter().any(|l| l == s) =>
+                    {
+                        match time::unit(s).filter(|_| !self.symbolic) {
+                            Option::Some(size) => {
+                                self.scanner.next();
+                                Result::Ok(self.node(Node::Duration(Box::new(v), size), start))
                             }
-                            self.expect(Token::Comma, "Syntax: mean(x,y,...)")?;
-                        }
-                        Result::Ok(v / c)
-                    }
-                    Function::Product => {
-                        let mut v: f64 = 1.0;
-                        self.expect(Token::Lparen, "Syntax: prod(x,y,...)")?;
-                        loop {
-                            v *= self.expr()?;
-                            if self.scanner.peek() == Token::Rparen {
-                                self.expect(Token::Rparen, "Syntax: prod(x,y,...)")?;
-                                break;
+                            Option::None if currency::is_code(s) => {
+                                self.scanner.next();
+                                let node = Node::Money(Box::new(v), s.to_string());
+                                Result::Ok(self.node(node, start))
                             }
-                            self.expect(Token::Comma, "Syntax: prod(x,y,...)")?;
+                            Option::None => Result::Ok(v),
                         }
-                        Result::Ok(v)
                     }
-                    _ => {
-                        let v = self.func()?;
-                        match *f {
-                            Function::Ln => Result::Ok(v.ln()),
-                            Function::Exp => Result::Ok(std::f64::consts::E.powf(v)),
-                            Function::Abs => Result::Ok(v.abs()),
-                            Function::Sqrt => {
-                                if v < 0.0 {
-                                    self.error(&format!("Cannot handle negative values ({})", v))
-                                } else {
-                                    Result::Ok(v.sqrt())
-                                }
-                            }
-                            Function::Cos => Result::Ok(v.cos()),
-                            Function::Sin => Result::Ok(v.sin()),
-                            Function::Tan => Result::Ok(v.tan()),
-                            Function::Asin => {
-                                if v <= 1.0 && v >= -1.0 {
-                                    Result::Ok(v.asin())
-                                } else {
-                                    self.error(&format!("Value outside range (-1 <= {} <= 1)", v))
-                                }
-                            }
-                            Function::Acos => {
-                                if v <= 1.0 && v >= -1.0 {
-                                    Result::Ok(v.acos())
-                                } else {
-                                    self.error(&format!("Value outside range (-1 <= {} <= 1)", v))
-                                }
-                            }
-                            Function::Atan => Result::Ok(v.atan()),
-                            _ => self.error("Unknown function"),
+                    _ => Result::Ok(v),
+                }
+            }
+        }
+    }
+
+    // solve(A,b) for linear systems, or solve lhs = rhs for equations
+    fn solve(&mut self, start: usize) -> Result<Expr, String> {
+        let syntax = "Syntax: solve(A,b) or solve lhs = rhs for x";
+        let lhs = if self.scanner.peek() == Token::Lparen {
+            self.scanner.next();
+            let open = self.scanner.span().0;
+            let mut args = self.argument_list(syntax)?;
+            match args.len() {
+                2 => return Result::Ok(self.node(Node::Call(Function::Solve, args), start)),
+                1 => {
+                    // The equation starts with a parenthesis
+                    let v = self.node(args.remove(0).node, open);
+                    let v = self.factor_(v)?;
+                    let v = self.term_(v)?;
+                    self.expr_(v)?
+                }
+                _ => return self.error(syntax),
+            }
+        } else {
+            self.expr()?
+        };
+        // Without a right side the roots of the expression are found
+        let rhs = if self.scanner.peek() == Token::Equals {
+            self.scanner.next();
+            self.expr()?
+        } else {
+            Expr::new(Node::Number(0.0), (lhs.span.1, lhs.span.1))
+        };
+        let var = if self.scanner.peek() == Token::Text("for") {
+            self.scanner.next();
+            match self.scanner.next() {
+                Token::Text(s) => Option::Some(s.to_string()),
+                _ => return self.error(syntax),
+            }
+        } else {
+            Option::None
+        };
+        let node = Node::Equation(Box::new(lhs), Box::new(rhs), var);
+        Result::Ok(self.node(node, start))
+    }
+
+    // simplify expr, or simplify expr in latex|unicode
+    fn simplify(&mut self, start: usize) -> Result<Expr, String> {
+        let Expr { node, span } = self.expr()?;
+        let notation = match node {
+            Node::Convert(_, ref name) => Notation::from_name(name),
+            _ => Option::None,
+        };
+        let e = match (notation, node) {
+            (Option::Some(_), Node::Convert(e, _)) => *e,
+            (_, node) => Expr::new(node, span),
+        };
+        let node = Node::Simplify(Box::new(e), notation.unwrap_or(Notation::Plain));
+        Result::Ok(self.node(node, start))
+    }
+
+    // table(expr, x, start, stop, step), optionally followed by a second variable
+    fn table(&mut self, start: usize) -> Result<Expr, String> {
+        let syntax = "Syntax: table(expr, x, start, stop, step) or table(expr, x, ..., y, ...)";
+        // The variables come after the expression, table(3s, s, 0, 2, 1) is not in seconds
+        let count = self.locals.len();
+        let names = self.table_variables();
+        self.locals.extend(names);
+        let args = self.arguments(syntax);
+        self.locals.truncate(count);
+        let mut args = args?;
+        if args.len() != 5 && args.len() != 9 {
+            return self.error(syntax);
+        }
+        let e = args.remove(0);
+        let mut names = Vec::new();
+        let mut bounds = Vec::new();
+        for (i, a) in args.into_iter().enumerate() {
+            match a.node {
+                Node::Variable(name) if i % 4 == 0 => names.push(name),
+                _ if i % 4 == 0 => return self.error(syntax),
+                _ => bounds.push(a),
+            }
+        }
+        let mut style = Style::Text;
+        if self.scanner.peek() == Token::Text("in") {
+            self.scanner.next();
+            style = match self.scanner.next() {
+                Token::Text(s) if Style::from_name(s).is_some() => Style::from_name(s).unwrap(),
+                _ => return self.error("Syntax: table(...) in text|csv|json"),
+            };
+        }
+        let node = Node::Table(Box::new(e), names, bounds, style);
+        Result::Ok(self.node(node, start))
+    }
+
+    // The names given as the second and sixth arguments of the table
+    fn table_variables(&self) -> Vec<String> {
+        let mut ahead = self.scanner.clone();
+        let (mut depth, mut index) = (0, 0);
+        let mut names = Vec::new();
+        loop {
+            match ahead.next() {
+                Token::Lparen | Token::Lbracket => depth += 1,
+                Token::Rparen | Token::Rbracket if depth <= 1 => return names,
+                Token::Rparen | Token::Rbracket => depth -= 1,
+                Token::Comma if depth == 1 => {
+                    index += 1;
+                    if let Token::Text(s) = ahead.peek() {
+                        if index % 4 == 1 {
+                            names.push(s.to_string());
                         }
                     }
                 }
+                Token::END => return names,
+                _ => {}
             }
-            _ => self.value(),
         }
     }
 
-    fn value(self: &mut Self) -> Result<f64, String> {
+    // dice("NdM+K") is shorthand for dice(N,M) + K
+    fn dice(&mut self, start: usize) -> Result<Expr, String> {
+        let syntax = "Syntax: dice(\"NdM+K\") or dice(count,sides)";
+        self.expect(Token::Lparen, syntax)?;
+        let args = match self.scanner.peek() {
+            Token::String(s) => {
+                self.scanner.next();
+                let span = self.scanner.span();
+                let (n, sides, bonus) = match parse_dice(s) {
+                    Option::Some(d) => d,
+                    Option::None => return self.error(syntax),
+                };
+                self.expect(Token::Rparen, syntax)?;
+                return Result::Ok(self.node(
+                    Node::Call(
+                        Function::Dice,
+                        vec![
+                            Expr::new(Node::Number(n), span),
+                            Expr::new(Node::Number(sides), span),
+                            Expr::new(Node::Number(bonus), span),
+                        ],
+                    ),
+                    start,
+                ));
+            }
+            _ => self.argument_list(syntax)?,
+        };
+        self.arity(Function::Dice, args, start)
+    }
+
+    fn value(&mut self) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Number(x) => {
                 self.scanner.next();
-                Result::Ok(x)
+                Result::Ok(Expr::new(Node::Number(x), self.scanner.span()))
+            }
+            Token::Date(x) => {
+                self.scanner.next();
+                Result::Ok(Expr::new(Node::Date(x), self.scanner.span()))
             }
             Token::Subtraction => {
                 self.scanner.next();
-                Result::Ok(-self.value()?)
+                let start = self.scanner.span().0;
+                let v = self.value()?;
+                Result::Ok(self.node(Node::Negate(Box::new(v)), start))
             }
             Token::Lparen => {
                 self.scanner.next();
+                let start = self.scanner.span().0;
                 let v = self.expr()?;
                 self.expect(Token::Rparen, "Expected Right Parenthesis")?;
-                Result::Ok(v)
+                Result::Ok(self.node(v.node, start))
+            }
+            Token::Lbracket => {
+                self.scanner.next();
+                let start = self.scanner.span().0;
+                let mut items = Vec::new();
+                loop {
+                    items.push(self.expr()?);
+                    if self.scanner.peek() == Token::Rbracket {
+                        self.scanner.next();
+                        break;
+                    }
+                    self.expect(Token::Comma, "Expected ]")?;
+                }
+                Result::Ok(self.node(Node::Matrix(items), start))
             }
             Token::Bar => {
                 self.scanner.next();
+                let start = self.scanner.span().0;
                 let v = self.expr()?;
                 self.expect(Token::Bar, "Expected |")?;
-                Result::Ok(v.abs())
+                Result::Ok(self.node(Node::Call(Function::Abs, vec![v]), start))
             }
             Token::Text(_) => self.id(),
+            Token::Unknown => {
+                self.scanner.next();
+                self.error("Unrecognized input")
+            }
             _ => self.error("Expected a number or parenthesis"),
         }
     }
 
-    fn id(self: &mut Self) -> Result<f64, String> {
+    fn id(&mut self) -> Result<Expr, String> {
         match self.scanner.peek() {
             Token::Text(s) => {
                 self.scanner.next();
+                let start = self.scanner.span().0;
+                let compound = self.compound(self.scanner.peek());
+                if let Option::Some(op) = compound.filter(|_| !self.symbolic) {
+                    return self.update(s, op, start);
+                }
                 match self.scanner.peek() {
-                    Token::Equals => {
+                    Token::Equals if !self.symbolic => {
                         self.scanner.next();
                         let v = self.expr()?;
-                        self.lexiographic_table.insert(s, v);
-                        Result::Ok(v)
+                        Result::Ok(self.node(Node::Assign(s.to_string(), Box::new(v)), start))
                     }
-                    _ => match self.lexiographic_table.get(s) {
-                        Option::Some(v) => return Result::Ok(*v),
-                        Option::None => self.error("Unknown variable or constant"),
-                    },
+                    Token::Lparen if !self.symbolic && self.definition() => self.define(s, start),
+                    Token::Lparen if self.context.function(s).is_some() => self.apply(s, start),
+                    // A name followed by a parenthesis is most likely a misspelled function
+                    Token::Lparen
+                        if !self.symbolic
+                            && self.context.get(s).is_none()
+                            && !self.locals.iter().any(|l| l == s) =>
+                    {
+                        let msg = self.context.unknown(s);
+                        self.error(&msg)
+                    }
+                    _ => Result::Ok(self.node(Node::Variable(s.to_string()), start)),
                 }
             }
             _ => self.error("Expexted a name or identifier"),
         }
     }
+
+    // x += 2 is x = x + 2
+    fn update(&mut self, name: &str, op: Operator, start: usize) -> Result<Expr, String> {
+        let variable = self.node(Node::Variable(name.to_string()), start);
+        self.scanner.next();
+        self.scanner.next();
+        let v = Expr::binary(op, variable, self.expr()?);
+        Result::Ok(self.node(Node::Assign(name.to_string(), Box::new(v)), start))
+    }
+
+    // The operator of x += 2, x *= 1.1 and so on, where x op= y is x = x op y
+    fn compound(&self, t: Token) -> Option<Operator> {
+        let op = match t {
+            Token::Addition => Operator::Add,
+            Token::Subtraction => Operator::Sub,
+            Token::Multiplication => Operator::Mul,
+            Token::Division => Operator::Div,
+            Token::Modulo => Operator::Mod,
+            Token::Power => Operator::Pow,
+            _ => return Option::None,
+        };
+        let mut ahead = self.scanner.clone();
+        ahead.next();
+        match ahead.peek() {
+            Token::Equals => Option::Some(op),
+            _ => Option::None,
+        }
+    }
+
+    // Whether the next token is a minus sign directly followed by a value, as in -3
+    fn negative(&self) -> bool {
+        let mut ahead = self.scanner.clone();
+        ahead.next();
+        let end = ahead.span().1;
+        ahead.peek_span().0 == end
+            && matches!(
+                ahead.peek(),
+                Token::Number(_) | Token::Function(_) | Token::Lparen | Token::Bar | Token::Text(_)
+            )
+    }
+
+    // Whether the parenthesis after a name is closed by one followed by =, as in f(x) = x^2
+    fn definition(&self) -> bool {
+        let mut ahead = self.scanner.clone();
+        let mut depth = 0;
+        loop {
+            match ahead.next() {
+                Token::Lparen => depth += 1,
+                Token::Rparen if depth == 1 => return ahead.peek() == Token::Equals,
+                Token::Rparen => depth -= 1,
+                Token::END => return false,
+                _ => {}
+            }
+        }
+    }
+
+    // f(x, n=2) = expr, the parameters are only known inside the defaults and the body
+    fn define(&mut self, name: &str, start: usize) -> Result<Expr, String> {
+        let syntax = "Syntax: f(x, y=default) = expr";
+        self.scanner.next();
+        let outer = std::mem::take(&mut self.locals);
+        let result = self.define_(name, start, syntax);
+        self.locals = outer;
+        result
+    }
+
+    fn define_(&mut self, name: &str, start: usize, syntax: &str) -> Result<Expr, String> {
+        let mut params: Vec<(String, Option<Expr>)> = Vec::new();
+        while self.scanner.peek() != Token::Rparen {
+            let param = match self.scanner.next() {
+                Token::Text(p) => p.to_string(),
+                _ => return self.error(syntax),
+            };
+            if params.iter().any(|p| p.0 == param) {
+                return self.error(&format!("The parameter '{}' is already defined", param));
+            }
+            let default = if self.scanner.peek() == Token::Equals {
+                self.scanner.next();
+                Option::Some(self.expr()?)
+            } else if params.iter().any(|p| p.1.is_some()) {
+                return self.error("Parameters without defaults must come before those with");
+            } else {
+                Option::None
+            };
+            self.locals.push(param.clone());
+            params.push((param, default));
+            if self.scanner.peek() != Token::Rparen {
+                self.expect(Token::Comma, syntax)?;
+            }
+        }
+        self.expect(Token::Rparen, syntax)?;
+        self.expect(Token::Equals, syntax)?;
+        let body = self.expr()?;
+        Result::Ok(self.node(
+            Node::Define(name.to_string(), params, Box::new(body)),
+            start,
+        ))
+    }
+
+    // A call of a user defined function, checked against its parameters
+    fn apply(&mut self, name: &str, start: usize) -> Result<Expr, String> {
+        let function = match self.context.function(name) {
+            Option::Some(f) => f.clone(),
+            Option::None => return self.error(&self.context.unknown(name)),
+        };
+        let params: Vec<&str> = function.params.iter().map(|p| p.0.as_str()).collect();
+        let syntax = function.syntax(name);
+        self.scanner.next();
+        let (args, keywords) = if self.scanner.peek() == Token::Rparen {
+            self.scanner.next();
+            (Vec::new(), Vec::new())
+        } else {
+            self.keyword_list(name, &params, &format!("Syntax: {}", syntax))?
+        };
+        let span = (start, self.scanner.span().1);
+        let count = args.len();
+        // Keywords always come after the positional arguments, so there cannot be too many
+        let missing = function
+            .params
+            .iter()
+            .skip(count)
+            .find(|p| p.1.is_none() && !keywords.iter().any(|k| k.0 == p.0));
+        let result = match missing {
+            _ if keywords.is_empty() => {
+                scanner::check_arity(name, function.min(), params.len(), count, &syntax)
+            }
+            Option::Some(p) => Result::Err(format!(
+                "{} is missing the argument '{}' ({})",
+                name, p.0, syntax
+            )),
+            Option::None => Result::Ok(()),
+        };
+        match result {
+            Result::Ok(()) => Result::Ok(Expr::new(
+                Node::Apply(name.to_string(), args, keywords),
+                span,
+            )),
+            Result::Err(msg) => Result::Err(self.format_error(&Error::new(&msg, span))),
+        }
+    }
 }
 
+// Why the scanner could not read a token
+fn unrecognized(text: &str) -> String {
+    match text.trim().chars().next() {
+        Option::Some('"') => "Missing the closing quote of a string".to_string(),
+        Option::Some(c) if c.is_ascii_digit() || c == '.' => {
+            format!("Invalid number or date '{}'", text.trim())
+        }
+        Option::Some(c) => format!("Unrecognized character '{}'", c),
+        Option::None => "Unrecognized input".to_string(),
+    }
+}
+
+// A duration literal, or a sum of them
+fn is_duration(e: &Expr) -> bool {
+    match e.node {
+        Node::Duration(..) => true,
+        Node::Binary(Operator::Add, _, ref r) => is_duration(r),
+        _ => false,
+    }
+}
+
+// Parse "NdM", "dM", "NdM+K" and "NdM-K" into (N, M, K)
+fn parse_dice(s: &str) -> Option<(f64, f64, f64)> {
+    let s = s.trim().to_lowercase();
+    let d = s.find('d')?;
+    let count = match s[..d].trim() {
+        "" => 1,
+        n => n.parse::<u32>().ok()?,
+    };
+    let rest = &s[d + 1..];
+    let (sides, bonus) = match rest.find(['+', '-']) {
+        Option::Some(i) => (
+            &rest[..i],
+            rest[i..].replace('+', "").trim().parse::<i64>().ok()?,
+        ),
+        Option::None => (rest, 0),
+    };
+    let sides = sides.trim().parse::<u32>().ok()?;
+    Option::Some((count as f64, sides as f64, bonus as f64))
+}
+
+// Whether a comma token at the span is the end of a line
+fn newline(scanner: &Scanner, span: (usize, usize)) -> bool {
+    scanner.input()[span.0..].starts_with('\n')
+}
+
+// The results are values since 2.0, use Value::as_number or Value::to_string for the f64
+// or text that 1.x returned
 impl std::iter::Iterator for Parser<'_> {
-    type Item = Result<f64, String>;
+    type Item = Result<Value, String>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.scanner.peek() {
-            Token::END => Option::None,
-            _ => match self.expr() {
-                Result::Ok(v) => Option::Some(Result::Ok(v)),
-                Result::Err(e) => {
-                    loop {
-                        if let Token::END | Token::Comma = self.scanner.next() {
-                            break;
-                        }
-                    }
-                    Option::Some(Result::Err(e))
-                }
-            }
+        match self.parse()? {
+            Result::Ok(e) => Option::Some(self.eval(&e)),
+            Result::Err(e) => Option::Some(Result::Err(e)),
         }
     }
 }
@@ -0,0 +1,326 @@
+// Plotting functions of one variable in the terminal, drawn with braille dots (2x4 in every
+// character) or ASCII characters. Points where a function is undefined are left as gaps,
+// and lines are not drawn across poles.
+
+use crate::color;
+use crate::compile::{self, CompiledExpr};
+use crate::context::Context;
+use crate::table::Range;
+
+// Markers for the lines of an ASCII plot
+const MARKERS: &[char] = &['*', 'o', 'x', '#', '%', '@'];
+// Largest plot in the terminal, in characters
+pub const MAX_WIDTH: usize = 1000;
+pub const MAX_HEIGHT: usize = 500;
+// Bits of the braille dots, by row and column within a character
+const BRAILLE: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Plot {
+    // Size of the drawing area in characters
+    pub width: usize,
+    pub height: usize,
+    pub ascii: bool,
+    pub color: bool,
+    // The visible range of values, chosen from the values when None
+    pub y: Option<(f64, f64)>,
+}
+
+// A function sampled at evenly spaced points, None where it is undefined
+#[derive(PartialEq, Debug, Clone)]
+pub struct Series {
+    pub name: String,
+    pub values: Vec<Option<f64>>,
+}
+
+impl Default for Plot {
+    fn default() -> Self {
+        Plot {
+            width: 72,
+            height: 20,
+            ascii: false,
+            color: false,
+            y: Option::None,
+        }
+    }
+}
+
+impl Plot {
+    // Number of points across the plot
+    pub fn columns(&self) -> usize {
+        if self.ascii {
+            self.width
+        } else {
+            self.width * 2
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        if self.ascii {
+            self.height
+        } else {
+            self.height * 4
+        }
+    }
+
+    // Draw functions of the variable over the range, the step of the range is not used
+    pub fn plot(&self, functions: &[&str], x: &Range, context: &Context) -> Result<String, String> {
+        if self.width > MAX_WIDTH || self.height > MAX_HEIGHT {
+            return Result::Err(format!(
+                "Error: The size of a plot in the terminal can be at most {}x{}",
+                MAX_WIDTH, MAX_HEIGHT
+            ));
+        }
+        let mut series = Vec::with_capacity(functions.len());
+        for f in functions {
+            series.push(self.sample(f, x, context)?);
+        }
+        Result::Ok(self.render((x.start, x.stop), &series))
+    }
+
+    pub fn sample(&self, input: &str, x: &Range, context: &Context) -> Result<Series, String> {
+        let mut f = function(input, &x.name, context)?;
+        let values = steps(x.start, x.stop, self.columns())
+            .map(|t| value_at(&mut f, t))
+            .collect();
+        Result::Ok(Series {
+            name: input.trim().to_string(),
+            values,
+        })
+    }
+
+    pub fn render(&self, x: (f64, f64), series: &[Series]) -> String {
+        let (cols, rows) = (self.columns(), self.rows());
+        let (y0, y1) = self.y.unwrap_or_else(|| y_range(series));
+        // Dots are counted from the top left corner
+        let row = |y: f64| (y1 - y) / (y1 - y0) * (rows - 1) as f64;
+        let axis_row = if y0 <= 0.0 && 0.0 <= y1 {
+            Option::Some(row(0.0).round() as usize)
+        } else {
+            Option::None
+        };
+        let axis_col = if x.0 <= 0.0 && 0.0 <= x.1 {
+            Option::Some((-x.0 / (x.1 - x.0) * (cols - 1) as f64).round() as usize)
+        } else {
+            Option::None
+        };
+        let mut dots: Vec<Option<usize>> = vec![Option::None; cols * rows];
+        let mut fill = |index: usize, c: usize, a: f64, b: f64| {
+            // Keep far away points from making long loops
+            let (a, b) = (a.clamp(-1.0, rows as f64), b.clamp(-1.0, rows as f64));
+            let (lo, hi) = (a.min(b).round() as i64, a.max(b).round() as i64);
+            for r in lo.max(0)..=hi.min(rows as i64 - 1) {
+                dots[r as usize * cols + c] = Option::Some(index);
+            }
+        };
+        for (index, s) in series.iter().enumerate() {
+            let points: Vec<Option<f64>> = s.values.iter().map(|v| v.map(row)).collect();
+            for (c, &p) in points.iter().enumerate().take(cols) {
+                if let Option::Some(a) = p {
+                    fill(index, c, a, a);
+                }
+                let b = match points.get(c + 1) {
+                    Option::Some(&Option::Some(b)) if c + 1 < cols => b,
+                    _ => continue,
+                };
+                let a = match p {
+                    Option::Some(a) => a,
+                    Option::None => continue,
+                };
+                // A jump from above to below the plot is a pole, such as in tan x
+                let last = (rows - 1) as f64;
+                if (a < 0.0 && b > last) || (b < 0.0 && a > last) {
+                    continue;
+                }
+                // Each point draws half of the line to the other one
+                let (a, b) = (a.round(), b.round());
+                let mid = (a + b) / 2.0;
+                if a < b {
+                    fill(index, c, a, mid.floor());
+                    fill(index, c + 1, mid.ceil(), b);
+                } else {
+                    fill(index, c, a, mid.ceil());
+                    fill(index, c + 1, mid.floor(), b);
+                }
+            }
+        }
+        let top = label(y1);
+        let bottom = label(y0);
+        let margin = top.chars().count().max(bottom.chars().count());
+        let (side, tick, corner, line) = if self.ascii {
+            ('|', '+', '+', '-')
+        } else {
+            ('│', '┤', '└', '─')
+        };
+        let mut lines = Vec::with_capacity(self.height + 3 + series.len());
+        for cy in 0..self.height {
+            let (name, t) = match cy {
+                0 => (top.as_str(), tick),
+                _ if cy + 1 == self.height => (bottom.as_str(), tick),
+                _ => ("", side),
+            };
+            let mut s = format!("{:>1$} {2}", name, margin, t);
+            for cx in 0..self.width {
+                s.push_str(&self.cell(&dots, cx, cy, axis_row, axis_col));
+            }
+            lines.push(s.trim_end().to_string());
+        }
+        lines.push(format!(
+            "{:>1$} {2}{3}",
+            "",
+            margin,
+            corner,
+            line.to_string().repeat(self.width)
+        ));
+        let (left, right) = (label(x.0), label(x.1));
+        let gap = self
+            .width
+            .saturating_sub(left.chars().count() + right.chars().count())
+            .max(1);
+        lines.push(format!(
+            "{:>1$}  {2}{3:4$}{5}",
+            "", margin, left, "", gap, right
+        ));
+        for (index, s) in series.iter().enumerate() {
+            let marker = if self.ascii {
+                MARKERS[index % MARKERS.len()].to_string()
+            } else {
+                "──".to_string()
+            };
+            lines.push(format!(
+                "{:>1$}  {2} {3}",
+                "",
+                margin,
+                self.paint(&marker, index),
+                s.name
+            ));
+        }
+        lines.join("\n")
+    }
+
+    fn cell(
+        &self,
+        dots: &[Option<usize>],
+        cx: usize,
+        cy: usize,
+        axis_row: Option<usize>,
+        axis_col: Option<usize>,
+    ) -> String {
+        let cols = self.columns();
+        if self.ascii {
+            return match dots[cy * cols + cx] {
+                Option::Some(i) => self.paint(&MARKERS[i % MARKERS.len()].to_string(), i),
+                Option::None => {
+                    match (axis_row == Option::Some(cy), axis_col == Option::Some(cx)) {
+                        (true, true) => "+",
+                        (true, false) => "-",
+                        (false, true) => "|",
+                        (false, false) => " ",
+                    }
+                    .to_string()
+                }
+            };
+        }
+        let mut bits = 0;
+        let mut series = Option::None;
+        for (dy, row) in BRAILLE.iter().enumerate() {
+            for (dx, bit) in row.iter().enumerate() {
+                let (r, c) = (cy * 4 + dy, cx * 2 + dx);
+                if let Option::Some(i) = dots[r * cols + c] {
+                    series = Option::Some(i);
+                    bits |= bit;
+                } else if axis_row == Option::Some(r) || axis_col == Option::Some(c) {
+                    bits |= bit;
+                }
+            }
+        }
+        let c = match bits {
+            0 => ' ',
+            _ => std::char::from_u32(0x2800 + bits).unwrap_or(' '),
+        };
+        match series {
+            Option::Some(i) => self.paint(&c.to_string(), i),
+            Option::None => c.to_string(),
+        }
+    }
+
+    fn paint(&self, s: &str, index: usize) -> String {
+        if self.color {
+            color::series(s, index)
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+// Compile a function of the variable, other unknown names are errors
+pub(crate) fn function(input: &str, var: &str, context: &Context) -> Result<CompiledExpr, String> {
+    let mut context = context.clone();
+    context.remove(var);
+    let f = compile::compile_with(input, context)?;
+    match f.inputs().iter().find(|&n| n != var) {
+        Option::Some(name) => Result::Err(format!(
+            "Error: Unknown variable '{}' in '{}' (the plot is over {})",
+            name,
+            input.trim(),
+            var
+        )),
+        Option::None => Result::Ok(f),
+    }
+}
+
+// None where the function is undefined
+pub(crate) fn value_at(f: &mut CompiledExpr, t: f64) -> Option<f64> {
+    let used = f.inputs().len();
+    f.eval(&[t][..used]).ok().filter(|y| y.is_finite())
+}
+
+// n evenly spaced values from start to stop
+pub(crate) fn steps(start: f64, stop: f64, n: usize) -> impl Iterator<Item = f64> {
+    (0..n).map(move |i| start + (stop - start) * i as f64 / (n - 1).max(1) as f64)
+}
+
+// The range of the values, leaving out the extreme values close to poles
+fn y_range(series: &[Series]) -> (f64, f64) {
+    range(
+        series
+            .iter()
+            .flat_map(|s| s.values.iter().filter_map(|&v| v))
+            .collect(),
+    )
+}
+
+pub(crate) fn range(mut values: Vec<f64>) -> (f64, f64) {
+    if values.is_empty() {
+        return (-1.0, 1.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let quantile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+    let (lo, hi) = (quantile(0.05), quantile(0.95));
+    let span = (hi - lo) / 2.0;
+    let y0 = values[0].max(lo - span);
+    let y1 = values[values.len() - 1].min(hi + span);
+    if y1 - y0 <= 1e-12 * y0.abs().max(y1.abs()) {
+        (y0 - 1.0, y1 + 1.0)
+    } else {
+        (y0, y1)
+    }
+}
+
+// Numbers on the axes with about four significant digits
+pub(crate) fn label(x: f64) -> String {
+    if x == 0.0 {
+        return "0".to_string();
+    }
+    let digits = (3 - x.abs().log10().floor() as i32).clamp(0, 12) as usize;
+    let s = format!("{:.*}", digits, x);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    match s {
+        "-0" => "0".to_string(),
+        _ => s.to_string(),
+    }
+}
@@ -1,11 +1,18 @@
 use natural_constants::physics;
 
+use crate::time;
+use crate::value::Locale;
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Token<'a> {
     Number(f64),
+    // Seconds since 1970-01-01
+    Date(f64),
     Unknown,
     Text(&'a str),
+    String(&'a str),
     Function(Function),
+    Command(Command),
     Addition,
     Subtraction,
     Multiplication,
@@ -13,9 +20,13 @@ pub enum Token<'a> {
     Modulo,
     Power,
     Factorial,
+    DoubleFactorial,
+    Degree,
     Comma,
     Lparen,
     Rparen,
+    Lbracket,
+    Rbracket,
     Equals,
     Bar,
     END,
@@ -25,15 +36,20 @@ impl std::fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
             Token::Number(x) => write!(f, "Value: {}", x),
+            Token::Date(x) => write!(f, "Date: {}", time::format_date(x)),
             Token::Text(ref s) => write!(f, "Text: {}", s),
+            Token::String(ref s) => write!(f, "String: \"{}\"", s),
             Token::END => write!(f, "END"),
             Token::Unknown => write!(f, "Unknown"),
             Token::Comma => write!(f, "Symbol: ,"),
             Token::Lparen => write!(f, "Symbol: ("),
             Token::Rparen => write!(f, "Symbol: )"),
+            Token::Lbracket => write!(f, "Symbol: ["),
+            Token::Rbracket => write!(f, "Symbol: ]"),
             Token::Equals => write!(f, "Symbol: ="),
             Token::Bar => write!(f, "Symbol: |"),
             Token::Function(ref s) => write!(f, "Function: {}", s),
+            Token::Command(ref s) => write!(f, "Command: {}", s),
             Token::Addition => write!(f, "Operator: +"),
             Token::Subtraction => write!(f, "Operator: -"),
             Token::Multiplication => write!(f, "Operator: *"),
@@ -41,6 +57,8 @@ impl std::fmt::Display for Token<'_> {
             Token::Modulo => write!(f, "Operator: %"),
             Token::Power => write!(f, "Operator: ^"),
             Token::Factorial => write!(f, "Operator: !"),
+            Token::DoubleFactorial => write!(f, "Operator: !!"),
+            Token::Degree => write!(f, "Operator: °"),
         }
     }
 }
@@ -62,6 +80,304 @@ pub enum Function {
     Mean,
     Product,
     Exp,
+    Rnd,
+    Seed,
+    RandInt,
+    Uniform,
+    Normal,
+    Exponential,
+    Dice,
+    NCr,
+    NPr,
+    Gcd,
+    Lcm,
+    IsPrime,
+    Factor,
+    Fib,
+    PowMod,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Sec,
+    Csc,
+    Cot,
+    Log2,
+    Log10,
+    Cbrt,
+    NthRoot,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Sign,
+    Hypot,
+    Clamp,
+    Gamma,
+    LGamma,
+    Beta,
+    Erf,
+    Erfc,
+    ErfInv,
+    BesselJ0,
+    BesselJ1,
+    BesselJn,
+    BesselY0,
+    Zeta,
+    LambertW,
+    Pv,
+    Fv,
+    Pmt,
+    Nper,
+    Rate,
+    Npv,
+    Irr,
+    Compound,
+    Now,
+    Today,
+    Transpose,
+    Det,
+    Inv,
+    Rank,
+    Trace,
+    Solve,
+    Eig,
+    Identity,
+    Zeros,
+    Simplify,
+    Table,
+}
+
+// A parameter of a function, the optional ones come last
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Param {
+    Required(&'static str),
+    // Filled in with the value when left out
+    Default(&'static str, f64),
+    // Left out, and handled by the function (the text describes the default)
+    Optional(&'static str, &'static str),
+}
+
+impl Param {
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Required(name) | Param::Default(name, _) | Param::Optional(name, _) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Param::Required(name) => write!(f, "{}", name),
+            Param::Default(name, x) => write!(f, "{}={}", name, x),
+            Param::Optional(name, s) => write!(f, "{}={}", name, s),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Signature {
+    pub params: &'static [Param],
+    // Whether the last parameter can be repeated, as in sum(x, ...)
+    pub variadic: bool,
+}
+
+impl Signature {
+    const fn new(params: &'static [Param]) -> Signature {
+        Signature {
+            params,
+            variadic: false,
+        }
+    }
+
+    const fn variadic(params: &'static [Param]) -> Signature {
+        Signature {
+            params,
+            variadic: true,
+        }
+    }
+
+    pub fn min(&self) -> usize {
+        self.params
+            .iter()
+            .filter(|p| matches!(p, Param::Required(_)))
+            .count()
+    }
+
+    pub fn max(&self) -> usize {
+        if self.variadic {
+            usize::MAX
+        } else {
+            self.params.len()
+        }
+    }
+
+    // The arguments with defaults added for the left out parameters, None when nothing is added
+    pub fn fill(&self, args: &[f64]) -> Option<Vec<f64>> {
+        let defaults: Vec<f64> = self
+            .params
+            .iter()
+            .skip(args.len())
+            .map_while(|p| match *p {
+                Param::Default(_, x) => Option::Some(x),
+                _ => Option::None,
+            })
+            .collect();
+        if defaults.is_empty() {
+            return Option::None;
+        }
+        Option::Some(args.iter().copied().chain(defaults).collect())
+    }
+}
+
+const NONE: &[Param] = &[];
+const X: &[Param] = &[Param::Required("x")];
+const N: &[Param] = &[Param::Required("n")];
+const A: &[Param] = &[Param::Required("A")];
+const XY: &[Param] = &[Param::Required("x"), Param::Required("y")];
+const AB: &[Param] = &[Param::Required("a"), Param::Required("b")];
+const NK: &[Param] = &[Param::Required("n"), Param::Required("k")];
+
+impl Function {
+    pub fn signature(self) -> Signature {
+        match self {
+            Function::Log => Signature::new(&[Param::Required("x"), Param::Optional("base", "e")]),
+            Function::Atan2 => Signature::new(&[Param::Required("y"), Param::Required("x")]),
+            Function::Sum | Function::Mean | Function::Product | Function::Gcd | Function::Lcm => {
+                Signature::variadic(X)
+            }
+            Function::Rnd | Function::Now | Function::Today => Signature::new(NONE),
+            Function::Seed | Function::IsPrime | Function::Factor | Function::Fib => {
+                Signature::new(N)
+            }
+            Function::Identity => Signature::new(N),
+            Function::RandInt | Function::Uniform | Function::Beta => Signature::new(AB),
+            Function::Normal => Signature::new(&[Param::Required("mu"), Param::Required("sigma")]),
+            Function::Exponential => Signature::new(&[Param::Required("lambda")]),
+            Function::Dice => Signature::new(&[
+                Param::Required("count"),
+                Param::Required("sides"),
+                Param::Default("bonus", 0.0),
+            ]),
+            Function::NCr | Function::NPr => Signature::new(NK),
+            Function::PowMod => Signature::new(&[
+                Param::Required("base"),
+                Param::Required("exp"),
+                Param::Required("mod"),
+            ]),
+            Function::NthRoot => Signature::new(&[Param::Required("x"), Param::Required("n")]),
+            Function::Round => {
+                Signature::new(&[Param::Required("x"), Param::Default("digits", 0.0)])
+            }
+            Function::Hypot => Signature::new(XY),
+            Function::Clamp => Signature::new(&[
+                Param::Required("x"),
+                Param::Required("min"),
+                Param::Required("max"),
+            ]),
+            Function::BesselJn => Signature::new(&[Param::Required("n"), Param::Required("x")]),
+            Function::Zeta => Signature::new(&[Param::Required("s")]),
+            Function::Pv => Signature::new(&[
+                Param::Required("rate"),
+                Param::Required("n"),
+                Param::Required("pmt"),
+                Param::Default("fv", 0.0),
+            ]),
+            Function::Fv => Signature::new(&[
+                Param::Required("rate"),
+                Param::Required("n"),
+                Param::Required("pmt"),
+                Param::Default("pv", 0.0),
+            ]),
+            Function::Pmt => Signature::new(&[
+                Param::Required("rate"),
+                Param::Required("n"),
+                Param::Required("pv"),
+                Param::Default("fv", 0.0),
+            ]),
+            Function::Nper => Signature::new(&[
+                Param::Required("rate"),
+                Param::Required("pmt"),
+                Param::Required("pv"),
+                Param::Default("fv", 0.0),
+            ]),
+            Function::Rate => Signature::new(&[
+                Param::Required("n"),
+                Param::Required("pmt"),
+                Param::Required("pv"),
+                Param::Default("fv", 0.0),
+            ]),
+            Function::Npv => Signature::variadic(&[Param::Required("rate"), Param::Required("x")]),
+            Function::Irr => Signature::variadic(XY),
+            Function::Compound => Signature::new(&[
+                Param::Required("principal"),
+                Param::Required("rate"),
+                Param::Required("years"),
+                Param::Default("times", 1.0),
+            ]),
+            Function::Transpose
+            | Function::Det
+            | Function::Inv
+            | Function::Rank
+            | Function::Trace
+            | Function::Eig => Signature::new(A),
+            Function::Solve => Signature::new(&[Param::Required("A"), Param::Required("b")]),
+            Function::Zeros => {
+                Signature::new(&[Param::Required("rows"), Param::Optional("cols", "rows")])
+            }
+            Function::Simplify => Signature::new(&[Param::Required("expr")]),
+            Function::Table => Signature::new(&[
+                Param::Required("expr"),
+                Param::Required("x"),
+                Param::Required("start"),
+                Param::Required("stop"),
+                Param::Required("step"),
+            ]),
+            _ => Signature::new(X),
+        }
+    }
+
+    // How the function is called, such as round(x, digits=0) or sum(x, ...)
+    pub fn syntax(self) -> String {
+        let sig = self.signature();
+        let mut params: Vec<String> = sig.params.iter().map(|p| p.to_string()).collect();
+        if sig.variadic {
+            params.push("...".to_string());
+        }
+        format!("{}({})", self, params.join(", "))
+    }
+
+    // An error message when the number of arguments does not match
+    pub fn check_arity(self, count: usize) -> Result<(), String> {
+        let signature = self.signature();
+        let name = self.to_string();
+        check_arity(
+            &name,
+            signature.min(),
+            signature.max(),
+            count,
+            &self.syntax(),
+        )
+    }
+
+    // Functions that give a different result each time, or change the random state
+    pub fn is_random(self) -> bool {
+        matches!(
+            self,
+            Function::Rnd
+                | Function::RandInt
+                | Function::Uniform
+                | Function::Normal
+                | Function::Exponential
+                | Function::Dice
+                | Function::Seed
+                | Function::Now
+                | Function::Today
+        )
+    }
 }
 
 impl std::fmt::Display for Function {
@@ -82,53 +398,355 @@ impl std::fmt::Display for Function {
             Function::Mean => write!(f, "mean"),
             Function::Product => write!(f, "product"),
             Function::Exp => write!(f, "exp"),
+            Function::Rnd => write!(f, "rnd"),
+            Function::Seed => write!(f, "seed"),
+            Function::RandInt => write!(f, "rand_int"),
+            Function::Uniform => write!(f, "uniform"),
+            Function::Normal => write!(f, "normal"),
+            Function::Exponential => write!(f, "exponential"),
+            Function::Dice => write!(f, "dice"),
+            Function::NCr => write!(f, "nCr"),
+            Function::NPr => write!(f, "nPr"),
+            Function::Gcd => write!(f, "gcd"),
+            Function::Lcm => write!(f, "lcm"),
+            Function::IsPrime => write!(f, "isprime"),
+            Function::Factor => write!(f, "factor"),
+            Function::Fib => write!(f, "fib"),
+            Function::PowMod => write!(f, "powmod"),
+            Function::Sinh => write!(f, "sinh"),
+            Function::Cosh => write!(f, "cosh"),
+            Function::Tanh => write!(f, "tanh"),
+            Function::Asinh => write!(f, "asinh"),
+            Function::Acosh => write!(f, "acosh"),
+            Function::Atanh => write!(f, "atanh"),
+            Function::Sec => write!(f, "sec"),
+            Function::Csc => write!(f, "csc"),
+            Function::Cot => write!(f, "cot"),
+            Function::Log2 => write!(f, "log2"),
+            Function::Log10 => write!(f, "log10"),
+            Function::Cbrt => write!(f, "cbrt"),
+            Function::NthRoot => write!(f, "nthroot"),
+            Function::Floor => write!(f, "floor"),
+            Function::Ceil => write!(f, "ceil"),
+            Function::Round => write!(f, "round"),
+            Function::Trunc => write!(f, "trunc"),
+            Function::Sign => write!(f, "sign"),
+            Function::Hypot => write!(f, "hypot"),
+            Function::Clamp => write!(f, "clamp"),
+            Function::Gamma => write!(f, "gamma"),
+            Function::LGamma => write!(f, "lgamma"),
+            Function::Beta => write!(f, "beta"),
+            Function::Erf => write!(f, "erf"),
+            Function::Erfc => write!(f, "erfc"),
+            Function::ErfInv => write!(f, "erfinv"),
+            Function::BesselJ0 => write!(f, "besselj0"),
+            Function::BesselJ1 => write!(f, "besselj1"),
+            Function::BesselJn => write!(f, "besseljn"),
+            Function::BesselY0 => write!(f, "bessely0"),
+            Function::Zeta => write!(f, "zeta"),
+            Function::LambertW => write!(f, "lambertw"),
+            Function::Pv => write!(f, "pv"),
+            Function::Fv => write!(f, "fv"),
+            Function::Pmt => write!(f, "pmt"),
+            Function::Nper => write!(f, "nper"),
+            Function::Rate => write!(f, "rate"),
+            Function::Npv => write!(f, "npv"),
+            Function::Irr => write!(f, "irr"),
+            Function::Compound => write!(f, "compound"),
+            Function::Now => write!(f, "now"),
+            Function::Today => write!(f, "today"),
+            Function::Transpose => write!(f, "transpose"),
+            Function::Det => write!(f, "det"),
+            Function::Inv => write!(f, "inv"),
+            Function::Rank => write!(f, "rank"),
+            Function::Trace => write!(f, "trace"),
+            Function::Solve => write!(f, "solve"),
+            Function::Eig => write!(f, "eig"),
+            Function::Identity => write!(f, "identity"),
+            Function::Zeros => write!(f, "zeros"),
+            Function::Simplify => write!(f, "simplify"),
+            Function::Table => write!(f, "table"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Command {
+    Mode,
+    Const,
+    Vars,
+    Who,
+    Del,
+    Clear,
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Command::Mode => write!(f, "mode"),
+            Command::Const => write!(f, "const"),
+            Command::Vars => write!(f, "vars"),
+            Command::Who => write!(f, "who"),
+            Command::Del => write!(f, "del"),
+            Command::Clear => write!(f, "clear"),
         }
     }
 }
 
+// Every constant, function and command name understood by `Scanner::parse_text`, which
+// ignores case
+const NAMES: &[(&str, Token<'static>)] = &[
+    // Constants
+    ("e", Token::Number(std::f64::consts::E)),
+    ("pi", Token::Number(std::f64::consts::PI)),
+    ("tau", Token::Number(std::f64::consts::PI * 2.0)),
+    ("sqrt2", Token::Number(std::f64::consts::SQRT_2)),
+    ("deg2rad", Token::Number(std::f64::consts::PI / 180.0)),
+    (
+        "rad2deg",
+        Token::Number(std::f64::consts::FRAC_1_PI * 180.0),
+    ),
+    ("epsilon", Token::Number(f64::EPSILON)),
+    ("c", Token::Number(physics::speed_of_light_vac)),
+    // Functions
+    ("ln", Token::Function(Function::Ln)),
+    ("log", Token::Function(Function::Log)),
+    ("exp", Token::Function(Function::Exp)),
+    ("sin", Token::Function(Function::Sin)),
+    ("cos", Token::Function(Function::Cos)),
+    ("tan", Token::Function(Function::Tan)),
+    ("abs", Token::Function(Function::Abs)),
+    ("sqrt", Token::Function(Function::Sqrt)),
+    ("asin", Token::Function(Function::Asin)),
+    ("arcsin", Token::Function(Function::Asin)),
+    ("acos", Token::Function(Function::Acos)),
+    ("arccos", Token::Function(Function::Acos)),
+    ("atan", Token::Function(Function::Atan)),
+    ("arctan", Token::Function(Function::Atan)),
+    ("atan2", Token::Function(Function::Atan2)),
+    ("arctan2", Token::Function(Function::Atan2)),
+    ("sum", Token::Function(Function::Sum)),
+    ("mean", Token::Function(Function::Mean)),
+    ("avg", Token::Function(Function::Mean)),
+    ("average", Token::Function(Function::Mean)),
+    ("prod", Token::Function(Function::Product)),
+    ("product", Token::Function(Function::Product)),
+    ("rnd", Token::Function(Function::Rnd)),
+    ("seed", Token::Function(Function::Seed)),
+    ("rand_int", Token::Function(Function::RandInt)),
+    ("uniform", Token::Function(Function::Uniform)),
+    ("normal", Token::Function(Function::Normal)),
+    ("exponential", Token::Function(Function::Exponential)),
+    ("dice", Token::Function(Function::Dice)),
+    ("nCr", Token::Function(Function::NCr)),
+    ("choose", Token::Function(Function::NCr)),
+    ("nPr", Token::Function(Function::NPr)),
+    ("gcd", Token::Function(Function::Gcd)),
+    ("lcm", Token::Function(Function::Lcm)),
+    ("isprime", Token::Function(Function::IsPrime)),
+    ("factor", Token::Function(Function::Factor)),
+    ("fib", Token::Function(Function::Fib)),
+    ("powmod", Token::Function(Function::PowMod)),
+    ("sinh", Token::Function(Function::Sinh)),
+    ("cosh", Token::Function(Function::Cosh)),
+    ("tanh", Token::Function(Function::Tanh)),
+    ("asinh", Token::Function(Function::Asinh)),
+    ("arsinh", Token::Function(Function::Asinh)),
+    ("acosh", Token::Function(Function::Acosh)),
+    ("arcosh", Token::Function(Function::Acosh)),
+    ("atanh", Token::Function(Function::Atanh)),
+    ("artanh", Token::Function(Function::Atanh)),
+    ("sec", Token::Function(Function::Sec)),
+    ("csc", Token::Function(Function::Csc)),
+    ("cosec", Token::Function(Function::Csc)),
+    ("cot", Token::Function(Function::Cot)),
+    ("log2", Token::Function(Function::Log2)),
+    ("log10", Token::Function(Function::Log10)),
+    ("cbrt", Token::Function(Function::Cbrt)),
+    ("nthroot", Token::Function(Function::NthRoot)),
+    ("floor", Token::Function(Function::Floor)),
+    ("ceil", Token::Function(Function::Ceil)),
+    ("round", Token::Function(Function::Round)),
+    ("trunc", Token::Function(Function::Trunc)),
+    ("sign", Token::Function(Function::Sign)),
+    ("sgn", Token::Function(Function::Sign)),
+    ("hypot", Token::Function(Function::Hypot)),
+    ("clamp", Token::Function(Function::Clamp)),
+    ("gamma", Token::Function(Function::Gamma)),
+    ("lgamma", Token::Function(Function::LGamma)),
+    ("beta", Token::Function(Function::Beta)),
+    ("erf", Token::Function(Function::Erf)),
+    ("erfc", Token::Function(Function::Erfc)),
+    ("erfinv", Token::Function(Function::ErfInv)),
+    ("besselj0", Token::Function(Function::BesselJ0)),
+    ("besselj1", Token::Function(Function::BesselJ1)),
+    ("besseljn", Token::Function(Function::BesselJn)),
+    ("bessely0", Token::Function(Function::BesselY0)),
+    ("zeta", Token::Function(Function::Zeta)),
+    ("lambertw", Token::Function(Function::LambertW)),
+    ("pv", Token::Function(Function::Pv)),
+    ("fv", Token::Function(Function::Fv)),
+    ("pmt", Token::Function(Function::Pmt)),
+    ("nper", Token::Function(Function::Nper)),
+    ("rate", Token::Function(Function::Rate)),
+    ("npv", Token::Function(Function::Npv)),
+    ("irr", Token::Function(Function::Irr)),
+    ("compound", Token::Function(Function::Compound)),
+    ("now", Token::Function(Function::Now)),
+    ("today", Token::Function(Function::Today)),
+    ("transpose", Token::Function(Function::Transpose)),
+    ("det", Token::Function(Function::Det)),
+    ("inv", Token::Function(Function::Inv)),
+    ("rank", Token::Function(Function::Rank)),
+    ("trace", Token::Function(Function::Trace)),
+    ("solve", Token::Function(Function::Solve)),
+    ("eig", Token::Function(Function::Eig)),
+    ("eigenvalues", Token::Function(Function::Eig)),
+    ("identity", Token::Function(Function::Identity)),
+    ("eye", Token::Function(Function::Identity)),
+    ("zeros", Token::Function(Function::Zeros)),
+    ("simplify", Token::Function(Function::Simplify)),
+    ("table", Token::Function(Function::Table)),
+    // Commands
+    ("mode", Token::Command(Command::Mode)),
+    ("const", Token::Command(Command::Const)),
+    ("vars", Token::Command(Command::Vars)),
+    ("who", Token::Command(Command::Who)),
+    ("del", Token::Command(Command::Del)),
+    ("clear", Token::Command(Command::Clear)),
+];
+
+// The names alone, for suggesting a name close to an unknown one
+pub const KEYWORDS: &[&str] = &keywords();
+
+const fn keywords() -> [&'static str; NAMES.len()] {
+    let mut names = [""; NAMES.len()];
+    let mut i = 0;
+    while i < NAMES.len() {
+        names[i] = NAMES[i].0;
+        i += 1;
+    }
+    names
+}
+
+#[derive(Clone)]
 pub struct Scanner<'a> {
     string: &'a str,
     iterator: std::iter::Peekable<std::str::CharIndices<'a>>,
     index_current: usize,
     index_next: usize,
+    end_current: usize,
+    end_next: usize,
     token_current: Token<'a>,
     token_next: Token<'a>,
+    // A ^ has been returned for superscript digits, which come next as a number
+    superscript: bool,
+    // 3,5 is 3.5, and ; separates values
+    decimal_comma: bool,
+    // Open parentheses and brackets, inside which a newline does not end the expression
+    depth: usize,
 }
 
 impl<'a> Scanner<'a> {
     pub fn new(string: &'a str) -> Scanner<'a> {
+        Scanner::with_locale(string, &Locale::default())
+    }
+
+    pub fn with_locale(string: &'a str, locale: &Locale) -> Scanner<'a> {
+        Scanner::start(string, locale.decimal_comma())
+    }
+
+    fn start(string: &'a str, decimal_comma: bool) -> Scanner<'a> {
         let mut sc = Scanner {
-            string: string,
+            string,
             iterator: string.char_indices().peekable(),
             index_current: 0,
             index_next: 0,
+            end_current: 0,
+            end_next: 0,
             token_current: Token::Unknown,
             token_next: Token::Unknown,
+            superscript: false,
+            decimal_comma,
+            depth: 0,
         };
         sc.next();
         sc
     }
 
     pub fn print_pos(&self) -> String {
-        let i = if self.index_current > 0 {
-            self.index_current
-        } else {
-            0
-        };
-        format!(
-            "{}\nPosition: {}\n{}\n{:4$}^",
-            self.token_current, self.index_current, self.string, "", i
-        )
+        Scanner::print_at(self.string, self.token_current, self.index_current)
+    }
+
+    pub fn print_at(string: &str, token: Token, index: usize) -> String {
+        // The caret is placed by characters, not bytes (2×π)
+        let column = |s: &str| s.chars().count();
+        let index = index.min(string.len());
+        match Scanner::line_at(string, index) {
+            Option::Some((line, start, text)) => format!(
+                "{}\nLine: {}\n{}\n{:4$}^",
+                token,
+                line,
+                text,
+                "",
+                column(&string[start..index])
+            ),
+            Option::None => format!(
+                "{}\nPosition: {}\n{}\n{:4$}^",
+                token,
+                index,
+                string,
+                "",
+                column(&string[..index])
+            ),
+        }
+    }
+
+    // For input with several lines: the number, start and text of the line around an index
+    pub fn line_at(string: &str, index: usize) -> Option<(usize, usize, &str)> {
+        if !string.trim_end().contains('\n') {
+            return Option::None;
+        }
+        let index = index.min(string.len());
+        let start = string[..index].rfind('\n').map_or(0, |i| i + 1);
+        let end = string[index..]
+            .find('\n')
+            .map_or(string.len(), |i| index + i);
+        let line = string[..start].matches('\n').count() + 1;
+        Option::Some((line, start, string[start..end].trim_end_matches('\r')))
+    }
+
+    // The token starting at a byte index of the input, read with the same locale
+    pub fn token_at(&self, index: usize) -> Token<'a> {
+        Scanner::start(&self.string[index..], self.decimal_comma).next()
     }
 
-    pub fn next(self: &mut Self) -> Token<'a> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Token<'a> {
         self.index_current = self.index_next;
+        self.end_current = self.end_next;
         self.token_current = self.token_next;
         self.token_next = self.get_next_token();
+        self.end_next = match self.token_next {
+            Token::END => self.string.len(),
+            _ => self.iterator.peek().map_or(self.string.len(), |&(i, _)| i),
+        };
         self.token_current
     }
 
-    #[allow(dead_code)]
+    // Byte range of the current token in the input
+    pub fn span(&self) -> (usize, usize) {
+        (self.index_current, self.end_current.max(self.index_current))
+    }
+
+    pub fn peek_span(&self) -> (usize, usize) {
+        (self.index_next, self.end_next.max(self.index_next))
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.string
+    }
+
     pub fn current(&self) -> Token<'a> {
         self.token_current
     }
@@ -137,7 +755,26 @@ impl<'a> Scanner<'a> {
         self.token_next
     }
 
+    // A decimal comma is followed by a digit, otherwise it still separates values
+    fn comma(&self, (i, c): (usize, char)) -> bool {
+        self.decimal_comma
+            && c == ','
+            && self.string[i + 1..].starts_with(|d: char| d.is_ascii_digit())
+    }
+
     fn get_next_token(&mut self) -> Token<'a> {
+        // x² == x^2, the ^ is returned before the digits are read
+        if let Option::Some(&(i, c)) = self.iterator.peek() {
+            if superscript(c).is_some() && !self.superscript {
+                self.superscript = true;
+                self.index_next = i;
+                return Token::Power;
+            }
+        }
+        if self.superscript {
+            self.superscript = false;
+            return self.superscript_number();
+        }
         let oc = match self.iterator.next() {
             Option::None => return Token::END,
             Option::Some(c) => c,
@@ -145,22 +782,81 @@ impl<'a> Scanner<'a> {
         self.index_next = oc.0;
         match oc.1 {
             '+' => Token::Addition,
-            '-' => Token::Subtraction,
-            '/' | ':' => Token::Division,
+            '-' | '−' => Token::Subtraction,
+            '/' | ':' | '÷' => Token::Division,
+            '×' | '·' | '⋅' => Token::Multiplication,
+            // π is never part of a name, so that πr² is π * r^2
+            'π' => Token::Number(std::f64::consts::PI),
+            '√' => Token::Function(Function::Sqrt),
+            '∛' => Token::Function(Function::Cbrt),
             '%' => Token::Modulo,
             '^' => Token::Power,
-            '!' => Token::Factorial,
+            '!' => {
+                // !! == double factorial
+                match self.iterator.peek() {
+                    Option::Some(&(_, '!')) => {
+                        self.iterator.next();
+                        Token::DoubleFactorial
+                    }
+                    _ => Token::Factorial,
+                }
+            }
             ',' | ';' => Token::Comma,
-            '(' => Token::Lparen,
-            ')' => Token::Rparen,
-            '[' => Token::Lparen,
-            ']' => Token::Rparen,
+            '(' => {
+                self.depth += 1;
+                Token::Lparen
+            }
+            ')' => {
+                self.depth = self.depth.saturating_sub(1);
+                Token::Rparen
+            }
+            '[' => {
+                self.depth += 1;
+                Token::Lbracket
+            }
+            ']' => {
+                self.depth = self.depth.saturating_sub(1);
+                Token::Rbracket
+            }
+            // A newline separates expressions like a comma, except inside parentheses
+            '\n' if self.depth == 0 => Token::Comma,
+            // A comment lasts until the end of the line
+            '#' => {
+                while self.iterator.peek().is_some_and(|&(_, c)| c != '\n') {
+                    self.iterator.next();
+                }
+                self.get_next_token()
+            }
+            // A backslash at the end of a line continues the expression on the next line
+            '\\' => {
+                let rest = &self.string[oc.0 + 1..];
+                let line = rest.find('\n').map_or(rest, |i| &rest[..i]);
+                if !line.trim().is_empty() {
+                    return Token::Unknown;
+                }
+                let end = oc.0 + 1 + line.len();
+                while self.iterator.peek().is_some_and(|&(i, _)| i <= end) {
+                    self.iterator.next();
+                }
+                self.get_next_token()
+            }
             '=' => Token::Equals,
             '|' => Token::Bar,
+            '°' => Token::Degree,
+            '"' => {
+                let start = oc.0 + 1;
+                loop {
+                    match self.iterator.next() {
+                        Option::None => return Token::Unknown,
+                        Option::Some((i, '"')) => return Token::String(&self.string[start..i]),
+                        _ => {}
+                    }
+                }
+            }
             '*' => {
                 // ** == ^
                 match self.iterator.peek() {
-                    Option::None => return Token::Multiplication,
+                    Option::None => Token::Multiplication,
                     Option::Some(d) => match d.1 {
                         '*' => {
                             self.iterator.next();
@@ -171,13 +867,21 @@ impl<'a> Scanner<'a> {
                 }
             }
             _ => {
-                let mut end = oc.0;
-                if oc.1.is_numeric() || oc.1 == '.' {
+                let mut end = oc.0 + oc.1.len_utf8() - 1;
+                // Dates such as 2026-10-18 or 2026-10-18T12:30
+                if let Option::Some((date, len)) = time::parse_date(&self.string[oc.0..]) {
+                    while self.iterator.peek().is_some_and(|&(i, _)| i < oc.0 + len) {
+                        self.iterator.next();
+                    }
+                    return date.map_or(Token::Unknown, Token::Date);
+                }
+                if oc.1.is_ascii_digit() || oc.1 == '.' {
                     loop {
-                        match self.iterator.peek() {
+                        match self.iterator.peek().copied() {
                             Option::None => break,
                             Option::Some(d) => {
-                                if d.1.is_numeric() || d.1 == '.' || d.1 == 'E' {
+                                if d.1.is_ascii_digit() || d.1 == '.' || d.1 == 'E' || self.comma(d)
+                                {
                                     end = d.0;
                                 } else {
                                     break;
@@ -186,7 +890,8 @@ impl<'a> Scanner<'a> {
                         };
                         self.iterator.next();
                     }
-                    match self.string[self.index_next..(end + 1)].parse::<f64>() {
+                    let number = self.string[self.index_next..(end + 1)].replace(',', ".");
+                    match number.parse::<f64>() {
                         Result::Ok(n) => Token::Number(n),
                         _ => Token::Unknown,
                     }
@@ -195,8 +900,12 @@ impl<'a> Scanner<'a> {
                         match self.iterator.peek() {
                             Option::None => break,
                             Option::Some(d) => {
-                                if d.1.is_alphanumeric() || d.1 == '_' {
-                                    end = d.0;
+                                if (d.1.is_alphanumeric()
+                                    && superscript(d.1).is_none()
+                                    && d.1 != 'π')
+                                    || d.1 == '_'
+                                {
+                                    end = d.0 + d.1.len_utf8() - 1;
                                 } else {
                                     break;
                                 }
@@ -205,44 +914,79 @@ impl<'a> Scanner<'a> {
                         self.iterator.next();
                     }
                     Scanner::parse_text(&self.string[self.index_next..(end + 1)])
-                } else {
+                } else if oc.1.is_whitespace() {
                     self.get_next_token()
+                } else {
+                    // Skipping unknown characters could silently change the result
+                    Token::Unknown
                 }
             }
         }
     }
 
+    // Superscript digits with an optional sign, such as ⁻¹
+    fn superscript_number(&mut self) -> Token<'a> {
+        let mut sign = 1.0;
+        let mut digits = Option::None;
+        while let Option::Some(&(i, c)) = self.iterator.peek() {
+            match superscript(c) {
+                Option::Some(d) if d >= 0 => {
+                    digits = Option::Some(digits.unwrap_or(0.0) * 10.0 + d as f64)
+                }
+                Option::Some(_) if i == self.index_next && c == '⁻' => sign = -1.0,
+                Option::Some(_) if i == self.index_next => {}
+                _ => break,
+            }
+            self.iterator.next();
+        }
+        digits.map_or(Token::Unknown, |d| Token::Number(sign * d))
+    }
+
     fn parse_text(s: &'a str) -> Token<'a> {
         let l = s.to_lowercase();
-        match l.as_ref() {
-            //Constants
-            "e" => Token::Number(std::f64::consts::E),
-            "pi" => Token::Number(std::f64::consts::PI),
-            "tau" => Token::Number(std::f64::consts::PI * 2.0),
-            "sqrt2" => Token::Number(std::f64::consts::SQRT_2),
-            "deg2rad" => Token::Number(std::f64::consts::PI / 180.0),
-            "rad2deg" => Token::Number(std::f64::consts::FRAC_1_PI * 180.0),
-            "epsilon" => Token::Number(std::f64::EPSILON),
-            "c" => Token::Number(physics::speed_of_light_vac),
-            "rnd" => Token::Number(rand::random::<f64>()),
-            //Functions
-            "ln" => Token::Function(Function::Ln),
-            "log" => Token::Function(Function::Log),
-            "exp" => Token::Function(Function::Exp),
-            "sin" => Token::Function(Function::Sin),
-            "cos" => Token::Function(Function::Cos),
-            "tan" => Token::Function(Function::Tan),
-            "abs" => Token::Function(Function::Abs),
-            "sqrt" => Token::Function(Function::Sqrt),
-            "asin" | "arcsin" => Token::Function(Function::Asin),
-            "acos" | "arccos" => Token::Function(Function::Acos),
-            "atan" | "arctan" => Token::Function(Function::Atan),
-            "atan2" | "arctan2" => Token::Function(Function::Atan2),
-            "sum" => Token::Function(Function::Sum),
-            "mean" | "avg" | "average" => Token::Function(Function::Mean),
-            "prod" | "product" => Token::Function(Function::Product),
-            //Unknown
-            _ => Token::Text(s),
+        // τ is not among the names, as it would be suggested for every single letter
+        let l = if l == "τ" { "tau" } else { l.as_str() };
+        match NAMES.iter().find(|n| n.0.eq_ignore_ascii_case(l)) {
+            Option::Some(&(_, token)) => token,
+            Option::None => Token::Text(s),
         }
     }
 }
+
+// Shared by built-in and user defined functions, max is usize::MAX for variadic functions
+pub fn check_arity(
+    name: &str,
+    min: usize,
+    max: usize,
+    count: usize,
+    syntax: &str,
+) -> Result<(), String> {
+    if (min..=max).contains(&count) {
+        return Result::Ok(());
+    }
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let expected = if max == usize::MAX {
+        format!("at least {} argument{}", min, plural(min))
+    } else if min == max {
+        format!("{} argument{}", min, plural(min))
+    } else {
+        format!("{} to {} arguments", min, max)
+    };
+    Result::Err(format!(
+        "{} expects {}, got {} ({})",
+        name, expected, count, syntax
+    ))
+}
+
+// The value of a superscript digit, and -1 for the signs
+fn superscript(c: char) -> Option<i32> {
+    match c {
+        '⁰' => Option::Some(0),
+        '¹' => Option::Some(1),
+        '²' => Option::Some(2),
+        '³' => Option::Some(3),
+        '⁴'..='⁹' => Option::Some(c as i32 - '⁰' as i32),
+        '⁻' | '⁺' => Option::Some(-1),
+        _ => Option::None,
+    }
+}
@@ -0,0 +1,462 @@
+// Solving equations in one unknown. Polynomials are recognised from the expression and
+// all their roots are found, other equations are solved numerically for real roots.
+
+use crate::ast::{Expr, Node, Operator};
+use crate::context::{Context, Error};
+use crate::value::{Format, Value};
+
+// Highest power of the unknown that is expanded into a polynomial
+const MAX_DEGREE: usize = 64;
+// At most this many real roots are shown, the ones closest to zero
+const MAX_ROOTS: usize = 10;
+
+pub fn solve(
+    context: &mut Context,
+    lhs: &Expr,
+    rhs: &Expr,
+    var: Option<&str>,
+    span: (usize, usize),
+) -> Result<Value, Error> {
+    let name = match var {
+        Option::Some(name) => name.to_string(),
+        Option::None => unknown(context, lhs, rhs, span)?,
+    };
+    // The unknown must not be replaced by a variable with the same name
+    let old = context.remove(&name);
+    let res = solve_for(context, lhs, rhs, &name);
+    context.remove(&name);
+    if let Option::Some(v) = old {
+        context.set(&name, v);
+    }
+    res.map(Value::Text)
+}
+
+fn unknown(
+    context: &Context,
+    lhs: &Expr,
+    rhs: &Expr,
+    span: (usize, usize),
+) -> Result<String, Error> {
+    let mut names = lhs.variables();
+    for name in rhs.variables() {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.retain(|name| context.get(name).is_none());
+    match names.len() {
+        1 => Result::Ok(names[0].to_string()),
+        0 => Result::Err(Error::new(
+            "There is no unknown to solve for (use: solve lhs = rhs for x)",
+            span,
+        )),
+        _ => Result::Err(Error::new(
+            &format!(
+                "There is more than one unknown ({}), use: solve lhs = rhs for x",
+                names.join(", ")
+            ),
+            span,
+        )),
+    }
+}
+
+fn solve_for(context: &mut Context, lhs: &Expr, rhs: &Expr, name: &str) -> Result<String, Error> {
+    let poly = match (
+        polynomial(context, lhs, name)?,
+        polynomial(context, rhs, name)?,
+    ) {
+        (Option::Some(a), Option::Some(b)) => Option::Some(trim(add(&a, &scale(&b, -1.0)))),
+        _ => Option::None,
+    };
+    let (real, complex) = match poly {
+        Option::Some(ref p) if p.len() == 1 => {
+            return Result::Ok(if p[0] == 0.0 {
+                format!("Every {} is a solution", name)
+            } else {
+                "No solution".to_string()
+            })
+        }
+        Option::Some(ref p) => {
+            let roots = polynomial_roots(p);
+            let real = roots.iter().filter(|r| r.1 == 0.0).map(|r| r.0).collect();
+            let complex = roots.into_iter().filter(|r| r.1 != 0.0).collect();
+            (real, complex)
+        }
+        Option::None => (numeric(context, lhs, rhs, name)?, Vec::new()),
+    };
+    let mut real: Vec<f64> = real;
+    let more = real.len() > MAX_ROOTS;
+    if more {
+        // The roots closest to zero are the most interesting
+        real.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+        real.truncate(MAX_ROOTS);
+        real.sort_by(f64::total_cmp);
+    }
+    let format = context.format();
+    let mut solutions: Vec<String> = real.iter().map(|&x| show(x, format)).collect();
+    solutions.extend(complex.iter().map(|&(re, im)| show_complex(re, im, format)));
+    if solutions.is_empty() {
+        return Result::Ok("No solution found".to_string());
+    }
+    let mut text: Vec<String> = solutions
+        .iter()
+        .map(|s| format!("{} = {}", name, s))
+        .collect();
+    if more {
+        text.push("...".to_string());
+    }
+    Result::Ok(text.join(" or "))
+}
+
+// Coefficients of the expression as a polynomial in the unknown, lowest power first.
+// Parts that do not depend on the unknown are evaluated, None if it is not a polynomial.
+fn polynomial(context: &mut Context, e: &Expr, name: &str) -> Result<Option<Vec<f64>>, Error> {
+    if !e.variables().contains(&name) {
+        return context.number(e).map(|x| Option::Some(vec![x]));
+    }
+    let p = match e.node {
+        Node::Variable(_) => vec![0.0, 1.0],
+        Node::Negate(ref a) => match polynomial(context, a, name)? {
+            Option::Some(a) => scale(&a, -1.0),
+            Option::None => return Result::Ok(Option::None),
+        },
+        // 100 + 10% depends on the left side
+        Node::Binary(Operator::Add, _, ref r) | Node::Binary(Operator::Sub, _, ref r)
+            if matches!(r.node, Node::Percent(_)) =>
+        {
+            return Result::Ok(Option::None)
+        }
+        Node::Binary(op, ref l, ref r) => {
+            let (a, b) = match (polynomial(context, l, name)?, polynomial(context, r, name)?) {
+                (Option::Some(a), Option::Some(b)) => (a, b),
+                _ => return Result::Ok(Option::None),
+            };
+            match op {
+                Operator::Add => add(&a, &b),
+                Operator::Sub => add(&a, &scale(&b, -1.0)),
+                Operator::Mul => mul(&a, &b),
+                Operator::Div if b.len() == 1 && b[0] != 0.0 => scale(&a, 1.0 / b[0]),
+                Operator::Pow if a.len() == 1 && b.len() == 1 && a[0].powf(b[0]).is_finite() => {
+                    vec![a[0].powf(b[0])]
+                }
+                // The degree is compared as a float, (x^2)^(10^19) does not fit in usize
+                Operator::Pow
+                    if b.len() == 1
+                        && b[0].fract() == 0.0
+                        && b[0] >= 0.0
+                        && (a.len() - 1) as f64 * b[0] <= MAX_DEGREE as f64 =>
+                {
+                    (0..b[0] as usize).fold(vec![1.0], |p, _| mul(&p, &a))
+                }
+                _ => return Result::Ok(Option::None),
+            }
+        }
+        _ => return Result::Ok(Option::None),
+    };
+    Result::Ok(Option::Some(trim(p)))
+}
+
+fn add(a: &[f64], b: &[f64]) -> Vec<f64> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0.0) + b.get(i).unwrap_or(&0.0))
+        .collect()
+}
+
+fn scale(a: &[f64], s: f64) -> Vec<f64> {
+    a.iter().map(|x| x * s).collect()
+}
+
+fn mul(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut p = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            p[i + j] += x * y;
+        }
+    }
+    p
+}
+
+// Remove leading zero coefficients
+fn trim(mut p: Vec<f64>) -> Vec<f64> {
+    while p.len() > 1 && p[p.len() - 1] == 0.0 {
+        p.pop();
+    }
+    p
+}
+
+// All distinct roots of a polynomial (of degree 1 or more) as (re, im), real roots first
+// in increasing order and real roots have an imaginary part of exactly zero
+pub fn polynomial_roots(p: &[f64]) -> Vec<(f64, f64)> {
+    let p = trim(p.to_vec());
+    let zeros = p.iter().take_while(|&&x| x == 0.0).count();
+    let p = &p[zeros.min(p.len() - 1)..];
+    let mut roots = match p.len() {
+        1 => Vec::new(),
+        2 => vec![(-p[0] / p[1], 0.0)],
+        3 => quadratic(p[2], p[1], p[0]),
+        _ => durand_kerner(p),
+    };
+    if zeros > 0 {
+        roots.push((0.0, 0.0));
+    }
+    let mut roots: Vec<Complex> = roots
+        .into_iter()
+        .map(|(re, im)| (round(re), round(im)))
+        .collect();
+    roots.sort_by(|a, b| {
+        (a.1 != 0.0)
+            .cmp(&(b.1 != 0.0))
+            .then(a.0.total_cmp(&b.0))
+            .then(a.1.total_cmp(&b.1))
+    });
+    roots.dedup();
+    roots
+}
+
+fn quadratic(a: f64, b: f64, c: f64) -> Vec<(f64, f64)> {
+    let d = b * b - 4.0 * a * c;
+    if d < 0.0 {
+        let im = (-d).sqrt() / (2.0 * a).abs();
+        let re = -b / (2.0 * a);
+        vec![(re, -im), (re, im)]
+    } else {
+        // Avoids cancellation when b*b is much larger than 4ac
+        let q = -(b + b.signum() * d.sqrt()) / 2.0;
+        vec![(q / a, 0.0), (c / q, 0.0)]
+    }
+}
+
+type Complex = (f64, f64);
+
+fn cmul(a: Complex, b: Complex) -> Complex {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn cdiv(a: Complex, b: Complex) -> Complex {
+    let d = b.0 * b.0 + b.1 * b.1;
+    ((a.0 * b.0 + a.1 * b.1) / d, (a.1 * b.0 - a.0 * b.1) / d)
+}
+
+fn cabs(a: Complex) -> f64 {
+    a.0.hypot(a.1)
+}
+
+// All roots at once with the Weierstrass (Durand-Kerner) iteration
+fn durand_kerner(p: &[f64]) -> Vec<(f64, f64)> {
+    let n = p.len() - 1;
+    let monic: Vec<f64> = p.iter().map(|c| c / p[n]).collect();
+    // The roots are within this radius (Cauchy's bound)
+    let radius = 1.0 + monic[..n].iter().fold(0.0f64, |m, c| m.max(c.abs()));
+    let mut z: Vec<Complex> = (0..n)
+        .map(|k| {
+            let angle = std::f64::consts::PI * 2.0 * k as f64 / n as f64 + 0.4;
+            (radius * angle.cos() / 2.0, radius * angle.sin() / 2.0)
+        })
+        .collect();
+    for _ in 0..1000 {
+        let mut change = 0.0f64;
+        for i in 0..n {
+            let mut d = (1.0, 0.0);
+            for j in 0..n {
+                if i != j {
+                    d = cmul(d, (z[i].0 - z[j].0, z[i].1 - z[j].1));
+                }
+            }
+            let step = cdiv(evaluate(&monic, z[i]), d);
+            if !step.0.is_finite() || !step.1.is_finite() {
+                continue;
+            }
+            z[i] = (z[i].0 - step.0, z[i].1 - step.1);
+            change = change.max(cabs(step) / (1.0 + cabs(z[i])));
+        }
+        if change < 1e-15 {
+            break;
+        }
+    }
+    // Multiple roots converge slowly and end up as a cluster of estimates. A root of
+    // multiplicity k is a simple root of the (k-1)th derivative, where it is polished.
+    let mut roots: Vec<Complex> = Vec::new();
+    let mut used = vec![false; n];
+    for i in 0..n {
+        if used[i] {
+            continue;
+        }
+        let radius = 1e-3 * (1.0 + cabs(z[i]));
+        let close: Vec<usize> = (i..n)
+            .filter(|&j| !used[j] && cabs((z[i].0 - z[j].0, z[i].1 - z[j].1)) < radius)
+            .collect();
+        close.iter().for_each(|&j| used[j] = true);
+        let k = close.len() as f64;
+        let sum = close
+            .iter()
+            .fold((0.0, 0.0), |s, &j| (s.0 + z[j].0, s.1 + z[j].1));
+        let mean = (sum.0 / k, sum.1 / k);
+        let q = (1..close.len()).fold(p.to_vec(), |q, _| derivative(&q));
+        let x = newton(&q, mean, radius);
+        // Separate roots that are just close to each other are not merged
+        let size: f64 = p
+            .iter()
+            .enumerate()
+            .map(|(i, c)| c.abs() * cabs(x).powi(i as i32))
+            .sum();
+        if close.len() == 1 || cabs(evaluate(p, x)) <= 1e-12 * size {
+            roots.push(x);
+        } else {
+            roots.extend(close.iter().map(|&j| newton(p, z[j], radius)));
+        }
+    }
+    roots
+        .into_iter()
+        .map(|(re, im)| {
+            if im.abs() <= 1e-9 * (1.0 + re.abs()) {
+                (re, 0.0)
+            } else {
+                (re, im)
+            }
+        })
+        .collect()
+}
+
+fn derivative(p: &[f64]) -> Vec<f64> {
+    p.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| c * i as f64)
+        .collect()
+}
+
+fn evaluate(p: &[f64], z: Complex) -> Complex {
+    p.iter().rev().fold((0.0, 0.0), |acc, &c| {
+        let (re, im) = cmul(acc, z);
+        (re + c, im)
+    })
+}
+
+// Newton's method from z, the start is kept if the iteration wanders off
+fn newton(p: &[f64], z: Complex, radius: f64) -> Complex {
+    let dp = derivative(p);
+    let mut x = z;
+    for _ in 0..50 {
+        let step = cdiv(evaluate(p, x), evaluate(&dp, x));
+        if !step.0.is_finite() || !step.1.is_finite() {
+            break;
+        }
+        x = (x.0 - step.0, x.1 - step.1);
+        if cabs(step) <= f64::EPSILON * cabs(x) {
+            break;
+        }
+    }
+    if cabs((x.0 - z.0, x.1 - z.1)) < radius {
+        x
+    } else {
+        z
+    }
+}
+
+// Real roots of lhs - rhs found from sign changes, dense near zero and sparser further out
+fn numeric(context: &mut Context, lhs: &Expr, rhs: &Expr, name: &str) -> Result<Vec<f64>, Error> {
+    let mut first_error = Option::None;
+    let mut f = |x: f64| -> f64 {
+        context.set(name, Value::Number(x));
+        let y = match context.number(lhs) {
+            Result::Ok(a) => context.number(rhs).map(|b| a - b),
+            e => e,
+        };
+        match y {
+            Result::Ok(y) => y,
+            Result::Err(e) => {
+                first_error.get_or_insert(e);
+                f64::NAN
+            }
+        }
+    };
+    let mut xs: Vec<f64> = (-2000..=2000).map(|i| i as f64 / 100.0).collect();
+    for k in 1..=300 {
+        let x = 20.0 * 1.05f64.powi(k);
+        xs.push(x);
+        xs.push(-x);
+    }
+    xs.sort_by(f64::total_cmp);
+    let ys: Vec<f64> = xs.iter().map(|&x| f(x)).collect();
+    let mut roots = Vec::new();
+    for i in 0..xs.len() {
+        if ys[i] == 0.0 {
+            roots.push(xs[i]);
+        } else if i + 1 < xs.len() && ys[i] * ys[i + 1] < 0.0 {
+            if let Option::Some(x) = bisect(&mut f, xs[i], xs[i + 1], ys[i], ys[i + 1]) {
+                roots.push(x);
+            }
+        }
+    }
+    if roots.is_empty() && ys.iter().all(|y| y.is_nan()) {
+        if let Option::Some(e) = first_error {
+            return Result::Err(e);
+        }
+    }
+    roots.dedup_by(|a, b| (*a - *b).abs() <= 1e-9 * (1.0 + a.abs()));
+    Result::Ok(roots)
+}
+
+// A root in [a, b] where f changes sign, None if f has a pole there instead
+fn bisect<F: FnMut(f64) -> f64>(
+    f: &mut F,
+    mut a: f64,
+    mut b: f64,
+    fa: f64,
+    fb: f64,
+) -> Option<f64> {
+    let bound = fa.abs().min(fb.abs());
+    let mut fa = fa;
+    for _ in 0..200 {
+        let m = a + (b - a) / 2.0;
+        if m == a || m == b {
+            break;
+        }
+        let fm = f(m);
+        if fm == 0.0 {
+            return Option::Some(m);
+        } else if fm.is_nan() {
+            return Option::None;
+        } else if (fm < 0.0) == (fa < 0.0) {
+            a = m;
+            fa = fm;
+        } else {
+            b = m;
+        }
+    }
+    let x = if f(a).abs() <= f(b).abs() { a } else { b };
+    if f(x).abs() <= bound {
+        Option::Some(x)
+    } else {
+        Option::None
+    }
+}
+
+// Roots are rounded to 12 significant digits to hide the errors from the iterations
+pub(crate) fn round(x: f64) -> f64 {
+    let x = format!("{:.11e}", x).parse::<f64>().unwrap_or(x);
+    if x == 0.0 {
+        0.0
+    } else {
+        x
+    }
+}
+
+fn show(x: f64, format: &Format) -> String {
+    Value::Number(round(x)).format(format)
+}
+
+fn show_complex(re: f64, im: f64, format: &Format) -> String {
+    let (re, im) = (round(re), round(im));
+    let imag = if im.abs() == 1.0 {
+        "i".to_string()
+    } else {
+        format!("{}i", show(im.abs(), format))
+    };
+    match (re == 0.0, im < 0.0) {
+        (true, false) => imag,
+        (true, true) => format!("-{}", imag),
+        (false, false) => format!("{} + {}", show(re, format), imag),
+        (false, true) => format!("{} - {}", show(re, format), imag),
+    }
+}
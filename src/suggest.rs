@@ -0,0 +1,99 @@
+use std::cmp::Reverse;
+
+// Edit distance (optimal string alignment, so swapped letters count as one edit)
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut v = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                v = v.min(d[i - 2][j - 2] + 1);
+            }
+            d[i][j] = v;
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// The (at most three) candidates closest to the name, best match first
+pub fn suggest<'b, I>(name: &str, candidates: I) -> Vec<&'b str>
+where
+    I: IntoIterator<Item = &'b str>,
+{
+    rank(name, candidates.into_iter().map(|c| (c, false)))
+}
+
+// As `suggest`, but names defined by the user come before built-in ones that are as close
+pub fn suggest_defined<'b, I, J>(name: &str, defined: I, builtin: J) -> Vec<&'b str>
+where
+    I: IntoIterator<Item = &'b str>,
+    J: IntoIterator<Item = &'b str>,
+{
+    let defined = defined.into_iter().map(|c| (c, true));
+    rank(name, defined.chain(builtin.into_iter().map(|c| (c, false))))
+}
+
+// Only the closest candidates are kept, ties go to defined names and then to the longest
+// shared prefix (sped is closer to speed than to seed). The name itself is not suggested.
+fn rank<'b, I>(name: &str, candidates: I) -> Vec<&'b str>
+where
+    I: Iterator<Item = (&'b str, bool)>,
+{
+    let original = name;
+    let name = name.to_lowercase();
+    let limit = std::cmp::max(1, name.chars().count().div_ceil(3));
+    let prefix = |c: &str| {
+        name.chars()
+            .zip(c.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    let mut found: Vec<(usize, bool, Reverse<usize>, &'b str)> = candidates
+        .filter(|&(c, _)| c != original)
+        .map(|(c, defined)| {
+            let lower = c.to_lowercase();
+            (
+                distance(&name, &lower),
+                !defined,
+                Reverse(prefix(&lower)),
+                c,
+            )
+        })
+        .filter(|&(d, ..)| d <= limit)
+        .collect();
+    found.sort();
+    found.dedup_by(|a, b| a.3 == b.3);
+    let best = found.first().map_or(0, |f| f.0);
+    found
+        .into_iter()
+        .take_while(|f| f.0 == best)
+        .take(3)
+        .map(|f| f.3)
+        .collect()
+}
+
+// Formats an "unknown name" message with the suggestions appended
+pub fn message(problem: &str, name: &str, hints: &[&str]) -> String {
+    match hints.len() {
+        0 => format!("{} '{}'", problem, name),
+        1 => format!("{} '{}', did you mean '{}'?", problem, name, hints[0]),
+        n => format!(
+            "{} '{}', did you mean '{}' or '{}'?",
+            problem,
+            name,
+            hints[..n - 1].join("', '"),
+            hints[n - 1]
+        ),
+    }
+}
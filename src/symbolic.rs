@@ -0,0 +1,881 @@
+// Symbolic simplification and printing of expressions. An expression is simplified by
+// expanding it into a sum of terms, each a coefficient times integer powers of atoms.
+// Atoms are everything else, such as variables, function calls and the constants pi and e.
+
+use std::f64::consts::{E, PI};
+
+use crate::ast::{Expr, Node, Operator};
+use crate::context::Context;
+use crate::currency;
+use crate::scanner::Function;
+use crate::table::Style;
+use crate::time;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Notation {
+    // Text that the parser accepts
+    Plain,
+    Unicode,
+    Latex,
+}
+
+impl Notation {
+    pub fn from_name(name: &str) -> Option<Notation> {
+        match name.to_lowercase().as_ref() {
+            "plain" | "text" => Option::Some(Notation::Plain),
+            "unicode" => Option::Some(Notation::Unicode),
+            "latex" | "tex" => Option::Some(Notation::Latex),
+            _ => Option::None,
+        }
+    }
+}
+
+// Products with more terms than this are not expanded
+const MAX_TERMS: usize = 100;
+
+#[derive(Clone, Debug)]
+struct Term {
+    coef: f64,
+    // (key, atom, power) sorted by the key, which is the atom in plain notation
+    factors: Vec<(String, Expr, i64)>,
+}
+
+impl Term {
+    fn same(&self, other: &Term) -> bool {
+        self.factors.len() == other.factors.len()
+            && self
+                .factors
+                .iter()
+                .zip(other.factors.iter())
+                .all(|(a, b)| a.0 == b.0 && a.2 == b.2)
+    }
+
+    // Constant atoms such as pi or sqrt(2) do not count
+    fn degree(&self) -> i64 {
+        self.factors
+            .iter()
+            .filter(|f| !f.1.variables().is_empty())
+            .map(|f| f.2)
+            .sum()
+    }
+}
+
+type Sum = Vec<Term>;
+
+pub fn simplify(context: &mut Context, e: &Expr) -> Expr {
+    let sum = to_sum(context, e);
+    from_sum(&sum, e.span)
+}
+
+fn to_sum(context: &mut Context, e: &Expr) -> Sum {
+    match e.node {
+        Node::Number(x) if x == PI || x == E => atom(e.clone()),
+        Node::Number(x) => constant(x),
+        Node::Negate(ref a) => scale(to_sum(context, a), -1.0),
+        // 100 + 10% is relative to the left side, elsewhere 10% is 0.1
+        Node::Binary(op @ Operator::Add, ref l, ref r)
+        | Node::Binary(op @ Operator::Sub, ref l, ref r)
+            if matches!(r.node, Node::Percent(_)) =>
+        {
+            let mut percent = (**r).clone();
+            for c in percent.children_mut() {
+                *c = simplify(context, c);
+            }
+            let l = simplify(context, l);
+            fold(context, Expr::binary(op, l, percent))
+        }
+        Node::Percent(ref a) => scale(to_sum(context, a), 0.01),
+        Node::Binary(op, ref l, ref r) => {
+            let a = to_sum(context, l);
+            let b = to_sum(context, r);
+            binary(op, a, b, e.span)
+        }
+        Node::Call(f, ref args) => call(context, f, args, e.span),
+        _ => {
+            let mut e = e.clone();
+            for c in e.children_mut() {
+                *c = simplify(context, c);
+            }
+            fold(context, e)
+        }
+    }
+}
+
+fn constant(x: f64) -> Sum {
+    if x == 0.0 {
+        Vec::new()
+    } else {
+        vec![Term {
+            coef: x,
+            factors: Vec::new(),
+        }]
+    }
+}
+
+fn atom(e: Expr) -> Sum {
+    power_of(e, 1)
+}
+
+fn power_of(e: Expr, n: i64) -> Sum {
+    let mut key = format(&e, Notation::Plain);
+    // Random numbers from different calls are not like terms
+    if !constant_expr(&e) && e.variables().is_empty() {
+        key = format!("{}@{}", key, e.span.0);
+    }
+    vec![Term {
+        coef: 1.0,
+        factors: vec![(key, e, n)],
+    }]
+}
+
+// The value of a sum without atoms
+fn value(s: &[Term]) -> Option<f64> {
+    match s.len() {
+        0 => Option::Some(0.0),
+        1 if s[0].factors.is_empty() => Option::Some(s[0].coef),
+        _ => Option::None,
+    }
+}
+
+// Results that look exact are folded, others such as sqrt(2) are kept symbolic
+fn nice(x: f64) -> Option<f64> {
+    if !x.is_finite() {
+        Option::None
+    } else if (x - x.round()).abs() < 1e-12 {
+        Option::Some(x.round())
+    } else if (x * 1e9).round() / 1e9 == x {
+        Option::Some(x)
+    } else {
+        Option::None
+    }
+}
+
+// Expressions that always evaluate to the same value without side effects
+fn constant_expr(e: &Expr) -> bool {
+    let pure = match e.node {
+        Node::Variable(_)
+        | Node::Assign(..)
+        | Node::Const(..)
+        | Node::Unpack(..)
+        | Node::Mode(_)
+        | Node::Workspace(..)
+        | Node::Equation(..)
+        | Node::Simplify(..)
+        | Node::Table(..)
+        | Node::Apply(..)
+        | Node::Define(..) => false,
+        Node::Call(f, _) => !f.is_random(),
+        _ => true,
+    };
+    pure && e.children().into_iter().all(constant_expr)
+}
+
+fn fold(context: &mut Context, e: Expr) -> Sum {
+    if constant_expr(&e) {
+        if let Result::Ok(v) = context.eval(&e) {
+            if let Option::Some(x) = v.as_number().and_then(nice) {
+                return constant(x);
+            }
+        }
+    }
+    atom(e)
+}
+
+fn scale(s: Sum, x: f64) -> Sum {
+    if x == 0.0 {
+        return Vec::new();
+    }
+    s.into_iter()
+        .map(|t| Term {
+            coef: t.coef * x,
+            factors: t.factors,
+        })
+        .collect()
+}
+
+fn add(mut a: Sum, b: Sum) -> Sum {
+    a.extend(b);
+    collect(a)
+}
+
+fn binary(op: Operator, a: Sum, b: Sum, span: (usize, usize)) -> Sum {
+    let keep = |a: &Sum, b: &Sum| atom(Expr::binary(op, from_sum(a, span), from_sum(b, span)));
+    match op {
+        Operator::Add => add(a, b),
+        Operator::Sub => add(a, scale(b, -1.0)),
+        Operator::Mul => mul(a, b, span),
+        Operator::Div if value(&b) == Option::Some(0.0) => keep(&a, &b),
+        // (x + 1) / (x - 1) is not split into two fractions
+        Operator::Div if a.len() > 1 && b.len() > 1 => {
+            mul(atom(from_sum(&a, span)), power(b, -1, span), span)
+        }
+        Operator::Div => mul(a, power(b, -1, span), span),
+        Operator::Mod => match (value(&a), value(&b)) {
+            (Option::Some(x), Option::Some(y)) if y != 0.0 => constant(x % y),
+            _ => keep(&a, &b),
+        },
+        Operator::Pow => match (value(&a), value(&b)) {
+            (_, Option::Some(n)) if n.fract() == 0.0 && n.abs() <= MAX_TERMS as f64 => {
+                power(a, n as i64, span)
+            }
+            (Option::Some(x), Option::Some(y)) => match nice(x.powf(y)) {
+                Option::Some(v) => constant(v),
+                Option::None => keep(&a, &b),
+            },
+            _ => keep(&a, &b),
+        },
+    }
+}
+
+fn multiply(a: &Term, b: &Term) -> Term {
+    let mut factors = a.factors.clone();
+    for f in b.factors.iter() {
+        match factors.binary_search_by(|g| g.0.cmp(&f.0)) {
+            Result::Ok(i) => factors[i].2 += f.2,
+            Result::Err(i) => factors.insert(i, f.clone()),
+        }
+    }
+    factors.retain(|f| f.2 != 0);
+    Term {
+        coef: a.coef * b.coef,
+        factors,
+    }
+}
+
+// None if the expanded product would have too many terms
+fn product(a: &[Term], b: &[Term]) -> Option<Sum> {
+    if a.len() * b.len() > MAX_TERMS {
+        return Option::None;
+    }
+    let terms = a
+        .iter()
+        .flat_map(|x| b.iter().map(move |y| multiply(x, y)))
+        .collect();
+    Option::Some(collect(terms))
+}
+
+fn mul(a: Sum, b: Sum, span: (usize, usize)) -> Sum {
+    if let Option::Some(p) = product(&a, &b) {
+        return p;
+    }
+    // Too large to expand, so the sums are kept as they are
+    let wrap = |s: Sum| {
+        if s.len() > 1 {
+            atom(from_sum(&s, span))
+        } else {
+            s
+        }
+    };
+    product(&wrap(a), &wrap(b)).unwrap_or_default()
+}
+
+fn power(a: Sum, n: i64, span: (usize, usize)) -> Sum {
+    if n == 0 {
+        return constant(1.0);
+    }
+    if a.len() == 1 {
+        let coef = a[0].coef.powi(n as i32);
+        if coef.is_finite() && coef != 0.0 {
+            let factors = a[0]
+                .factors
+                .iter()
+                .map(|(k, e, p)| (k.clone(), e.clone(), p * n))
+                .collect();
+            return vec![Term { coef, factors }];
+        }
+    }
+    if n > 0 && a.is_empty() {
+        return Vec::new();
+    }
+    if n > 0 {
+        let mut p = a.clone();
+        for _ in 1..n {
+            match product(&p, &a) {
+                Option::Some(x) => p = x,
+                Option::None => return power_of(from_sum(&a, span), n),
+            }
+        }
+        return p;
+    }
+    power_of(from_sum(&a, span), n)
+}
+
+// Combine like terms and apply trigonometric identities
+fn collect(terms: Vec<Term>) -> Sum {
+    let mut out: Vec<Term> = Vec::new();
+    for t in terms.into_iter().map(tangent) {
+        match out.iter_mut().find(|o| o.same(&t)) {
+            Option::Some(o) => o.coef += t.coef,
+            Option::None => out.push(t),
+        }
+    }
+    out.retain(|t| t.coef != 0.0);
+    pythagoras(out)
+}
+
+// The argument of a call to f
+fn argument(e: &Expr, f: Function) -> Option<&Expr> {
+    match e.node {
+        Node::Call(g, ref args) if g == f && args.len() == 1 => Option::Some(&args[0]),
+        _ => Option::None,
+    }
+}
+
+fn call_of(f: Function, arg: &Expr, span: (usize, usize)) -> Expr {
+    Expr::new(Node::Call(f, vec![arg.clone()]), span)
+}
+
+// sin(x) / cos(x) == tan(x)
+fn tangent(mut t: Term) -> Term {
+    loop {
+        let pair = t.factors.iter().enumerate().find_map(|(i, s)| {
+            let arg = format(argument(&s.1, Function::Sin)?, Notation::Plain);
+            let j = t.factors.iter().position(|c| {
+                c.2 < 0
+                    && argument(&c.1, Function::Cos).map(|a| format(a, Notation::Plain))
+                        == Option::Some(arg.clone())
+            })?;
+            if s.2 > 0 {
+                Option::Some((i, j))
+            } else {
+                Option::None
+            }
+        });
+        let (i, j) = match pair {
+            Option::Some(p) => p,
+            Option::None => return t,
+        };
+        let m = t.factors[i].2.min(-t.factors[j].2);
+        let sin = &t.factors[i].1;
+        let tan = match argument(sin, Function::Sin) {
+            Option::Some(arg) => call_of(Function::Tan, arg, sin.span),
+            Option::None => return t,
+        };
+        t.factors[i].2 -= m;
+        t.factors[j].2 += m;
+        let rest = Term {
+            coef: t.coef,
+            factors: t.factors.into_iter().filter(|f| f.2 != 0).collect(),
+        };
+        t = multiply(&rest, &power_of(tan, m)[0]);
+    }
+}
+
+// a sin(x)^2 + a cos(x)^2 == a
+fn pythagoras(terms: Sum) -> Sum {
+    for (i, t) in terms.iter().enumerate() {
+        for (k, s) in t.factors.iter().enumerate() {
+            let arg = match argument(&s.1, Function::Sin) {
+                Option::Some(arg) if s.2 == 2 => arg,
+                _ => continue,
+            };
+            let mut rest = t.clone();
+            rest.factors.remove(k);
+            let cos = call_of(Function::Cos, arg, s.1.span);
+            let target = multiply(&rest, &power_of(cos, 2)[0]);
+            let j = terms
+                .iter()
+                .position(|o| o.same(&target) && o.coef == t.coef);
+            if let Option::Some(j) = j {
+                let mut out: Vec<Term> = terms
+                    .iter()
+                    .enumerate()
+                    .filter(|&(n, _)| n != i && n != j)
+                    .map(|(_, t)| t.clone())
+                    .collect();
+                out.push(rest);
+                return collect(out);
+            }
+        }
+    }
+    terms
+}
+
+fn inverse(f: Function) -> Option<Function> {
+    match f {
+        Function::Sin => Option::Some(Function::Asin),
+        Function::Cos => Option::Some(Function::Acos),
+        Function::Tan => Option::Some(Function::Atan),
+        Function::Sinh => Option::Some(Function::Asinh),
+        Function::Exp => Option::Some(Function::Ln),
+        Function::Ln => Option::Some(Function::Exp),
+        _ => Option::None,
+    }
+}
+
+// Some(true) for odd functions, f(-x) == -f(x), and Some(false) for even functions
+fn odd(f: Function) -> Option<bool> {
+    match f {
+        Function::Sin
+        | Function::Tan
+        | Function::Cot
+        | Function::Csc
+        | Function::Asin
+        | Function::Atan
+        | Function::Sinh
+        | Function::Tanh
+        | Function::Asinh
+        | Function::Atanh => Option::Some(true),
+        Function::Cos | Function::Sec | Function::Cosh | Function::Abs => Option::Some(false),
+        _ => Option::None,
+    }
+}
+
+fn call(context: &mut Context, f: Function, args: &[Expr], span: (usize, usize)) -> Sum {
+    let args: Vec<Expr> = args.iter().map(|a| simplify(context, a)).collect();
+    if let [ref arg] = args[..] {
+        // sin(asin(x)) == x
+        if let Node::Call(g, ref inner) = arg.node {
+            if inner.len() == 1 && inverse(f) == Option::Some(g) {
+                return to_sum(context, &inner[0]);
+            }
+        }
+        // sin(-x) == -sin(x) and cos(-x) == cos(x)
+        if let Option::Some(odd) = odd(f) {
+            if format(arg, Notation::Plain).starts_with('-') {
+                let neg = Expr::new(Node::Negate(Box::new(arg.clone())), arg.span);
+                let s = call(context, f, &[neg], span);
+                return if odd { scale(s, -1.0) } else { s };
+            }
+        }
+    }
+    fold(context, Expr::new(Node::Call(f, args), span))
+}
+
+fn from_sum(s: &[Term], span: (usize, usize)) -> Expr {
+    let mut terms: Vec<&Term> = s.iter().collect();
+    // Highest degree first, then in alphabetical order
+    terms.sort_by(|a, b| {
+        b.degree().cmp(&a.degree()).then_with(|| {
+            let x = a.factors.iter().map(|f| (&f.0, -f.2));
+            let y = b.factors.iter().map(|f| (&f.0, -f.2));
+            x.cmp(y)
+        })
+    });
+    // 1 - x^2 rather than -(x^2) + 1
+    if let Option::Some(i) = terms.iter().position(|t| t.coef > 0.0) {
+        let t = terms.remove(i);
+        terms.insert(0, t);
+    }
+    let mut e: Option<Expr> = Option::None;
+    for t in terms {
+        e = Option::Some(match e {
+            Option::None => term(t.coef, &t.factors, span),
+            Option::Some(e) => {
+                let op = if t.coef < 0.0 {
+                    Operator::Sub
+                } else {
+                    Operator::Add
+                };
+                Expr::binary(op, e, term(t.coef.abs(), &t.factors, span))
+            }
+        });
+    }
+    e.unwrap_or_else(|| Expr::new(Node::Number(0.0), span))
+}
+
+fn term(coef: f64, factors: &[(String, Expr, i64)], span: (usize, usize)) -> Expr {
+    let number = |x: f64| Expr::new(Node::Number(x), span);
+    let power = |e: &Expr, p: i64| {
+        if p == 1 {
+            e.clone()
+        } else {
+            Expr::binary(Operator::Pow, e.clone(), number(p as f64))
+        }
+    };
+    let product = |items: Vec<Expr>| {
+        items
+            .into_iter()
+            .reduce(|a, b| Expr::binary(Operator::Mul, a, b))
+    };
+    // x / 3 instead of 0.3333333333333333x
+    let (c, d) = if nice(coef).is_none() && nice(1.0 / coef).is_some() {
+        (coef.signum(), (1.0 / coef).abs())
+    } else {
+        (coef, 1.0)
+    };
+    // Variables before other factors, x^2 * sin(x)
+    let mut factors: Vec<&(String, Expr, i64)> = factors.iter().collect();
+    factors.sort_by_key(|f| !matches!(f.1.node, Node::Variable(_) | Node::Number(_)));
+    let mut num: Vec<Expr> = factors
+        .iter()
+        .filter(|f| f.2 > 0)
+        .map(|f| power(&f.1, f.2))
+        .collect();
+    let mut den: Vec<Expr> = factors
+        .iter()
+        .filter(|f| f.2 < 0)
+        .map(|f| power(&f.1, -f.2))
+        .collect();
+    let numerator = if num.is_empty() {
+        number(c)
+    } else if c == 1.0 {
+        product(num).unwrap_or_else(|| number(1.0))
+    } else if c == -1.0 {
+        let p = product(num).unwrap_or_else(|| number(1.0));
+        Expr::new(Node::Negate(Box::new(p)), span)
+    } else {
+        num.insert(0, number(c));
+        product(num).unwrap_or_else(|| number(c))
+    };
+    if d != 1.0 {
+        den.insert(0, number(d));
+    }
+    match product(den) {
+        Option::Some(den) => Expr::binary(Operator::Div, numerator, den),
+        Option::None => numerator,
+    }
+}
+
+pub fn format(e: &Expr, notation: Notation) -> String {
+    Printer(notation).print(e, 0)
+}
+
+// Binding strength of the expressions, following the grammar in parser.rs
+const STATEMENT: u8 = 1;
+const SUM: u8 = 2;
+const PERCENT: u8 = 3;
+const PRODUCT: u8 = 4;
+const POWER: u8 = 6;
+const FUNCTION: u8 = 8;
+const VALUE: u8 = 10;
+
+fn precedence(e: &Expr) -> u8 {
+    match e.node {
+        Node::Assign(..)
+        | Node::Const(..)
+        | Node::Unpack(..)
+        | Node::Convert(..)
+        | Node::Equation(..)
+        | Node::Simplify(..)
+        | Node::Table(..)
+        | Node::Define(..)
+        | Node::Mode(_)
+        | Node::Workspace(..) => STATEMENT,
+        Node::Binary(Operator::Add, ..) | Node::Binary(Operator::Sub, ..) => SUM,
+        Node::Percent(_) => PERCENT,
+        Node::Binary(Operator::Mul, ..)
+        | Node::Binary(Operator::Div, ..)
+        | Node::Binary(Operator::Mod, ..) => PRODUCT,
+        Node::Binary(Operator::Pow, ..) | Node::Factorial(_) | Node::DoubleFactorial(_) => POWER,
+        Node::Call(..)
+        | Node::Apply(..)
+        | Node::Degrees(_)
+        | Node::Duration(..)
+        | Node::Money(..) => FUNCTION,
+        Node::Number(_) | Node::Date(_) | Node::Variable(_) | Node::Negate(_) | Node::Matrix(_) => {
+            VALUE
+        }
+    }
+}
+
+// Names that can follow a number directly, 2x is 2 * x but 2h is two hours
+fn leading_name(e: &Expr) -> bool {
+    match e.node {
+        Node::Variable(ref name) => time::unit(name).is_none() && !currency::is_code(name),
+        Node::Number(x) => x == PI || x == E,
+        Node::Call(..) | Node::Apply(..) => true,
+        Node::Binary(Operator::Pow, ref base, _) | Node::Factorial(ref base) => leading_name(base),
+        _ => false,
+    }
+}
+
+fn superscript(e: &Expr) -> Option<String> {
+    let n = match e.node {
+        Node::Number(x) => x,
+        Node::Negate(ref a) => match a.node {
+            Node::Number(x) => -x,
+            _ => return Option::None,
+        },
+        _ => return Option::None,
+    };
+    if n.fract() != 0.0 || n.abs() > 1e9 {
+        return Option::None;
+    }
+    n.to_string()
+        .chars()
+        .map(|c| match c {
+            '-' => Option::Some('⁻'),
+            _ => "⁰¹²³⁴⁵⁶⁷⁸⁹".chars().nth(c.to_digit(10)? as usize),
+        })
+        .collect()
+}
+
+fn latex_name(f: Function) -> String {
+    match f {
+        Function::Sin
+        | Function::Cos
+        | Function::Tan
+        | Function::Sec
+        | Function::Csc
+        | Function::Cot
+        | Function::Sinh
+        | Function::Cosh
+        | Function::Tanh
+        | Function::Ln
+        | Function::Log
+        | Function::Exp
+        | Function::Det
+        | Function::Gcd => format!("\\{}", f),
+        Function::Asin => "\\arcsin".to_string(),
+        Function::Acos => "\\arccos".to_string(),
+        Function::Atan => "\\arctan".to_string(),
+        _ => format!("\\operatorname{{{}}}", f),
+    }
+}
+
+struct Printer(Notation);
+
+impl Printer {
+    fn print(&self, e: &Expr, min: u8) -> String {
+        let s = self.node(e);
+        if precedence(e) < min {
+            self.parens(&s)
+        } else {
+            s
+        }
+    }
+
+    fn parens(&self, s: &str) -> String {
+        match self.0 {
+            Notation::Latex => format!("\\left({}\\right)", s),
+            _ => format!("({})", s),
+        }
+    }
+
+    fn minus(&self) -> &'static str {
+        match self.0 {
+            Notation::Unicode => "−",
+            _ => "-",
+        }
+    }
+
+    fn list(&self, items: &[Expr]) -> String {
+        let items: Vec<String> = items.iter().map(|e| self.print(e, 0)).collect();
+        items.join(", ")
+    }
+
+    fn number(&self, x: f64) -> String {
+        match self.0 {
+            _ if x == E => "e".to_string(),
+            Notation::Plain if x == PI => "pi".to_string(),
+            Notation::Unicode if x == PI => "π".to_string(),
+            Notation::Latex if x == PI => "\\pi".to_string(),
+            Notation::Unicode if x < 0.0 => format!("−{}", -x),
+            _ => x.to_string(),
+        }
+    }
+
+    fn name(&self, name: &str) -> String {
+        match self.0 {
+            Notation::Latex if name.chars().count() > 1 => format!("\\mathrm{{{}}}", name),
+            _ => name.to_string(),
+        }
+    }
+
+    fn node(&self, e: &Expr) -> String {
+        match e.node {
+            Node::Number(x) => self.number(x),
+            Node::Date(x) => time::format_date(x),
+            Node::Variable(ref name) => self.name(name),
+            Node::Matrix(ref items) => format!("[{}]", self.list(items)),
+            Node::Duration(ref a, size) => {
+                let unit = time::UNITS
+                    .iter()
+                    .find(|u| u.1 == size)
+                    .map_or("s", |u| u.0);
+                format!("{} {}", self.print(a, VALUE), unit)
+            }
+            Node::Money(ref a, ref code) => format!("{} {}", self.print(a, VALUE), code),
+            Node::Convert(ref a, ref unit) => format!("{} in {}", self.print(a, SUM), unit),
+            Node::Assign(ref name, ref a) => format!("{} = {}", self.name(name), self.print(a, 0)),
+            Node::Const(ref name, ref a) => {
+                format!("const {} = {}", self.name(name), self.print(a, 0))
+            }
+            Node::Unpack(ref names, ref values) => {
+                let names: Vec<String> = names.iter().map(|n| self.name(n)).collect();
+                format!("{} = {}", names.join(", "), self.list(values))
+            }
+            // -x^2 is (-x)^2 in the parser
+            Node::Negate(ref a) => {
+                let min = if self.0 == Notation::Plain {
+                    VALUE
+                } else {
+                    POWER
+                };
+                format!("{}{}", self.minus(), self.print(a, min))
+            }
+            Node::Factorial(ref a) => format!("{}!", self.print(a, POWER)),
+            Node::DoubleFactorial(ref a) => format!("{}!!", self.print(a, POWER)),
+            Node::Degrees(ref a) => match self.0 {
+                Notation::Latex => format!("{}^{{\\circ}}", self.print(a, VALUE)),
+                _ => format!("{}°", self.print(a, VALUE)),
+            },
+            Node::Percent(ref a) => match self.0 {
+                Notation::Latex => format!("{}\\%", self.print(a, PRODUCT)),
+                _ => format!("{}%", self.print(a, PRODUCT)),
+            },
+            Node::Mode(angle) => match angle {
+                Option::Some(a) => format!("mode {}", a),
+                Option::None => "mode".to_string(),
+            },
+            Node::Workspace(command, ref names) => std::iter::once(command.to_string())
+                .chain(names.iter().map(|n| self.name(n)))
+                .collect::<Vec<String>>()
+                .join(" "),
+            Node::Binary(op, ref l, ref r) => self.binary(op, l, r),
+            Node::Call(f, ref args) => self.call(f, args),
+            Node::Apply(ref name, ref args, ref keywords) => {
+                let mut items: Vec<String> = args.iter().map(|a| self.print(a, 0)).collect();
+                for (k, a) in keywords {
+                    items.push(format!("{}={}", k, self.print(a, 0)));
+                }
+                match self.0 {
+                    Notation::Latex => {
+                        format!("{}\\left({}\\right)", self.name(name), items.join(", "))
+                    }
+                    _ => format!("{}({})", self.name(name), items.join(", ")),
+                }
+            }
+            Node::Define(ref name, ref params, ref body) => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|(p, d)| match *d {
+                        Option::Some(ref d) => format!("{}={}", p, self.print(d, 0)),
+                        Option::None => p.clone(),
+                    })
+                    .collect();
+                format!(
+                    "{}({}) = {}",
+                    self.name(name),
+                    params.join(", "),
+                    self.print(body, 0)
+                )
+            }
+            Node::Equation(ref l, ref r, ref var) => {
+                let s = format!("solve {} = {}", self.print(l, SUM), self.print(r, SUM));
+                match *var {
+                    Option::Some(ref v) => format!("{} for {}", s, v),
+                    Option::None => s,
+                }
+            }
+            Node::Simplify(ref a, notation) => {
+                let s = format!("simplify {}", self.print(a, SUM));
+                match notation {
+                    Notation::Plain => s,
+                    Notation::Unicode => format!("{} in unicode", s),
+                    Notation::Latex => format!("{} in latex", s),
+                }
+            }
+            Node::Table(ref a, ref names, ref bounds, style) => {
+                let mut args = vec![self.print(a, 0)];
+                for (name, b) in names.iter().zip(bounds.chunks(3)) {
+                    args.push(format!("{}, {}", name, self.list(b)));
+                }
+                let s = format!("table({})", args.join(", "));
+                match style {
+                    Style::Text => s,
+                    _ => format!("{} in {}", s, style),
+                }
+            }
+        }
+    }
+
+    fn binary(&self, op: Operator, l: &Expr, r: &Expr) -> String {
+        match op {
+            Operator::Add | Operator::Sub => {
+                let sign = if op == Operator::Add {
+                    "+"
+                } else {
+                    self.minus()
+                };
+                format!("{} {} {}", self.print(l, SUM), sign, self.print(r, PERCENT))
+            }
+            Operator::Mul => {
+                let sign = match self.0 {
+                    // 2x
+                    _ if matches!(l.node, Node::Number(x) if x != PI && x != E)
+                        && precedence(r) > PRODUCT
+                        && leading_name(r) =>
+                    {
+                        ""
+                    }
+                    Notation::Plain => " * ",
+                    Notation::Unicode => "·",
+                    Notation::Latex => " \\cdot ",
+                };
+                format!(
+                    "{}{}{}",
+                    self.print(l, PRODUCT),
+                    sign,
+                    self.print(r, PRODUCT + 1)
+                )
+            }
+            Operator::Div if self.0 == Notation::Latex => {
+                format!("\\frac{{{}}}{{{}}}", self.print(l, 0), self.print(r, 0))
+            }
+            Operator::Div => format!(
+                "{} / {}",
+                self.print(l, PRODUCT),
+                self.print(r, PRODUCT + 1)
+            ),
+            Operator::Mod => {
+                // x % -y would be a percentage
+                let right = self.print(r, PRODUCT + 1);
+                let right = if right.starts_with('-') || right.starts_with('−') {
+                    self.parens(&right)
+                } else {
+                    right
+                };
+                let sign = if self.0 == Notation::Latex {
+                    "\\bmod"
+                } else {
+                    "%"
+                };
+                format!("{} {} {}", self.print(l, PRODUCT), sign, right)
+            }
+            Operator::Pow => match self.0 {
+                Notation::Unicode if superscript(r).is_some() => format!(
+                    "{}{}",
+                    self.print(l, POWER + 1),
+                    superscript(r).unwrap_or_default()
+                ),
+                Notation::Latex => {
+                    format!("{}^{{{}}}", self.print(l, POWER + 1), self.print(r, 0))
+                }
+                _ => format!("{}^{}", self.print(l, POWER), self.print(r, FUNCTION)),
+            },
+        }
+    }
+
+    fn call(&self, f: Function, args: &[Expr]) -> String {
+        match (self.0, f) {
+            (_, Function::Rnd) | (_, Function::Now) | (_, Function::Today) if args.is_empty() => {
+                f.to_string()
+            }
+            // The third argument is the bonus in dice("NdM+K")
+            (_, Function::Dice) if args.len() == 3 => {
+                let dice = format!("dice({})", self.list(&args[..2]));
+                match args[2].node {
+                    Node::Number(0.0) => dice,
+                    _ => self.parens(&format!("{} + {}", dice, self.print(&args[2], PERCENT))),
+                }
+            }
+            (Notation::Unicode, Function::Abs) => format!("|{}|", self.list(args)),
+            (Notation::Unicode, Function::Sqrt) if args.len() == 1 => {
+                format!("√{}", self.print(&args[0], VALUE))
+            }
+            (Notation::Latex, Function::Abs) => format!("\\left|{}\\right|", self.list(args)),
+            (Notation::Latex, Function::Sqrt) => format!("\\sqrt{{{}}}", self.list(args)),
+            (Notation::Latex, Function::NthRoot) if args.len() == 2 => format!(
+                "\\sqrt[{}]{{{}}}",
+                self.print(&args[1], 0),
+                self.print(&args[0], 0)
+            ),
+            (Notation::Latex, _) => {
+                format!("{}\\left({}\\right)", latex_name(f), self.list(args))
+            }
+            _ => format!("{}({})", f, self.list(args)),
+        }
+    }
+}
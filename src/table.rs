@@ -0,0 +1,318 @@
+// Tabulating an expression over ranges of one or two variables, printed as an aligned
+// table, CSV or JSON. Points where the expression cannot be evaluated are left empty.
+
+use crate::ast::Expr;
+use crate::context::Context;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::solve;
+use crate::value::{Format, Locale, Value};
+
+// Limit on the number of rows, counting every combination of two variables
+pub const MAX_ROWS: usize = 100_000;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Style {
+    Text,
+    Csv,
+    Json,
+}
+
+impl Style {
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name.to_lowercase().as_ref() {
+            "text" | "table" => Option::Some(Style::Text),
+            "csv" => Option::Some(Style::Csv),
+            "json" => Option::Some(Style::Json),
+            _ => Option::None,
+        }
+    }
+}
+
+impl std::fmt::Display for Style {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Style::Text => write!(f, "text"),
+            Style::Csv => write!(f, "csv"),
+            Style::Json => write!(f, "json"),
+        }
+    }
+}
+
+// The values start, start + step, ... up to and including stop
+#[derive(PartialEq, Debug, Clone)]
+pub struct Range {
+    pub name: String,
+    pub start: f64,
+    pub stop: f64,
+    pub step: f64,
+}
+
+impl Range {
+    pub fn new(name: &str, start: f64, stop: f64, step: f64) -> Result<Range, String> {
+        if !(start.is_finite() && stop.is_finite() && step.is_finite()) {
+            return Result::Err(format!("The range of {} must be finite", name));
+        }
+        if step == 0.0 || (stop - start) * step < 0.0 {
+            return Result::Err(format!(
+                "The step of {} must go from {} towards {}",
+                name, start, stop
+            ));
+        }
+        // Counted as a float, x=0:1E300 does not fit in usize
+        if count(start, stop, step) > MAX_ROWS as f64 {
+            return Result::Err(format!(
+                "Too many values in the range of {} (max {})",
+                name, MAX_ROWS
+            ));
+        }
+        Result::Ok(Range {
+            name: name.to_string(),
+            start,
+            stop,
+            step,
+        })
+    }
+
+    // "x=0:10:0.5", the step defaults to 1 and the bounds can be expressions such as 2pi
+    pub fn parse(s: &str) -> Result<Range, String> {
+        Range::parse_with(s, &Locale::default())
+    }
+
+    // With a decimal comma the step can be written as x=0:1:0,25
+    pub fn parse_with(s: &str, locale: &Locale) -> Result<Range, String> {
+        let (name, bounds) = split(s, locale)?;
+        match bounds[..] {
+            [start, stop] => Range::new(name, start, stop, 1.0),
+            [start, stop, step] => Range::new(name, start, stop, step),
+            _ => Result::Err(syntax(s)),
+        }
+    }
+
+    // Plots only use the start and stop, so x=0:1E6 is not split into steps of 1
+    pub fn interval(s: &str, locale: &Locale) -> Result<Range, String> {
+        let (name, bounds) = split(s, locale)?;
+        match bounds[..] {
+            [start, stop] | [start, stop, _] if stop > start => {
+                Range::new(name, start, stop, stop - start)
+            }
+            [start, stop] | [start, stop, _] => Range::new(name, start, stop, 1.0),
+            _ => Result::Err(syntax(s)),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        count(self.start, self.stop, self.step) as usize
+    }
+
+    pub fn values(&self) -> Vec<f64> {
+        (0..self.count())
+            .map(|i| solve::round(self.start + i as f64 * self.step))
+            .collect()
+    }
+}
+
+// Allow for rounding errors so that the stop is included
+fn count(start: f64, stop: f64, step: f64) -> f64 {
+    ((stop - start) / step + 1e-9).floor() + 1.0
+}
+
+fn syntax(s: &str) -> String {
+    format!("Invalid range '{}' (expected x=start:stop:step)", s)
+}
+
+// The name and bounds of "x=0:10:0.5"
+fn split<'a>(s: &'a str, locale: &Locale) -> Result<(&'a str, Vec<f64>), String> {
+    let (name, bounds) = s.split_once('=').ok_or_else(|| syntax(s))?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Result::Err(syntax(s));
+    }
+    let bounds = bounds
+        .split(':')
+        .map(|b| bound(b, locale))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| syntax(s))?;
+    Result::Ok((name, bounds))
+}
+
+fn bound(s: &str, locale: &Locale) -> Option<f64> {
+    if let Result::Ok(x) = s.trim().parse::<f64>() {
+        return Option::Some(x);
+    }
+    let mut parser = Parser::from(Scanner::with_locale(s, locale));
+    match (parser.next(), parser.next()) {
+        (Option::Some(Result::Ok(v)), Option::None) => v.as_number(),
+        _ => Option::None,
+    }
+}
+
+// One column for every variable and a last one for the expression. Missing values are NaN.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<f64>>,
+}
+
+// Evaluate the expression for every combination of the variables, the first one changing slowest
+pub fn tabulate(context: &mut Context, expr: &Expr, ranges: &[Range]) -> Result<Table, String> {
+    let mut rows: Vec<Vec<f64>> = vec![Vec::new()];
+    for r in ranges {
+        if rows.len().saturating_mul(r.count()) > MAX_ROWS {
+            return Result::Err(format!("Too many rows in the table (max {})", MAX_ROWS));
+        }
+        let values = r.values();
+        rows = rows
+            .iter()
+            .flat_map(|row| {
+                values.iter().map(move |&v| {
+                    let mut row = row.clone();
+                    row.push(v);
+                    row
+                })
+            })
+            .collect();
+    }
+    // The variables are restored afterwards
+    let old: Vec<Option<Value>> = ranges.iter().map(|r| context.remove(&r.name)).collect();
+    // Single points can be undefined, but an expression that fails everywhere is an error
+    let (mut error, mut failed) = (Option::None, 0);
+    for row in rows.iter_mut() {
+        for (r, &v) in ranges.iter().zip(row.iter()) {
+            context.set(&r.name, Value::Number(v));
+        }
+        let y = match context.number(expr) {
+            Result::Ok(y) => y,
+            Result::Err(e) => {
+                failed += 1;
+                error.get_or_insert(e.message);
+                f64::NAN
+            }
+        };
+        row.push(y);
+    }
+    for (r, v) in ranges.iter().zip(old) {
+        context.remove(&r.name);
+        if let Option::Some(v) = v {
+            context.set(&r.name, v);
+        }
+    }
+    if failed == rows.len() {
+        if let Option::Some(e) = error {
+            return Result::Err(e);
+        }
+    }
+    let mut columns: Vec<String> = ranges.iter().map(|r| r.name.clone()).collect();
+    columns.push(expr.to_string());
+    Result::Ok(Table { columns, rows })
+}
+
+impl Table {
+    pub fn format(&self, style: Style, format: &Format) -> String {
+        match style {
+            Style::Text => self.text(format),
+            Style::Csv => self.csv(format),
+            Style::Json => self.json(),
+        }
+    }
+
+    // Right aligned columns under a header
+    fn text(&self, format: &Format) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&x| {
+                        if x.is_nan() {
+                            String::new()
+                        } else {
+                            Value::Number(x).format(format)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|c| {
+                cells
+                    .iter()
+                    .map(|row| row[c].chars().count())
+                    .chain(std::iter::once(self.columns[c].chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |items: &[String]| {
+            let padded: Vec<String> = items
+                .iter()
+                .zip(widths.iter())
+                .map(|(s, &w)| format!("{:>1$}", s, w))
+                .collect();
+            padded.join("  ")
+        };
+        let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+        let mut lines = vec![line(&self.columns), line(&rule)];
+        lines.extend(cells.iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    // Spreadsheets expect ; between the cells when the decimal separator is a comma
+    fn csv(&self, format: &Format) -> String {
+        let sep = if format.decimal == ',' { ";" } else { "," };
+        let quote = |s: &String| {
+            if s.contains([',', ';', '"', '\n']) {
+                format!("\"{}\"", s.replace('"', "\"\""))
+            } else {
+                s.clone()
+            }
+        };
+        let mut lines = vec![self.columns.iter().map(quote).collect::<Vec<_>>().join(sep)];
+        let plain = Format {
+            thousands: Option::None,
+            ..*format
+        };
+        for row in self.rows.iter() {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|x| {
+                    if x.is_finite() {
+                        Value::Number(*x).format(&plain)
+                    } else {
+                        String::new()
+                    }
+                })
+                .collect();
+            lines.push(cells.join(sep));
+        }
+        lines.join("\n")
+    }
+
+    // An array with one object per row
+    fn json(&self) -> String {
+        let keys: Vec<String> = self
+            .columns
+            .iter()
+            .map(|s| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = keys
+                    .iter()
+                    .zip(row.iter())
+                    .map(|(k, x)| {
+                        if x.is_finite() {
+                            format!("{}: {}", k, x)
+                        } else {
+                            format!("{}: null", k)
+                        }
+                    })
+                    .collect();
+                format!("  {{{}}}", fields.join(", "))
+            })
+            .collect();
+        format!("[\n{}\n]", rows.join(",\n"))
+    }
+}
@@ -0,0 +1,168 @@
+// Dates are seconds since 1970-01-01 00:00 and durations are seconds.
+// There are no timezones or leap seconds, all dates are in the proleptic Gregorian calendar.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = 3600.0;
+const DAY: f64 = 86400.0;
+
+// Duration units and their length in seconds
+pub const UNITS: &[(&str, f64)] = &[
+    ("ms", 0.001),
+    ("s", 1.0),
+    ("sec", 1.0),
+    ("secs", 1.0),
+    ("second", 1.0),
+    ("seconds", 1.0),
+    ("min", MINUTE),
+    ("mins", MINUTE),
+    ("minute", MINUTE),
+    ("minutes", MINUTE),
+    ("h", HOUR),
+    ("hr", HOUR),
+    ("hrs", HOUR),
+    ("hour", HOUR),
+    ("hours", HOUR),
+    ("d", DAY),
+    ("day", DAY),
+    ("days", DAY),
+    ("wk", DAY * 7.0),
+    ("week", DAY * 7.0),
+    ("weeks", DAY * 7.0),
+];
+
+pub fn unit(name: &str) -> Option<f64> {
+    let name = name.to_lowercase();
+    UNITS.iter().find(|&&(n, _)| n == name).map(|&(_, s)| s)
+}
+
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+pub fn today() -> f64 {
+    (now() / DAY).floor() * DAY
+}
+
+// Days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// (year, month, day) from days since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Parse "YYYY-MM-DD", "YYYY-MM-DDTHH:MM" or "YYYY-MM-DDTHH:MM:SS" at the start of a string.
+// Returns the date (None if there is no such date) and the length of the literal.
+pub fn parse_date(s: &str) -> Option<(Option<f64>, usize)> {
+    let b = s.as_bytes();
+    let digits = |from: usize, to: usize| -> Option<i64> {
+        if to <= b.len() && b[from..to].iter().all(u8::is_ascii_digit) {
+            s[from..to].parse::<i64>().ok()
+        } else {
+            Option::None
+        }
+    };
+    let at = |i: usize, c: u8| b.get(i) == Option::Some(&c);
+    if !at(4, b'-') || !at(7, b'-') {
+        return Option::None;
+    }
+    let year = digits(0, 4)?;
+    let month = digits(5, 7)? as u32;
+    let day = digits(8, 10)? as u32;
+    let mut valid = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+    let mut len = 10;
+    let mut secs = 0;
+    if at(10, b'T') {
+        let (h, m) = (digits(11, 13)?, digits(14, 16)?);
+        if !at(13, b':') {
+            return Option::None;
+        }
+        let s = if at(16, b':') { digits(17, 19)? } else { 0 };
+        valid = valid && h < 24 && m < 60 && s < 60;
+        secs = h * 3600 + m * 60 + s;
+        len = if at(16, b':') { 19 } else { 16 };
+    }
+    if b.get(len).is_some_and(|c| c.is_ascii_alphanumeric()) {
+        return Option::None;
+    }
+    let date = (days_from_civil(year, month, day) * 86400 + secs) as f64;
+    Option::Some((
+        if valid {
+            Option::Some(date)
+        } else {
+            Option::None
+        },
+        len,
+    ))
+}
+
+pub fn format_date(secs: f64) -> String {
+    let secs = secs.round() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let rem = secs.rem_euclid(86400);
+    if rem == 0 {
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    } else {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            rem / 3600,
+            rem / 60 % 60,
+            rem % 60
+        )
+    }
+}
+
+// Durations are shown as for example "2d 3h 25min 10s"
+pub fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return format!("{}s", secs);
+    }
+    let total = (secs.abs() * 1000.0).round() / 1000.0;
+    let mut rest = total;
+    let mut parts = Vec::new();
+    for &(name, size) in [("d", DAY), ("h", HOUR), ("min", MINUTE)].iter() {
+        let n = (rest / size).floor();
+        if n > 0.0 {
+            parts.push(format!("{}{}", n, name));
+            rest = ((rest - n * size) * 1000.0).round() / 1000.0;
+        }
+    }
+    if rest > 0.0 || parts.is_empty() {
+        parts.push(format!("{}s", rest));
+    }
+    let sign = if secs < 0.0 && total > 0.0 { "-" } else { "" };
+    format!("{}{}", sign, parts.join(" "))
+}
@@ -0,0 +1,205 @@
+use crate::currency;
+use crate::matrix::Matrix;
+use crate::time;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    // Seconds since 1970-01-01
+    Date(f64),
+    // Seconds
+    Duration(f64),
+    // An amount of money in a currency
+    Money(f64, String),
+    // A 1x1 matrix behaves like a number
+    Matrix(Matrix),
+    // Prime factorization as (prime, exponent) pairs, behaves like the number it factors
+    Factors(Vec<(u64, u32)>),
+    // Output of commands
+    Text(String),
+}
+
+impl Value {
+    pub fn as_number(&self) -> Option<f64> {
+        match *self {
+            Value::Number(x) => Option::Some(x),
+            Value::Factors(ref f) => {
+                Option::Some(f.iter().map(|&(p, e)| (p as f64).powi(e as i32)).product())
+            }
+            Value::Matrix(ref m) if m.rows() == 1 && m.cols() == 1 => Option::Some(m.get(0, 0)),
+            Value::Date(_)
+            | Value::Duration(_)
+            | Value::Money(..)
+            | Value::Matrix(_)
+            | Value::Text(_) => Option::None,
+        }
+    }
+
+    // Name of the type of value, for error messages
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Value::Number(_) | Value::Factors(_) => "number",
+            Value::Date(_) => "date",
+            Value::Duration(_) => "duration",
+            Value::Money(..) => "currency",
+            Value::Matrix(_) => "matrix",
+            Value::Text(_) => "text",
+        }
+    }
+
+    pub fn format(&self, format: &Format) -> String {
+        match *self {
+            Value::Number(x) => format.number(&x.to_string()),
+            Value::Money(x, ref code) => format!(
+                "{} {}",
+                format.number(&format!("{:.2}", currency::round(x))),
+                code
+            ),
+            Value::Matrix(_) | Value::Duration(_) => format.written(&self.to_string()),
+            _ => self.to_string(),
+        }
+    }
+}
+
+// Separators of numbers in a language or region
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Locale {
+    pub decimal: char,
+    // Used when grouping digits
+    pub thousands: char,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            decimal: '.',
+            thousands: ',',
+        }
+    }
+}
+
+impl Locale {
+    // A language code such as fi, or a name such as fi_FI.UTF-8 or de-DE as in $LANG
+    pub fn from_name(name: &str) -> Option<Locale> {
+        let language = name
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        let (decimal, thousands) = match language.as_ref() {
+            "c" | "posix" | "en" | "ja" | "ko" | "zh" | "he" | "th" => ('.', ','),
+            "de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "sl" | "hr" => {
+                (',', '.')
+            }
+            "fi" | "sv" | "fr" | "nb" | "nn" | "no" | "ru" | "uk" | "pl" | "cs" | "sk" | "hu"
+            | "et" | "lv" | "lt" | "bg" => (',', '\u{a0}'),
+            _ => return Option::None,
+        };
+        Option::Some(Locale { decimal, thousands })
+    }
+
+    pub fn decimal_comma(&self) -> bool {
+        self.decimal == ','
+    }
+}
+
+// How values are printed
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Format {
+    // Separator between groups of thousands, such as 1,234,567
+    pub thousands: Option<char>,
+    // With a comma values are separated by ; instead
+    pub decimal: char,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            thousands: Option::None,
+            decimal: '.',
+        }
+    }
+}
+
+impl Format {
+    // The separators of the locale, digits are grouped if group is true
+    pub fn with_locale(locale: &Locale, group: bool) -> Format {
+        Format {
+            thousands: Option::Some(locale.thousands).filter(|_| group),
+            decimal: locale.decimal,
+        }
+    }
+
+    // [1,5; 2] with a decimal comma, as it would be written
+    pub fn written(&self, s: &str) -> String {
+        if self.decimal == '.' {
+            return s.to_string();
+        }
+        s.replace(',', ";").replace('.', &self.decimal.to_string())
+    }
+
+    // Between values in a list, which must differ from the decimal separator
+    pub fn separator(&self) -> &'static str {
+        if self.decimal == ',' {
+            "; "
+        } else {
+            ", "
+        }
+    }
+
+    fn number(&self, s: &str) -> String {
+        let (sign, s) = match s.strip_prefix('-') {
+            Option::Some(rest) => ("-", rest),
+            Option::None => ("", s),
+        };
+        let (int, frac) = s.split_at(s.find('.').unwrap_or(s.len()));
+        let frac = frac.replacen('.', &self.decimal.to_string(), 1);
+        let sep = match self.thousands {
+            Option::Some(c) if int.bytes().all(|c| c.is_ascii_digit()) => c,
+            _ => return format!("{}{}{}", sign, int, frac),
+        };
+        let mut out = String::from(sign);
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                out.push(sep);
+            }
+            out.push(c);
+        }
+        out + &frac
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Number(x)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Value::Number(x) => write!(f, "{}", x),
+            Value::Money(..) => write!(f, "{}", self.format(&Format::default())),
+            Value::Date(x) => write!(f, "{}", time::format_date(x)),
+            Value::Duration(x) => write!(f, "{}", time::format_duration(x)),
+            Value::Text(ref s) => write!(f, "{}", s),
+            Value::Matrix(ref m) => write!(f, "{}", m),
+            Value::Factors(ref factors) => {
+                if factors.is_empty() {
+                    return write!(f, "1");
+                }
+                for (i, &(p, e)) in factors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " * ")?;
+                    }
+                    if e == 1 {
+                        write!(f, "{}", p)?;
+                    } else {
+                        write!(f, "{}^{}", p, e)?;
+                    }
+                }
+                Result::Ok(())
+            }
+        }
+    }
+}
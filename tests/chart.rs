@@ -0,0 +1,76 @@
+use calc::chart::*;
+use calc::context::Context;
+use calc::table::Range;
+
+#[test]
+fn curves() {
+    assert_eq!(Curve::parse("sin x"), Curve::Graph("sin x".to_string()));
+    assert_eq!(
+        Curve::parse("(cos t, sin(2t))"),
+        Curve::Parametric("cos t".to_string(), "sin(2t)".to_string())
+    );
+    assert_eq!(
+        Curve::parse("r = 1 + cos t"),
+        Curve::Polar("1 + cos t".to_string())
+    );
+    // Parentheses that do not hold two functions are a graph
+    assert!(Curve::parse("(x+1)*(x-1)").is_graph());
+    assert!(Curve::parse("(x+1)").is_graph());
+    assert_eq!(
+        Curve::parse("( cos t ,sin t )").to_string(),
+        "(cos t, sin t)"
+    );
+}
+
+#[test]
+fn sampling() {
+    let context = Context::new();
+    let t = Range::parse("t=0:2pi").unwrap();
+    let circle = sample(&Curve::parse("(cos t, sin t)"), &t, &context).unwrap();
+    assert!(circle.domain.is_none());
+    assert!(circle.points.iter().all(|p| {
+        let (x, y) = p.unwrap();
+        (x.hypot(y) - 1.0).abs() < 1e-9
+    }));
+    let polar = sample(&Curve::parse("r = 2"), &t, &context).unwrap();
+    let (x, y) = polar.points[0].unwrap();
+    assert!((x - 2.0).abs() < 1e-9 && y.abs() < 1e-9);
+    let x = Range::parse("x=-1:1").unwrap();
+    let graph = sample(&Curve::parse("1/x"), &x, &context).unwrap();
+    assert_eq!(graph.domain, Option::Some((-1.0, 1.0)));
+    assert!(graph.points.iter().all(|p| p.is_some()));
+    assert!(sample(&Curve::parse("sqrt x"), &x, &context)
+        .unwrap()
+        .points
+        .iter()
+        .any(|p| p.is_none()));
+    assert!(sample(&Curve::parse("(cos t, y)"), &t, &context).is_err());
+}
+
+#[test]
+fn images() {
+    let context = Context::new();
+    let x = Range::parse("x=-10:10").unwrap();
+    let lines = vec![
+        sample(&Curve::parse("sin x"), &x, &context).unwrap(),
+        sample(&Curve::parse("tan x"), &x, &context).unwrap(),
+    ];
+    let chart = Chart::default();
+    let svg = chart.svg(&lines);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains(">sin x</text>") && svg.contains(">tan x</text>"));
+    assert!(svg.contains(">-10</text>") && svg.contains(">10</text>"));
+    // tan x is split at every pole
+    assert!(svg.matches("stroke=\"#d62728\"").count() > 6);
+    let png = chart.png(&lines).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // The width and height are in the header
+    assert_eq!(&png[16..24], &[0, 0, 3, 32, 0, 0, 1, 244]);
+    let escaped = vec![Line {
+        name: "a < b & c".to_string(),
+        points: vec![Option::Some((0.0, 0.0)), Option::Some((1.0, 1.0))],
+        domain: Option::None,
+    }];
+    assert!(chart.svg(&escaped).contains(">a &lt; b &amp; c</text>"));
+}
@@ -0,0 +1,57 @@
+use calc::color::*;
+use calc::parser::Parser;
+
+fn strip(s: &str) -> String {
+    let mut out = String::new();
+    let mut escape = false;
+    for c in s.chars() {
+        match c {
+            '\x1b' => escape = true,
+            'm' if escape => escape = false,
+            _ if escape => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[test]
+fn highlight_tokens() {
+    let s = highlight("2 + sin x", Option::None);
+    assert_eq!(strip(&s), "2 + sin x");
+    assert!(s.contains("\x1b[36m2\x1b[0m"));
+    assert!(s.contains("\x1b[35msin\x1b[0m"));
+    assert!(s.contains("\x1b[33m+\x1b[0m"));
+    assert!(s.contains("\x1b[32mx\x1b[0m"));
+}
+
+#[test]
+fn highlight_error() {
+    let s = highlight("1 / foo", Option::Some((4, 7)));
+    assert_eq!(strip(&s), "1 / foo");
+    assert!(s.contains("\x1b[4;31mfoo\x1b[0m"));
+}
+
+#[test]
+fn multiline_error() {
+    let mut p = Parser::new("x = 1\ny = x + foo\n");
+    p.set_color(true);
+    let e = p.nth(1).unwrap().unwrap_err();
+    let lines: Vec<String> = strip(&e).lines().map(|l| l.to_string()).collect();
+    assert_eq!(lines[2..], ["Line: 2", "y = x + foo", "        ^~~"]);
+}
+
+#[test]
+fn colored_error() {
+    let mut p = Parser::new("1 + foo * 2");
+    p.set_color(true);
+    let e = p.next().unwrap().unwrap_err();
+    assert_eq!(strip(&e).lines().last().unwrap(), format!("{:4}^~~", ""));
+    assert!(e.contains("\x1b[4;31mfoo"));
+}
+
+#[test]
+fn styles() {
+    assert_eq!(strip(&bold("5")), "5");
+    assert_ne!(bold("5"), "5");
+}
@@ -0,0 +1,83 @@
+use calc::compile::*;
+use calc::context::Context;
+use calc::parser::Parser;
+use calc::value::Value;
+
+#[test]
+fn inputs() {
+    let c = compile("y * x^2 + x + pi").unwrap();
+    assert_eq!(c.inputs(), ["y", "x"]);
+    assert_eq!(c.slot("x"), Option::Some(1));
+    assert_eq!(c.slot("pi"), Option::None);
+    let mut context = Context::new();
+    context.set("a", Value::Number(3.0));
+    let mut c = compile_with("a * b", context).unwrap();
+    assert_eq!(c.inputs(), ["b"]);
+    assert_eq!(c.eval(&[2.0]), Result::Ok(6.0));
+}
+
+#[test]
+fn functions() {
+    let mut p = Parser::new("f(x, n=2) = x^n + a, a = 1");
+    for res in &mut p {
+        res.unwrap();
+    }
+    let mut c = compile_with("f(y) + f(y, n=1)", p.into_context()).unwrap();
+    assert_eq!(c.inputs(), ["y"]);
+    assert_eq!(c.eval(&[3.0]), Result::Ok(14.0));
+    assert!(compile("f(x) = x").is_err());
+}
+
+#[test]
+fn evaluation() {
+    let mut c = compile("2x^2 - 3x + 1").unwrap();
+    assert_eq!(c.eval(&[0.0]), Result::Ok(1.0));
+    assert_eq!(c.eval(&[2.0]), Result::Ok(3.0));
+    assert_eq!(
+        c.eval_many(&[[1.0], [3.0], [-1.0]]),
+        Result::Ok(vec![0.0, 10.0, 6.0])
+    );
+    let mut c = compile("sum(a, b, 2) + x% + (100 + x%) + 3!! + sqrt 16").unwrap();
+    assert_eq!(
+        c.eval(&[1.0, 4.0, 50.0]),
+        Result::Ok(7.0 * 1.5 + 150.0 + 3.0 + 4.0)
+    );
+    let mut c = compile("sin(90°) + x!").unwrap();
+    assert!((c.eval(&[3.0]).unwrap() - 7.0).abs() < 1e-12);
+    // The same results as when interpreting
+    for input in [
+        "3x^2 - x/7",
+        "log(x, 2) * atan2(x, 1)",
+        "x % 3 + |x - 4|",
+        "-x^3",
+    ]
+    .iter()
+    {
+        let mut c = compile(input).unwrap();
+        for &x in [0.5, 2.0, 7.25].iter() {
+            let mut context = Context::new();
+            context.set("x", Value::Number(x));
+            let mut p =
+                calc::parser::Parser::with_context(calc::scanner::Scanner::new(input), context);
+            let expected = p.next().unwrap().unwrap().as_number();
+            assert_eq!(c.eval(&[x]).ok(), expected, "{} with x = {}", input, x);
+        }
+    }
+}
+
+#[test]
+fn errors() {
+    assert!(compile("x = 2").is_err());
+    assert!(compile("x, y").is_err());
+    assert!(compile("x days").is_err());
+    assert!(compile("1 / 0 + x").is_err());
+    assert!(compile("").is_err());
+    let mut c = compile("1 / x").unwrap();
+    assert_eq!(c.eval(&[2.0]), Result::Ok(0.5));
+    assert!(c.eval(&[0.0]).unwrap_err().contains("Division by zero"));
+    assert!(c.eval(&[1.0, 2.0]).is_err());
+    let e = c.eval_many(&[[1.0], [0.0]]).unwrap_err();
+    assert!(e.starts_with("Row 2: Error: Division by zero"));
+    let mut c = compile("sqrt(x)").unwrap();
+    assert!(c.eval(&[-1.0]).is_err());
+}
@@ -0,0 +1,43 @@
+use calc::currency::*;
+
+const RATES: &str = "
+base = \"EUR\"
+[rates]
+USD = 1.25
+SEK = 10
+";
+
+#[test]
+fn parse() {
+    let r = Rates::parse(RATES).unwrap();
+    assert_eq!(r.rate("EUR"), Option::Some(1.0));
+    assert_eq!(r.rate("SEK"), Option::Some(10.0));
+    assert_eq!(r.rate("GBP"), Option::None);
+    assert!(Rates::parse("[rates]\nUSD = 1.1").is_err());
+    assert!(Rates::parse("base = \"EUR\"\n[rates]\nUSD = -1").is_err());
+    assert!(Rates::parse("base = \"EUR\"\n[rates]\nusd = 1").is_err());
+    assert!(Rates::parse("base = ").is_err());
+}
+
+#[test]
+fn convert() {
+    let r = Rates::parse(RATES).unwrap();
+    assert_eq!(r.convert(10.0, "USD", "SEK"), Result::Ok(80.0));
+    assert_eq!(r.convert(5.0, "GBP", "GBP"), Result::Ok(5.0));
+    assert_eq!(
+        r.convert(5.0, "GBP", "EUR"),
+        Result::Err("No exchange rate for 'GBP'".to_string())
+    );
+    assert!(Rates::default().convert(1.0, "EUR", "USD").is_err());
+}
+
+#[test]
+fn rounding() {
+    assert_eq!(round(1.005), 1.01);
+    assert_eq!(round(2.675), 2.68);
+    assert_eq!(round(-1.005), -1.01);
+    assert_eq!(round(0.004), 0.0);
+    assert!(is_code("EUR"));
+    assert!(!is_code("Eur"));
+    assert!(!is_code("EURO"));
+}
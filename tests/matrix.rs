@@ -0,0 +1,67 @@
+use calc::matrix::*;
+
+fn m(rows: &[&[f64]]) -> Matrix {
+    Matrix::from_rows(&rows.iter().map(|r| r.to_vec()).collect::<Vec<_>>()).unwrap()
+}
+
+fn close(a: &Matrix, b: &Matrix) -> bool {
+    a.rows() == b.rows()
+        && a.cols() == b.cols()
+        && (0..a.rows()).all(|r| (0..a.cols()).all(|c| (a.get(r, c) - b.get(r, c)).abs() < 1e-9))
+}
+
+#[test]
+fn construct() {
+    assert_eq!(Matrix::identity(2), m(&[&[1.0, 0.0], &[0.0, 1.0]]));
+    assert_eq!(Matrix::zeros(1, 2), m(&[&[0.0, 0.0]]));
+    assert!(Matrix::from_rows(&[vec![1.0], vec![1.0, 2.0]]).is_none());
+    assert_eq!(
+        m(&[&[1.0, 2.0], &[3.0, 4.0]]).to_string(),
+        "[[1, 2], [3, 4]]"
+    );
+    assert_eq!(m(&[&[1.0, 2.0]]).to_string(), "[1, 2]");
+}
+
+#[test]
+fn products() {
+    let a = m(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+    assert_eq!(a.transpose().rows(), 3);
+    assert_eq!(
+        a.mul(&a.transpose()),
+        Option::Some(m(&[&[14.0, 32.0], &[32.0, 77.0]]))
+    );
+    assert!(a.mul(&a).is_none());
+    let f = m(&[&[1.0, 1.0], &[1.0, 0.0]]);
+    assert_eq!(f.pow(0), Option::Some(Matrix::identity(2)));
+    assert_eq!(f.pow(5), Option::Some(m(&[&[8.0, 5.0], &[5.0, 3.0]])));
+    assert!(close(
+        &f.pow(-2).unwrap().mul(&f.pow(2).unwrap()).unwrap(),
+        &Matrix::identity(2)
+    ));
+}
+
+#[test]
+fn decompositions() {
+    let a = m(&[&[2.0, -1.0, 0.0], &[-1.0, 2.0, -1.0], &[0.0, -1.0, 2.0]]);
+    assert!((a.det().unwrap() - 4.0).abs() < 1e-12);
+    assert_eq!(a.trace(), Option::Some(6.0));
+    assert_eq!(a.rank(), 3);
+    assert!(close(
+        &a.mul(&a.inv().unwrap()).unwrap(),
+        &Matrix::identity(3)
+    ));
+    let b = m(&[&[1.0], &[0.0], &[1.0]]);
+    assert!(close(&a.solve(&b).unwrap(), &m(&[&[1.0], &[1.0], &[1.0]])));
+    let e = a.eigenvalues().unwrap();
+    let s = 2f64.sqrt();
+    for (x, y) in e.iter().zip([2.0 - s, 2.0, 2.0 + s].iter()) {
+        assert!((x - y).abs() < 1e-9, "{:?}", e);
+    }
+    let singular = m(&[&[1.0, 2.0], &[2.0, 4.0]]);
+    assert_eq!(singular.det(), Option::Some(0.0));
+    assert_eq!(singular.rank(), 1);
+    assert!(singular.inv().is_none());
+    assert!(m(&[&[0.0, 1.0], &[1.0, 0.0]]).det().unwrap() == -1.0);
+    assert!(m(&[&[1.0, 2.0], &[3.0, 4.0]]).eigenvalues().is_none());
+    assert!(m(&[&[1.0, 2.0]]).det().is_none());
+}
@@ -1,8 +1,11 @@
+use calc::context::Context;
 use calc::parser::*;
+use calc::scanner::Scanner;
+use calc::value::{Format, Locale, Value};
 
 fn test(string: &str, value: f64) {
     let mut p = Parser::new(string);
-    let v = p.next().unwrap().unwrap();
+    let v = p.next().unwrap().unwrap().as_number().unwrap();
     assert!(
         (value - v).abs() < 0.000001,
         "{}: {} != {}",
@@ -12,22 +15,47 @@ fn test(string: &str, value: f64) {
     );
 }
 
-fn test_multiple(string: &str, values: &[f64]) {
+// Within a relative error of 1e-14
+fn test_precise(string: &str, value: f64) {
     let mut p = Parser::new(string);
+    let v = p.next().unwrap().unwrap().as_number().unwrap();
+    assert!(
+        (value - v).abs() <= 1e-14 * value.abs(),
+        "{}: {} != {}",
+        string,
+        v,
+        value
+    );
+}
+
+fn test_multiple(string: &str, values: &[f64]) {
+    let mut p = Parser::new(string).filter(|v| !matches!(v, Result::Ok(Value::Text(_))));
     for v in values {
-        let v2 = p.next().unwrap().unwrap();
+        let v2 = p.next().unwrap().unwrap().as_number().unwrap();
         assert!((v - v2).abs() < 0.000001, "{}: {} != {}", string, v2, v);
     }
 }
 
+fn test_display(string: &str, text: &str) {
+    let mut p = Parser::new(string);
+    assert_eq!(p.next().unwrap().unwrap().to_string(), text, "{}", string);
+}
+
 fn test_fail(string: &str) {
     let mut p = Parser::new(string);
-    for res in p.next() {
-        match res {
-            Result::Ok(v) => panic!("Should not return a valid answer ({} != {})", string, v),
-            _ => {}
+    if let Option::Some(Result::Ok(v)) = p.next() {
+        panic!("Should not return a valid answer ({} != {})", string, v);
+    }
+}
+
+fn test_error(string: &str, message: &str) {
+    for res in Parser::new(string) {
+        if let Result::Err(e) = res {
+            assert!(e.contains(message), "{}: {}", string, e);
+            return;
         }
     }
+    panic!("Should return an error ({})", string);
 }
 
 #[test]
@@ -44,17 +72,47 @@ fn easy() {
     test("(2)", 2.0);
 }
 
+#[test]
+fn unicode() {
+    test("2×π", 2.0 * std::f64::consts::PI);
+    test("√16 − 3", 1.0);
+    test("√(9) ÷ 3", 1.0);
+    test("2·3⋅4", 24.0);
+    test("3²", 9.0);
+    test("2³ + 1", 9.0);
+    test("10⁻²", 0.01);
+    test("2¹⁰", 1024.0);
+    // The same as -2^2
+    test("-2²", 4.0);
+    test("∛27", 3.0);
+    test("τ/π", 2.0);
+    test_multiple("r = 2, πr²", &[2.0, 4.0 * std::f64::consts::PI]);
+    test_multiple("x = 3, 2x²", &[3.0, 18.0]);
+    test_multiple("ä = 2, ä³", &[2.0, 8.0]);
+}
+
+#[test]
+fn unicode_fail() {
+    test_error("2 & 3", "Unrecognized character '&'");
+    test_error("(1 @ 2)", "Unrecognized character '@'");
+    test_error("2 ¼", "Unrecognized character '¼'");
+    test_error("x⁻", "Unrecognized character '⁻'");
+    test_error("1.2.3", "Invalid number or date '1.2.3'");
+    test_error("dice(\"3d6", "Missing the closing quote");
+    test_fail("²");
+}
+
 #[test]
 fn func() {
     test("cos pi", -1.0);
     test("sin pi", 0.0);
     test("tan pi", 0.0);
-    test("pi * rad2deg * deg2rad", 3.14159265);
+    test("pi * rad2deg * deg2rad", std::f64::consts::PI);
     test("abs -10", 10.0);
     test("ln 2 - log(2,e)", 0.0);
     test("sqrt2 - sqrt 2", 0.0);
     test("atan 5", 1.373400766945016);
-    test("asin 1", 1.5707963267948966);
+    test("asin 1", std::f64::consts::FRAC_PI_2);
     test("acos 1", 0.0);
     test("atan2(2,3)", 0.5880026035475675);
 }
@@ -68,6 +126,115 @@ fn func_fail() {
     test_fail("atan2 3");
 }
 
+#[test]
+fn arity() {
+    test("round(2.567)", 3.0);
+    test("round(2.567, 2)", 2.57);
+    test("round 2.5", 3.0);
+    test("log 100", 100f64.ln());
+    test("sum 4", 4.0);
+    test("pv(0.05, 10, -100)", 772.1734929184817);
+    test("compound(100, 0.05, 1)", 105.0);
+    test("rnd() * 0", 0.0);
+    // A coefficient belongs to the argument
+    test_multiple(
+        "x = 3, sin 2x, sin 2 x",
+        &[3.0, 6f64.sin(), 2f64.sin() * 3.0],
+    );
+    test("sin 2pi", (2.0 * std::f64::consts::PI).sin());
+    test_error("sin(1, 2)", "sin expects 1 argument, got 2 (sin(x))");
+    test_error("atan2(1)", "atan2 expects 2 arguments, got 1 (atan2(y, x))");
+    test_error(
+        "round(1, 2, 3)",
+        "round expects 1 to 2 arguments, got 3 (round(x, digits=0))",
+    );
+    test_error("sum()", "sum expects at least 1 argument, got 0");
+    test_error("rnd(2)", "rnd expects 0 arguments, got 1");
+    test_error("irr(1)", "irr expects at least 2 arguments");
+    test_error("dice(1)", "dice expects 2 to 3 arguments");
+    test_error("hypot 3", "Syntax: hypot(x, y)");
+}
+
+#[test]
+fn keywords() {
+    test("log(8, base=2)", 3.0);
+    test("log(x=100, base=10)", 2.0);
+    test("atan2(y=1, x=3)", 1f64.atan2(3.0));
+    test("atan2(x=3, y=1)", 1f64.atan2(3.0));
+    test("round(2.567, digits=1)", 2.6);
+    // Skipped parameters get their defaults
+    test("pv(0.05, 10, 0, fv=1000)", -1000.0 / 1.05f64.powi(10));
+    test("round(x=1.5)", 2.0);
+    test_multiple("base = 3, log(9, base=base), base", &[3.0, 2.0, 3.0]);
+    test_error(
+        "log(8, bas=2)",
+        "log has no parameter 'bas', did you mean 'base'?",
+    );
+    test_error(
+        "round(1, 2, digits=2)",
+        "round got the argument 'digits' twice",
+    );
+    test_error(
+        "log(base=2, x=8, base=3)",
+        "log got the argument 'base' twice",
+    );
+    test_error(
+        "log(base=2, 8)",
+        "Positional arguments must come before keyword arguments",
+    );
+    test_error(
+        "log(base=2)",
+        "log is missing the argument 'x' (log(x, base=e))",
+    );
+    test_error("sum(x=1)", "sum has no parameter 'x'");
+}
+
+#[test]
+fn user_functions() {
+    test_multiple(
+        "f(x, n=2) = x^n + 1, f(3), f(3, 3), f(n=3, x=2), f(x=2)",
+        &[10.0, 28.0, 9.0, 5.0],
+    );
+    // Parameters are not time units
+    test_multiple("f(h) = 2h, f(3)", &[6.0]);
+    test_display("f(x, n=2) = x^n + 1", "f(x, n=2) = x^n + 1");
+    // Defaults can use earlier parameters, variables are restored after a call
+    test_multiple(
+        "a = 5, m(a, b=a*2) = a + b, m(1), m(1, b=1), a",
+        &[5.0, 3.0, 2.0, 5.0],
+    );
+    test_multiple(
+        "y = 10, g(t) = t*y, g(2), y = 1, g(2)",
+        &[10.0, 20.0, 1.0, 2.0],
+    );
+    test_multiple("q(x) = x(x+1), q(2), x = 1, q(x + 1)", &[6.0, 1.0, 6.0]);
+    test_multiple("f(x) = x, f = 3, f(2)", &[3.0, 6.0]);
+    let mut p = Parser::new("f(x) = 2x, simplify f(f(y)) + 1, solve f(x) = 4");
+    p.next();
+    assert_eq!(p.next().unwrap().unwrap().to_string(), "4y + 1");
+    assert_eq!(p.next().unwrap().unwrap().to_string(), "x = 2");
+    test_error(
+        "f(x, n=2) = x^n, f(1, 2, 3)",
+        "f expects 1 to 2 arguments, got 3 (f(x, n=2))",
+    );
+    test_error("f(x, n=2) = x^n, f(n=2)", "f is missing the argument 'x'");
+    test_error("f(x) = x, f", "'f' is a function, call it as f(x)");
+    test_error(
+        "f(x, n=2) = x^n, f(1, m=2)",
+        "f has no parameter 'm', did you mean 'n' or 'x'?",
+    );
+    test_error("f(x, x) = 1", "The parameter 'x' is already defined");
+    test_error(
+        "f(a=1, b) = a",
+        "Parameters without defaults must come before those with",
+    );
+    test_error("f(2) = 1", "Syntax: f(x, y=default) = expr");
+    test_error(
+        "f(x) = 1, f(x) = f(x) + 1, f(1)",
+        "Too many nested calls of f",
+    );
+}
+
 #[test]
 fn comp_1() {
     test("((2 * 3 - 6)**0.5 + 3.212^2) / 10.0", 1.0316944);
@@ -88,6 +255,68 @@ fn no_mul() {
     test("2 -5", -3.0);
 }
 
+#[test]
+fn strict() {
+    let strict = |s: &str| {
+        let mut p = Parser::new(s);
+        p.set_strict(true);
+        p.map(|v| v.map(|v| v.as_number().unwrap()))
+            .collect::<Result<Vec<f64>, String>>()
+    };
+    // Coefficients are still allowed
+    assert_eq!(
+        strict("x = 2, 2x, -2x, 3*2x, 2.5x, 3(x+1), 2pi, 2sin(0)"),
+        Result::Ok(vec![
+            2.0,
+            4.0,
+            -4.0,
+            12.0,
+            5.0,
+            9.0,
+            2.0 * std::f64::consts::PI,
+            0.0
+        ])
+    );
+    assert_eq!(
+        strict("20% of 150, 3h 25min in min"),
+        Result::Ok(vec![30.0, 205.0])
+    );
+    for s in [
+        "2 3",
+        "e pi",
+        "x = 2, 2 x",
+        "x = 2, sin x y",
+        "x = 2, 2^3x",
+        "x = 2, 1/2x",
+        "(2)(3)",
+        "2 sin 1",
+    ]
+    .iter()
+    {
+        let e = strict(s).unwrap_err();
+        assert!(
+            e.contains("Implicit multiplication is not allowed"),
+            "{}: {}",
+            s,
+            e
+        );
+    }
+    assert!(strict("x = 2, sin x y")
+        .unwrap_err()
+        .contains("write 'sin x * y'"));
+    // The default is unchanged, but ambiguous forms are warned about
+    test("2 3", 6.0);
+    let mut p = Parser::new("x = 2, 2x, 1/2x, sin x y");
+    p.by_ref().for_each(drop);
+    assert_eq!(
+        p.take_warnings(),
+        [
+            "Warning: '1/2x' is read as '1/2 * x'",
+            "Warning: 'sin x y' is read as 'sin x * y'"
+        ]
+    );
+}
+
 #[test]
 fn multiple() {
     test("x = 5, x*x/5", 5.0);
@@ -102,6 +331,160 @@ fn multiple() {
     );
 }
 
+#[test]
+fn decimal_comma() {
+    let fi = Locale::from_name("fi").unwrap();
+    let values: Vec<f64> = Parser::from(Scanner::with_locale(
+        "3,5 + 1; round(2,567; 1); log(8; base=2); a, b = 1,5; 2\na*b",
+        &fi,
+    ))
+    .filter_map(|v| v.unwrap().as_number())
+    .collect();
+    assert_eq!(values, [4.5, 2.6, 3.0, 3.0]);
+}
+
+#[test]
+fn decimal_comma_text() {
+    let fi = Locale::from_name("fi").unwrap();
+    let mut context = Context::new();
+    context.set_format(Format::with_locale(&fi, false));
+    let input = "a, b = 1,5; 2\nsolve x^2 = 2,25\nsimplify 2,5*x + x\nf(x, y) = x*1,5 + y";
+    let texts: Vec<String> = Parser::with_context(Scanner::with_locale(input, &fi), context)
+        .map(|v| v.unwrap().to_string())
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            "a = 1,5; b = 2",
+            "x = -1,5 or x = 1,5",
+            "3,5x",
+            "f(x; y) = x * 1,5 + y"
+        ]
+    );
+    // The token of an error is read with the same locale
+    let mut p = Parser::from(Scanner::with_locale("2,5 USD + 1", &fi));
+    let e = p.next().unwrap().unwrap_err();
+    assert!(e.contains("Value: 2.5"), "{}", e);
+}
+
+#[test]
+fn scripts() {
+    let script = "#!/usr/bin/env calc
+# Costs of a trip
+fuel = 1.5 * 100   # litres
+hotel = 2 * 89
+
+total = sum(
+    fuel,  # comments also work here
+    hotel
+) \\
+  + 10
+total / 2
+";
+    test_multiple(script, &[150.0, 178.0, 338.0, 169.0]);
+    test_multiple("det([[1, 2],\n     [3, 4]])\n1", &[-2.0, 1.0]);
+    let e = Parser::new("x = 1\ny = (2 +\n  x)\nz = y +* 2")
+        .nth(2)
+        .unwrap();
+    assert_eq!(
+        e.unwrap_err(),
+        "Error: Expected a number or parenthesis\nOperator: +\nLine: 4\nz = y +* 2\n      ^"
+    );
+    test_error("1 + \\ 2", "Unrecognized character '\\'");
+}
+
+#[test]
+fn assignment() {
+    test_multiple(
+        "x = 2, x += 3, x *= 2, x -= 1 + 1, x /= 4, x ^= 2, x %= 3",
+        &[2.0, 5.0, 10.0, 8.0, 2.0, 4.0, 1.0],
+    );
+    test_multiple("x = 2, x *= 1 + 1, x", &[2.0, 4.0, 4.0]);
+    test_display("x = 2, x += 3", "2");
+    test_error("y += 1", "Unknown variable or constant 'y'");
+    // The values go to the end of the line
+    test_multiple("a, b = 3, 4\na, b, a + b", &[3.0, 4.0, 7.0]);
+    test_multiple("a, b = 1, 2\na, b = b, a\na, b", &[2.0, 1.0]);
+    test_display("a, b = 1, 2 + 3", "a = 1, b = 5");
+    test_multiple("a, b", &[]);
+    test_error("a, b = 1", "Expected 2 values for a, b, got 1");
+    test_error("p, q = 1, 2, 3", "Expected 2 values for p, q, got 3");
+    // Without enough values a known name is a separate expression
+    test_multiple("y = 1, y, x = 5", &[1.0, 1.0, 5.0]);
+    test_error("a, a = 1, 2", "'a' is assigned more than once");
+}
+
+#[test]
+fn constants() {
+    test_multiple("const g = 9.81, g * 2", &[9.81, 19.62]);
+    test_multiple("const g = 2, f(g) = g^2, f(3), g", &[2.0, 9.0, 2.0]);
+    test_error("const g = 9.81, g = 3", "Cannot assign to the constant 'g'");
+    test_error(
+        "const g = 9.81, g += 3",
+        "Cannot assign to the constant 'g'",
+    );
+    test_error(
+        "const g = 1, const g = 2",
+        "Cannot assign to the constant 'g'",
+    );
+    test_error(
+        "const g = 1, a, g = 2, 3",
+        "Cannot assign to the constant 'g'",
+    );
+    test_error("const g = 1, g(x) = x", "Cannot assign to the constant 'g'");
+    test_error("const 2 = 1", "Syntax: const name = expr");
+}
+
+fn texts(string: &str) -> Vec<String> {
+    Parser::new(string)
+        .filter_map(|v| match v.unwrap() {
+            Value::Text(s) => Option::Some(s),
+            _ => Option::None,
+        })
+        .collect()
+}
+
+#[test]
+fn workspace() {
+    let t = texts("b = 2, const g = 9.81, f(x, n=2) = x^n, a = [1, 2], vars, who");
+    assert_eq!(
+        t[1],
+        "a = [1, 2] (matrix)\nb = 2 (number)\nf(x, n=2) = x^n (function)\nconst g = 9.81 (number)"
+    );
+    assert_eq!(t[2], "a b f g");
+    let t = texts("x = 1, f(x) = x, y = 2, del x f, who, clear, vars");
+    assert_eq!(
+        t[1..],
+        [
+            "Deleted x, f",
+            "y",
+            "Deleted all variables and functions",
+            "No variables or functions are defined"
+        ]
+    );
+    test_multiple("const g = 1, del g, g = 2, g", &[1.0, 2.0, 2.0]);
+    test_error("x = 1, del x, x", "Unknown variable or constant 'x'");
+    test_error("x = 1, del x y", "Unknown variable or constant 'y'");
+    test_error("x = 1, del x x", "'x' is listed more than once");
+    test_error("mod = 1, del nope", "Unknown variable or constant 'nope'\n");
+    test_error("node = 1, del nope", "'nope', did you mean 'node'?");
+    test_error("del", "Syntax: del name ...");
+    // The same from Rust
+    let mut p = Parser::new("x = 2, f(t) = t + x");
+    for res in &mut p {
+        res.unwrap();
+    }
+    let context = p.context_mut();
+    assert_eq!(context.variables(), [("x", &Value::Number(2.0))]);
+    assert_eq!(context.functions()[0].1.syntax("f"), "f(t)");
+    context.set("y", Value::Number(3.0));
+    assert!(context.delete("x"));
+    assert!(!context.delete("x"));
+    assert_eq!(context.get("y"), Option::Some(&Value::Number(3.0)));
+    context.clear();
+    assert!(context.variables().is_empty() && context.functions().is_empty());
+}
+
 #[test]
 fn aggregate() {
     test("sum(1,2,3)", 6.0);
@@ -111,3 +494,486 @@ fn aggregate() {
     test("sum(exp(log(3)))", 3.0);
     test("sqrt(mean(exp(log(4))))", 2.0);
 }
+
+#[test]
+fn suggestions() {
+    test_error("sqr 4", "did you mean 'sqrt'?");
+    test_error("atna2(1, 2)", "did you mean 'atan2'");
+    test_error("speed = 5, sped * 2", "did you mean 'speed' or 'seed'?");
+    test_error("velocity = 5, velocty * 2", "did you mean 'velocity'?");
+    test_error("qwerty", "Unknown variable or constant 'qwerty'\n");
+}
+
+fn seeded(string: &str, seed: u64) -> Vec<f64> {
+    Parser::with_context(Scanner::new(string), Context::seeded(seed))
+        .map(|v| v.unwrap().as_number().unwrap())
+        .collect()
+}
+
+#[test]
+fn random() {
+    for v in seeded("rnd, rnd, rnd, uniform(2, 3), rand_int(-2, 2)", 1) {
+        assert!((-2.0..3.0).contains(&v), "{}", v);
+    }
+    for v in seeded("rand_int(1, 6), dice(1, 6), dice(\"d6\"), dice(\"1d6\")", 2) {
+        assert!((1.0..=6.0).contains(&v) && v.fract() == 0.0, "{}", v);
+    }
+    let v = seeded("dice(\"3d6+2\")", 3)[0];
+    assert!((5.0..=20.0).contains(&v) && v.fract() == 0.0, "{}", v);
+    assert!(seeded("exponential(2)", 4)[0] >= 0.0);
+    assert_eq!(seeded("normal(5, 0)", 5)[0], 5.0);
+}
+
+#[test]
+fn random_seed() {
+    let input = "rnd, normal(0, 1), exponential 3, dice(\"4d20\"), rand_int(0, 1000)";
+    assert_eq!(seeded(input, 42), seeded(input, 42));
+    assert_ne!(seeded(input, 42), seeded(input, 43));
+    let v = seeded("seed(7), rnd, rnd, seed(7), rnd", 0);
+    assert_eq!(v[1], v[4]);
+    assert_ne!(v[1], v[2]);
+}
+
+#[test]
+fn random_reevaluate() {
+    let mut p = Parser::new("rnd");
+    let e = p.parse().unwrap().unwrap();
+    let a = p.eval(&e).unwrap();
+    let b = p.eval(&e).unwrap();
+    assert_ne!(a, b);
+    let mut c1 = Context::seeded(9);
+    let mut c2 = Context::seeded(9);
+    assert_eq!(c1.eval(&e).unwrap(), c2.eval(&e).unwrap());
+}
+
+#[test]
+fn random_fail() {
+    test_fail("rand_int(3, 2)");
+    test_fail("normal(0, -1)");
+    test_fail("exponential 0");
+    test_fail("dice(\"3x6\")");
+    test_fail("dice(0, 6)");
+    test_fail("seed(-1)");
+    test_error("rand_int(0, 10^19)", "between -2^63 and 2^63");
+    test_error("dice(1, 10^19)", "less than 2^63 sides");
+    test_error("dice(10^15, 6)", "At most 1000000 dice");
+}
+
+#[test]
+fn combinatorics() {
+    test("nCr(5, 2)", 10.0);
+    test("choose(50, 25)", 126410606437752.0);
+    test("nCr(3, 5)", 0.0);
+    test("nPr(5, 2)", 20.0);
+    test("gcd(12, 18)", 6.0);
+    test("gcd(12, 18, 8)", 2.0);
+    test("lcm(4, 6)", 12.0);
+    test("lcm(2, 3, 4)", 12.0);
+    test("isprime 97", 1.0);
+    test("isprime 91", 0.0);
+    test("isprime(2^31-1)", 1.0);
+    test("isprime(1000000007)", 1.0);
+    test("isprime 1", 0.0);
+    test("fib 0 + fib 1", 1.0);
+    test("fib 10", 55.0);
+    test("fib(80)", 23416728348467685.0);
+    test("powmod(4, 13, 497)", 445.0);
+    test("powmod(2, 1000, 1000000007)", 688423210.0);
+}
+
+#[test]
+fn factorials() {
+    test("0!", 1.0);
+    test("5!", 120.0);
+    test("170!", 7.257415615307994e306);
+    test("0.5! - sqrt(pi)/2", 0.0);
+    test("(-0.5)! - sqrt pi", 0.0);
+    test("2.5!", 3.323350970447843);
+    test("5!!", 15.0);
+    test("6!!", 48.0);
+    test("0!!", 1.0);
+    test("3!!+1", 4.0);
+}
+
+#[test]
+fn factorization() {
+    test_display("factor 360", "2^3 * 3^2 * 5");
+    test_display("factor(97)", "97");
+    test_display("factor 1", "1");
+    test_display("factor(600851475143)", "71 * 839 * 1471 * 6857");
+    test_display("factor(2^31-1)", "2147483647");
+    test("factor(360) + 1", 361.0);
+    test("x = factor(12), x * 2", 12.0);
+}
+
+#[test]
+fn combinatorics_fail() {
+    test_fail("171!");
+    test_fail("(-2)!");
+    test_fail("2.5!!");
+    test_fail("nCr(2.5, 1)");
+    test_fail("nCr(-3, 1)");
+    test_fail("gcd(1.5, 3)");
+    test_fail("powmod(2, 3, 0)");
+    test_fail("factor 0");
+    test_fail("factor 1.5");
+    test_fail("fib(2000)");
+    test_fail("lcm(2^40+1, 2^40-1)");
+    // Overflowing results are errors, and huge arguments do not take forever
+    test_error("choose(2000, 1000)", "Result is too large");
+    test_error("fib(10^12)", "Result is too large");
+    test_error("(10^15)!!", "Result is too large");
+    test_error("nCr(10^15, 5*10^14)", "Result is too large");
+    test_error("nPr(10^15, 10^14)", "Result is too large");
+}
+
+#[test]
+fn angles() {
+    test("sin 30°", 0.5);
+    test("cos(60°) + 1", 1.5);
+    test_multiple("mode deg, sin 30", &[0.5]);
+    test_multiple("mode deg, sin 90, cos 180, tan 45", &[1.0, -1.0, 1.0]);
+    test_multiple(
+        "mode deg, asin 1, acos 0, atan 1, atan2(1, 1)",
+        &[90.0, 90.0, 45.0, 45.0],
+    );
+    test_multiple("mode grad, sin 100, asin 1, 90°", &[1.0, 100.0, 100.0]);
+    test_multiple("mode deg, sin pi, mode rad, sin pi", &[0.0548036, 0.0]);
+    test_multiple("mode deg, x = 30, mode rad, sin(x°)", &[30.0, 0.5]);
+    test_multiple("mode deg, sin 180 + cos 90 + sin 360", &[0.0]);
+    test_display("mode deg", "mode deg");
+    test_display("mode", "mode rad");
+}
+
+#[test]
+fn angles_fail() {
+    test_fail("mode foo");
+    test_error("mode deg, tan 90", "Tangent is undefined");
+    test_fail("2 * mode");
+}
+
+#[test]
+fn hyperbolic() {
+    test("sinh 1", 1.1752011936438014);
+    test("cosh 1", 1.5430806348152437);
+    test("tanh 1", 0.7615941559557649);
+    test("asinh(sinh 2)", 2.0);
+    test("acosh(cosh 2)", 2.0);
+    test("atanh(tanh 0.5)", 0.5);
+}
+
+#[test]
+fn elementary() {
+    test("sec 0", 1.0);
+    test("csc(pi/2)", 1.0);
+    test("cot(pi/4)", 1.0);
+    test_multiple("mode deg, sec 60, csc 30, cot 45", &[2.0, 2.0, 1.0]);
+    test("log2 8", 3.0);
+    test("log10 1000", 3.0);
+    test("log(e^2)", 2.0);
+    test("log(8, 2)", 3.0);
+    test("cbrt 27 + cbrt(-8)", 1.0);
+    test("nthroot(16, 4)", 2.0);
+    test("nthroot(-32, 5)", -2.0);
+    test("floor 2.7 + ceil 2.2 + trunc(-2.7)", 3.0);
+    test("floor(-2.5)", -3.0);
+    test("round 2.5", 3.0);
+    test("round(2.71828, 2)", 2.72);
+    test("round(1234, -2)", 1200.0);
+    test("round(2.5) * 2", 6.0);
+    test("round(1E300, 100)", 1e300);
+    test("round(1.5, 308)", 1.5);
+    test("sign(-3) + sign 0 + sgn 7", 0.0);
+    test("hypot(3, 4)", 5.0);
+    test("clamp(5, 0, 3) + clamp(-1, 0, 3)", 3.0);
+}
+
+#[test]
+fn elementary_fail() {
+    test_error(
+        "round(1, 400)",
+        "digits must be an integer from -308 to 308",
+    );
+    test_error("round(1, 0.5)", "digits must be an integer");
+    test_fail("acosh 0.5");
+    test_fail("atanh 1");
+    test_fail("csc 0");
+    test_fail("cot 0");
+    test_fail("log2 0");
+    test_fail("log10(-1)");
+    test_fail("nthroot(-16, 4)");
+    test_fail("nthroot(2, 0)");
+    test_fail("round(2.5, 0.5)");
+    test_fail("round(1, 2, 3)");
+    test_fail("clamp(1, 3, 0)");
+    test_fail("hypot 3");
+}
+
+#[test]
+fn special() {
+    // Reference values to about the precision of f64
+    test_precise("gamma 0.5", 1.772453850905516);
+    test_precise("gamma 5", 24.0);
+    test_precise("gamma(-1.5)", 2.363271801207355);
+    test_precise("lgamma 100", 359.1342053695754);
+    test_precise("beta(2, 3)", 1.0 / 12.0);
+    test_precise("erf 1", 0.8427007929497149);
+    test_precise("erfc 1", 0.15729920705028513);
+    test_precise("erfinv(erf 0.5)", 0.5);
+    test_precise("erfinv 0.999", 2.326753765513525);
+    test_precise("besselj0 1", 0.7651976865579666);
+    test_precise("besselj1 1", 0.4400505857449335);
+    test_precise("besseljn(2, 1)", 0.11490348493190049);
+    test_precise("bessely0 1", 0.08825696421567696);
+    test_precise("zeta 2", 1.6449340668482264);
+    test_precise("zeta 3", 1.2020569031595942);
+    test_precise("zeta 0.5", -1.4603545088095868);
+    test_precise("zeta 0", -0.5);
+    test_precise("zeta(-1)", -1.0 / 12.0);
+    test_precise("zeta(-2)", 0.0);
+    test_precise("lambertw 1", 0.5671432904097838);
+    test_precise("lambertw e", 1.0);
+    test_precise("lambertw(-1/e)", -1.0);
+    test_precise("lambertw 10", 1.7455280027406994);
+}
+
+#[test]
+fn special_fail() {
+    test_fail("gamma 0");
+    test_fail("gamma(-2)");
+    test_fail("lgamma 0");
+    test_fail("beta(0, 1)");
+    test_fail("erfinv 1");
+    test_fail("bessely0 0");
+    test_fail("besseljn(1.5, 1)");
+    test_fail("zeta 1");
+    test_fail("lambertw(-1)");
+    test_fail("beta 2");
+}
+
+#[test]
+fn financial() {
+    test("pmt(0.05/12, 360, 200000)", -1073.6432460242797);
+    test("pv(0.05, 10, -100)", 772.1734929184817);
+    test("fv(0.05, 10, -100)", 1257.789253554884);
+    test("fv(0, 10, -100, -50)", 1050.0);
+    test("nper(0.01, -100, 1000)", 10.588644459423241);
+    test("rate(360, pmt(0.004, 360, 200000), 200000)", 0.004);
+    test("npv(0.1, -1000, 500, 600)", -45.07888805409476);
+    test("irr(-1000, 500, 600)", 0.06394102980498542);
+    test("npv(irr(-100, 30, 40, 50), 30, 40, 50) - 100", 0.0);
+    test("compound(1000, 0.05, 10)", 1628.894626777442);
+    test("compound(1000, 0.05, 10, 12)", 1647.00949769028);
+}
+
+#[test]
+fn percent() {
+    test("15%", 0.15);
+    test("100 + 15%", 115.0);
+    test("100 - 15%", 85.0);
+    test("200 * 15%", 30.0);
+    test("(50%) * 4", 2.0);
+    test("20 % of 150", 30.0);
+    test("100 + 15% of 200", 130.0);
+    test("pmt(5%/12, 360, 200000)", -1073.6432460242797);
+    test("5 % 3", 2.0);
+    test("10 % (4)", 2.0);
+    test("5 % -3", 2.0);
+    test("5%-3", 2.0);
+    test("5% - 3", -2.95);
+}
+
+#[test]
+fn financial_fail() {
+    test_fail("irr(100, 100)");
+    test_fail("irr(-100)");
+    test_fail("pmt(0.1, 0, 100)");
+    test_fail("compound(1000, 0.05, 10, 0)");
+    test_fail("pv(0.1, 10)");
+    test_fail("20 % of");
+}
+
+#[test]
+fn dates() {
+    test_display("2026-10-18 + 90 days", "2027-01-16");
+    test_display("2026-10-18 - 2026-01-01", "290d");
+    test_display("3h 25min * 4", "13h 40min");
+    test_display("2 * (1d 3h 25min)", "2d 6h 50min");
+    test_display("2024-02-29T23:59:59 + 1s", "2024-03-01");
+    test_display("2026-10-18T12:30 - 1 week", "2026-10-11T12:30:00");
+    test_display("-(90 min)", "-1h 30min");
+    test_display("1.5h / 3", "30min");
+    test("(2026-03-01 - 2024-03-01) in days", 730.0);
+    test("90 min in hours", 1.5);
+    test("2h / 30min", 4.0);
+    test_multiple("h = 2, 3 h", &[2.0, 6.0]);
+    let v = Parser::new("(now - today) in hours")
+        .next()
+        .unwrap()
+        .unwrap()
+        .as_number()
+        .unwrap();
+    assert!((0.0..24.0).contains(&v), "{}", v);
+}
+
+#[test]
+fn dates_fail() {
+    test_fail("2026-02-30");
+    test_fail("2026-10-18T24:00");
+    test_error("2026-01-01 + 2026-01-01", "Cannot compute date + date");
+    test_error("2026-01-01 * 2", "Cannot compute date * number");
+    test_error("3h + 2", "Cannot compute duration + number");
+    test_error("5 in hours", "Cannot convert a number");
+    test_error("3h in parsecs", "Unknown unit 'parsecs'");
+    test_error("3h / 0", "Division by zero");
+    test_fail("sin(3h)");
+}
+
+fn money(string: &str, text: &str) {
+    let rates = "base = \"EUR\"\n[rates]\nUSD = 1.25\nSEK = 10\n";
+    let mut context = Context::new();
+    context.set_rates(calc::currency::Rates::parse(rates).unwrap());
+    let res = Parser::with_context(Scanner::new(string), context)
+        .last()
+        .unwrap();
+    match res {
+        Result::Ok(v) => assert_eq!(v.to_string(), text, "{}", string),
+        Result::Err(e) => assert!(e.contains(text), "{}: {}", string, e),
+    }
+}
+
+#[test]
+fn currencies() {
+    money("120 EUR + 30 USD in SEK", "1440.00 SEK");
+    money("120 EUR + 30 USD", "144.00 EUR");
+    money("10 USD in EUR", "8.00 EUR");
+    money("100 EUR + 15%", "115.00 EUR");
+    money("3 * 9.99 USD", "29.97 USD");
+    money("100 SEK / 4", "25.00 SEK");
+    money("100 EUR / 25 USD", "5");
+    money("x = 5 EUR, x * 2 - 1 EUR", "9.00 EUR");
+    money("1.005 EUR", "1.01 EUR");
+    money("-(2 EUR)", "-2.00 EUR");
+    money("5 GBP in EUR", "No exchange rate for 'GBP'");
+    money("5 EUR + 2 GBP", "No exchange rate for 'GBP'");
+    money("10 EUR + 5", "Cannot compute currency + number");
+    money("10 EUR / 0", "Division by zero");
+    money("2 EUR in hours", "Cannot convert a currency to hours");
+    test_error("10 EUR in USD", "No exchange rates have been loaded");
+}
+
+#[test]
+fn matrices() {
+    test_display("[[1,2],[3,4]] * [[5,6],[7,8]]", "[[19, 22], [43, 50]]");
+    test_display("transpose [[1,2,3],[4,5,6]]", "[[1, 4], [2, 5], [3, 6]]");
+    test_display("inv([[2,0],[0,4]])", "[[0.5, 0], [0, 0.25]]");
+    test_display("solve([[2,1],[1,3]], [3,5])", "[0.8, 1.4]");
+    test_display("solve([[2,1],[1,3]], [[3],[5]])", "[[0.8], [1.4]]");
+    let mut p = Parser::new("A = [[1,1],[1,0]], A^10");
+    assert_eq!(
+        p.nth(1).unwrap().unwrap().to_string(),
+        "[[89, 55], [55, 34]]"
+    );
+    // A row vector on the right is a column
+    test_display("[[1,2],[3,4]] * [5,6]", "[17, 39]");
+    test_display("[[1,2],[3,4]] * [[5],[6]]", "[[17], [39]]");
+    test_display("zeros(2, 3)", "[[0, 0, 0], [0, 0, 0]]");
+    test_display("eye 2 * 3 - [[1,0],[0,1]]", "[[2, 0], [0, 2]]");
+    test_display("[1, 2] / 2", "[0.5, 1]");
+    test_display("-[[1],[2]]", "[[-1], [-2]]");
+    test("det [[1,2],[3,4]]", -2.0);
+    test("det(inv [[1,2],[3,4]])", -0.5);
+    test("rank [[1,2],[2,4]]", 1.0);
+    test("trace(identity 3)", 3.0);
+    test("[2 + 3] * 2", 10.0);
+    test("det [[2,1],[1,2]] - det(eye 3)", 2.0);
+    let mut p = Parser::new("eig [[2,1],[1,2]]");
+    let e = p.next().unwrap().unwrap().to_string();
+    assert!(
+        e == "[1, 3]" || e == "[0.9999999999999998, 2.9999999999999996]",
+        "{}",
+        e
+    );
+}
+
+#[test]
+fn matrices_fail() {
+    test_error(
+        "[1,2] + [1,2,3]",
+        "Matrix dimensions do not match (1x2 + 1x3)",
+    );
+    test_error("[[1,2],[3]]", "All rows must have the same length");
+    test_error("[1, [2, 3]]", "Cannot mix numbers and rows");
+    test_error("inv [[1,2],[2,4]]", "singular");
+    test_error("det [1,2]", "det needs a square matrix (1x2)");
+    test_error("eig [[1,2],[3,4]]", "symmetric");
+    test_error("solve([[1,0],[0,1]], [1,2,3])", "Expected 2 values");
+    test_error("[[1,2],[3,4]]^0.5", "Matrix powers must be integers");
+    test_error("[1, 2] + 1", "Cannot compute matrix + number");
+    test_error("zeros(0)", "at least one row");
+    test_error("identity(10000)", "Matrix is too large");
+    test_error("zeros(1000, 1001)", "Matrix is too large");
+    test_fail("sin [1, 2]");
+    test_fail("[1, 2");
+}
+
+#[test]
+fn equations() {
+    test_display("solve x^2 - 5x + 6 = 0", "x = 2 or x = 3");
+    test_display("solve x^2 + 1 = 0", "x = -i or x = i");
+    test_display("solve x^3 - 6x^2 + 11x = 6", "x = 1 or x = 2 or x = 3");
+    test_display("solve (x-1)^3", "x = 1");
+    test_display("solve (t - 1)(t + 2) = 0", "t = -2 or t = 1");
+    test_display("solve 2y / 4 = 3 - y", "y = 2");
+    test_display("solve x^2 + 2x + 5", "x = -1 - 2i or x = -1 + 2i");
+    test_display("solve x = x + 1", "No solution");
+    test_display("solve 2(x + 1) = 2x + 2", "Every x is a solution");
+    test_display("solve cos x = x", "x = 0.739085133215");
+    test_display("solve sqrt(x) = 3", "x = 9");
+    test_display("solve 2^x = 8", "x = 3");
+    test_display("solve 1/x = 0", "No solution found");
+    test_display("solve x^2 = 2", "x = -1.41421356237 or x = 1.41421356237");
+    test_display("solve (x^2)^(10^19) = 1", "x = -1 or x = 1");
+    test_display("solve 2s - 4 = 0 for s", "s = 2");
+    test_display("solve 2h = 4 for h", "h = 2");
+    test_display("solve x = 2^100", "x = 1267650600230000000000000000000");
+    let mut p = Parser::new("x = 5, solve x^2 = 4 for x");
+    assert_eq!(p.nth(1).unwrap().unwrap().to_string(), "x = -2 or x = 2");
+    test_multiple("x = 5, solve x^2 = 4 for x, x", &[5.0, 5.0]);
+    let s = Parser::new("solve sin x = 0")
+        .next()
+        .unwrap()
+        .unwrap()
+        .to_string();
+    assert!(
+        s.contains("x = -3.14159265359 or x = 0 or x = 3.14159265359"),
+        "{}",
+        s
+    );
+    assert!(s.ends_with("or ..."), "{}", s);
+}
+
+#[test]
+fn equations_fail() {
+    test_error("solve x^2 = y", "more than one unknown (x, y)");
+    test_error("solve 5 = 3", "no unknown");
+    test_error("solve x = 1 for", "Syntax: solve");
+    test_error("solve(1, 2, 3)", "Syntax: solve");
+    test_error("solve sin(x) = y for x", "Unknown variable");
+}
+
+#[test]
+fn simplification() {
+    test_display("simplify x + x + 2*3", "2x + 6");
+    test_display("simplify (x + 1)^2 - x^2", "2x + 1");
+    test_display("simplify sin(x)^2 + cos(x)^2", "1");
+    test_display("simplify x^2 sin(x) in unicode", "x²·sin(x)");
+    test_display("simplify x / (x + 1) in latex", "\\frac{x}{x + 1}");
+    test_display("simplify 2 * 3 + 10%", "6.6");
+    test_display("simplify pi + pi", "2pi");
+    test_display("simplify x - 2 h", "x - 2 * h");
+    test_display("simplify 2d + d", "3 * d");
+    test_display("simplify rnd - rnd", "rnd - rnd");
+    test_display("simplify x(x - 1)", "x^2 - x");
+    test_multiple("x = 2, simplify x + x", &[2.0]);
+}
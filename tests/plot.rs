@@ -0,0 +1,90 @@
+use calc::context::Context;
+use calc::plot::*;
+use calc::table::Range;
+
+fn ascii(width: usize, height: usize) -> Plot {
+    Plot {
+        width,
+        height,
+        ascii: true,
+        ..Plot::default()
+    }
+}
+
+#[test]
+fn render_ascii() {
+    let series = Series {
+        name: "x".to_string(),
+        values: vec![Option::Some(-2.0), Option::Some(0.0), Option::Some(2.0)],
+    };
+    assert_eq!(
+        ascii(3, 3).render((-1.0, 1.0), &[series]),
+        " 2 + |*\n   |-*-\n-2 +*|\n   +---\n    -1 1\n    * x"
+    );
+}
+
+#[test]
+fn gaps_and_poles() {
+    // The undefined point is left out, and the jump from the top to the bottom is not joined
+    let series = Series {
+        name: "f".to_string(),
+        values: vec![
+            Option::Some(1.0),
+            Option::None,
+            Option::Some(1.0),
+            Option::Some(100.0),
+            Option::Some(-100.0),
+            Option::Some(0.0),
+        ],
+    };
+    let plot = Plot {
+        y: Option::Some((0.0, 1.0)),
+        ..ascii(6, 2)
+    };
+    assert_eq!(
+        plot.render((1.0, 6.0), &[series]),
+        "1 +* *\n0 +-----*\n  +------\n   1    6\n   * f"
+    );
+}
+
+#[test]
+fn sampling() {
+    let x = Range::parse("t=0:1").unwrap();
+    let context = Context::new();
+    let s = ascii(5, 5).sample("1 / (t - 0.5)", &x, &context).unwrap();
+    assert_eq!(s.name, "1 / (t - 0.5)");
+    assert_eq!(
+        s.values,
+        vec![
+            Option::Some(-2.0),
+            Option::Some(-4.0),
+            Option::None,
+            Option::Some(4.0),
+            Option::Some(2.0)
+        ]
+    );
+    let s = ascii(3, 3).sample("2", &x, &context).unwrap();
+    assert_eq!(s.values, vec![Option::Some(2.0); 3]);
+    // Braille has two points in every character
+    assert_eq!(
+        Plot::default()
+            .sample("t", &x, &context)
+            .unwrap()
+            .values
+            .len(),
+        144
+    );
+    assert!(ascii(3, 3).sample("t + u", &x, &context).is_err());
+    assert!(ascii(3, 3).sample("t = 2", &x, &context).is_err());
+    let plot = ascii(10, 4).plot(&["t^2", "sqrt t"], &x, &context).unwrap();
+    assert!(plot.ends_with("* t^2\n   o sqrt t"));
+}
+
+#[test]
+fn too_large() {
+    let x = Range::parse("x=0:1").unwrap();
+    let error = ascii(100000, 20).plot(&["x"], &x, &Context::new());
+    assert!(error.unwrap_err().contains("at most 1000x500"));
+    let error = ascii(20, usize::MAX).plot(&["x"], &x, &Context::new());
+    assert!(error.unwrap_err().contains("at most 1000x500"));
+}
@@ -1,4 +1,5 @@
 use calc::scanner::*;
+use calc::value::Locale;
 
 #[test]
 fn multiplication() {
@@ -7,6 +8,52 @@ fn multiplication() {
     assert_eq!(sc.next(), Token::END);
 }
 
+#[test]
+fn decimal_comma() {
+    let fi = Locale::from_name("fi").unwrap();
+    let mut sc = Scanner::with_locale("3,5; 1,2 , 7;2", &fi);
+    assert_eq!(sc.next(), Token::Number(3.5));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Number(1.2));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Number(7.0));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Number(2.0));
+    assert_eq!(sc.next(), Token::END);
+    let mut sc = Scanner::new("3,5");
+    assert_eq!(sc.next(), Token::Number(3.0));
+    assert_eq!(sc.next(), Token::Comma);
+}
+
+#[test]
+fn lines() {
+    let mut sc = Scanner::new("1 # one\n(2,\n 3) \\\n+ 4\n\n");
+    assert_eq!(sc.next(), Token::Number(1.0));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.span(), (7, 8));
+    assert_eq!(sc.next(), Token::Lparen);
+    assert_eq!(sc.next(), Token::Number(2.0));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Number(3.0));
+    assert_eq!(sc.next(), Token::Rparen);
+    assert_eq!(sc.next(), Token::Addition);
+    assert_eq!(sc.next(), Token::Number(4.0));
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::END);
+    assert_eq!(Scanner::line_at("a\nbc\nd", 4), Option::Some((2, 2, "bc")));
+    assert_eq!(Scanner::line_at("abc\n", 1), Option::None);
+}
+
+#[test]
+fn caret() {
+    // The caret counts characters, × and π are two bytes each
+    let s = Scanner::print_at("2×π + foo", Token::Text("foo"), 8);
+    assert_eq!(s.lines().last().unwrap(), format!("{:6}^", ""));
+    let s = Scanner::print_at("1\n2×π + foo", Token::Text("foo"), 10);
+    assert_eq!(s.lines().last().unwrap(), format!("{:6}^", ""));
+}
+
 #[test]
 fn string() {
     let mut sc = Scanner::new(" asd gfd ");
@@ -28,15 +75,12 @@ fn function() {
 #[test]
 fn random() {
     let num = 20;
-    let string: String = std::iter::repeat(" rnd ").take(num).collect();
+    let string: String = " rnd ".repeat(num);
     let mut sc = Scanner::new(&string);
     let mut ctr = 0;
     loop {
         match sc.next() {
-            Token::Number(x) => {
-                assert!(x < 1.0);
-                assert!(x >= 0.0);
-            }
+            Token::Function(Function::Rnd) => {}
             Token::END => break,
             _ => panic!("Invalid token"),
         }
@@ -56,6 +100,17 @@ fn unwrap(t: &Token) -> f64 {
     }
 }
 
+#[test]
+fn quoted() {
+    let mut sc = Scanner::new("dice(\"3d6\") \"open");
+    assert_eq!(sc.next(), Token::Function(Function::Dice));
+    assert_eq!(sc.next(), Token::Lparen);
+    assert_eq!(sc.next(), Token::String("3d6"));
+    assert_eq!(sc.next(), Token::Rparen);
+    assert_eq!(sc.next(), Token::Unknown);
+    assert_eq!(sc.next(), Token::END);
+}
+
 #[test]
 fn value() {
     let mut sc = Scanner::new("1 pi 2E2 1.1 1.2");
@@ -118,3 +173,127 @@ fn end() {
     assert_eq!(sc.next(), Token::END);
     assert_eq!(sc.current(), Token::END);
 }
+
+#[test]
+fn keywords() {
+    for k in KEYWORDS {
+        if let Token::Text(_) = Scanner::new(k).next() {
+            panic!("Keyword not recognised: {}", k);
+        }
+    }
+    assert_eq!(Scanner::new("NCR").next(), Token::Function(Function::NCr));
+    assert_eq!(Scanner::new("τ").next(), Scanner::new("tau").next());
+    assert_eq!(Scanner::new("sinx").next(), Token::Text("sinx"));
+}
+
+#[test]
+fn span() {
+    let mut sc = Scanner::new(" sin  12.5+x");
+    sc.next();
+    assert_eq!(sc.span(), (1, 4));
+    sc.next();
+    assert_eq!(sc.span(), (6, 10));
+    sc.next();
+    assert_eq!(sc.span(), (10, 11));
+    sc.next();
+    assert_eq!(sc.span(), (11, 12));
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn symbols() {
+    let mut sc = Scanner::new("30° 5!! 3! mode");
+    assert!(close(unwrap(&sc.next()), 30.0));
+    assert_eq!(sc.next(), Token::Degree);
+    assert!(close(unwrap(&sc.next()), 5.0));
+    assert_eq!(sc.next(), Token::DoubleFactorial);
+    assert!(close(unwrap(&sc.next()), 3.0));
+    assert_eq!(sc.next(), Token::Factorial);
+    assert_eq!(sc.next(), Token::Command(Command::Mode));
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn dates() {
+    let mut sc = Scanner::new("2026-10-18 - 1970-01-02T01:00 2026-02-30 2026-10");
+    assert_eq!(sc.next(), Token::Date(1792281600.0));
+    assert_eq!(sc.span(), (0, 10));
+    assert_eq!(sc.next(), Token::Subtraction);
+    assert_eq!(sc.next(), Token::Date(90000.0));
+    assert_eq!(sc.next(), Token::Unknown);
+    assert!(close(unwrap(&sc.next()), 2026.0));
+    assert_eq!(sc.next(), Token::Subtraction);
+    assert!(close(unwrap(&sc.next()), 10.0));
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn brackets() {
+    let mut sc = Scanner::new("[[1], det]");
+    assert_eq!(sc.next(), Token::Lbracket);
+    assert_eq!(sc.next(), Token::Lbracket);
+    assert!(close(unwrap(&sc.next()), 1.0));
+    assert_eq!(sc.next(), Token::Rbracket);
+    assert_eq!(sc.next(), Token::Comma);
+    assert_eq!(sc.next(), Token::Function(Function::Det));
+    assert_eq!(sc.next(), Token::Rbracket);
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn unicode() {
+    let mut sc = Scanner::new("2×π ÷ √x − y⁻¹ · 3²");
+    assert!(close(unwrap(&sc.next()), 2.0));
+    assert_eq!(sc.next(), Token::Multiplication);
+    assert!(close(unwrap(&sc.next()), std::f64::consts::PI));
+    assert_eq!(sc.next(), Token::Division);
+    assert_eq!(sc.next(), Token::Function(Function::Sqrt));
+    assert_eq!(sc.next(), Token::Text("x"));
+    assert_eq!(sc.next(), Token::Subtraction);
+    assert_eq!(sc.next(), Token::Text("y"));
+    assert_eq!(sc.next(), Token::Power);
+    assert_eq!(sc.next(), Token::Number(-1.0));
+    assert_eq!(sc.span(), (19, 24));
+    assert_eq!(sc.next(), Token::Multiplication);
+    assert!(close(unwrap(&sc.next()), 3.0));
+    assert_eq!(sc.next(), Token::Power);
+    assert_eq!(sc.next(), Token::Number(2.0));
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn unknown() {
+    // Unrecognized characters are not skipped
+    let mut sc = Scanner::new("2 & 3 ⁻");
+    assert!(close(unwrap(&sc.next()), 2.0));
+    assert_eq!(sc.next(), Token::Unknown);
+    assert_eq!(sc.span(), (2, 3));
+    assert!(close(unwrap(&sc.next()), 3.0));
+    assert_eq!(sc.next(), Token::Power);
+    assert_eq!(sc.next(), Token::Unknown);
+    assert_eq!(sc.next(), Token::END);
+}
+
+#[test]
+fn signatures() {
+    assert_eq!(Function::Round.syntax(), "round(x, digits=0)");
+    assert_eq!(Function::Log.syntax(), "log(x, base=e)");
+    assert_eq!(Function::Sum.syntax(), "sum(x, ...)");
+    assert_eq!(Function::Rnd.syntax(), "rnd()");
+    let pv = Function::Pv.signature();
+    assert_eq!((pv.min(), pv.max()), (3, 4));
+    assert_eq!(pv.params[3].name(), "fv");
+    assert_eq!(
+        pv.fill(&[0.1, 2.0, 3.0]),
+        Option::Some(vec![0.1, 2.0, 3.0, 0.0])
+    );
+    assert_eq!(pv.fill(&[0.1, 2.0, 3.0, 4.0]), Option::None);
+    // Optional parameters without a value are left to the function
+    assert_eq!(Function::Log.signature().fill(&[1.0]), Option::None);
+    assert_eq!(Function::Npv.signature().max(), usize::MAX);
+    assert!(Function::Sin.check_arity(1).is_ok());
+    assert_eq!(
+        Function::Sin.check_arity(2),
+        Result::Err("sin expects 1 argument, got 2 (sin(x))".to_string())
+    );
+}
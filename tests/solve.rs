@@ -0,0 +1,37 @@
+use calc::solve::*;
+
+#[test]
+fn polynomials() {
+    assert_eq!(polynomial_roots(&[-4.0, 2.0]), vec![(2.0, 0.0)]);
+    assert_eq!(
+        polynomial_roots(&[6.0, -5.0, 1.0]),
+        vec![(2.0, 0.0), (3.0, 0.0)]
+    );
+    assert_eq!(
+        polynomial_roots(&[1.0, 0.0, 1.0]),
+        vec![(0.0, -1.0), (0.0, 1.0)]
+    );
+    assert_eq!(polynomial_roots(&[0.0, 0.0, 1.0]), vec![(0.0, 0.0)]);
+    assert_eq!(
+        polynomial_roots(&[0.0, -1.0, 0.0, 1.0, 0.0]),
+        vec![(-1.0, 0.0), (0.0, 0.0), (1.0, 0.0)]
+    );
+    // (x - 1)^2 (x + 2)
+    assert_eq!(
+        polynomial_roots(&[2.0, -3.0, 0.0, 1.0]),
+        vec![(-2.0, 0.0), (1.0, 0.0)]
+    );
+    // x^4 = 16
+    assert_eq!(
+        polynomial_roots(&[-16.0, 0.0, 0.0, 0.0, 1.0]),
+        vec![(-2.0, 0.0), (2.0, 0.0), (0.0, -2.0), (0.0, 2.0)]
+    );
+}
+
+#[test]
+fn close_roots() {
+    // (x - 1)(x - 1.001)(x + 3)
+    let roots = polynomial_roots(&[3.003, -5.002, 0.999, 1.0]);
+    assert_eq!(roots.len(), 3);
+    assert!((roots[1].0 - 1.0).abs() < 1e-9 && (roots[2].0 - 1.001).abs() < 1e-9);
+}
@@ -0,0 +1,38 @@
+use calc::suggest::*;
+
+#[test]
+fn edit_distance() {
+    assert_eq!(distance("sqrt", "sqrt"), 0);
+    assert_eq!(distance("sqr", "sqrt"), 1);
+    assert_eq!(distance("atna2", "atan2"), 1);
+    assert_eq!(distance("cos", "sin"), 3);
+    assert_eq!(distance("", "abs"), 3);
+}
+
+#[test]
+fn closest() {
+    assert_eq!(suggest("sqr", vec!["sqrt", "sqrt2", "sin"]), vec!["sqrt"]);
+    assert_eq!(suggest("PIE", vec!["pi", "e"]), vec!["pi"]);
+    // Ties go to the longest shared prefix, and farther names are left out
+    assert_eq!(suggest("tn", vec!["tan", "ln", "sin"]), vec!["tan", "ln"]);
+    assert_eq!(
+        suggest("sped", vec!["nper", "seed", "speed"]),
+        vec!["speed", "seed"]
+    );
+    assert!(suggest("qwerty", vec!["sqrt", "pi"]).is_empty());
+    assert_eq!(suggest("f", vec!["f", "F"]), vec!["F"]);
+    // Names defined by the user come first
+    assert_eq!(
+        suggest_defined("sped", vec!["seed"], vec!["speed", "nper"]),
+        vec!["seed", "speed"]
+    );
+}
+
+#[test]
+fn format() {
+    assert_eq!(message("Unknown", "x", &[]), "Unknown 'x'");
+    assert_eq!(
+        message("Unknown", "tn", &["ln", "tan"]),
+        "Unknown 'tn', did you mean 'ln' or 'tan'?"
+    );
+}
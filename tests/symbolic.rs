@@ -0,0 +1,103 @@
+use calc::context::Context;
+use calc::parser::*;
+use calc::scanner::Scanner;
+use calc::symbolic::*;
+use calc::value::Value;
+
+fn parser(input: &str) -> Parser<'_> {
+    let mut context = Context::seeded(1);
+    context.set("x", Value::Number(0.7));
+    context.set("y", Value::Number(-1.3));
+    Parser::with_context(Scanner::new(input), context)
+}
+
+fn eval(input: &str) -> f64 {
+    let mut p = parser(input);
+    p.next().unwrap().unwrap().as_number().unwrap()
+}
+
+// The printed form parses back to an expression with the same text and value
+fn round_trip(input: &str) {
+    let mut p = parser(input);
+    let e = p.parse().unwrap().unwrap();
+    let text = e.to_string();
+    let again = parser(&text).parse().unwrap().unwrap().to_string();
+    assert_eq!(again, text, "{}", input);
+    let (a, b) = (eval(input), eval(&text));
+    assert!((a - b).abs() < 1e-9, "{} = {}, {} = {}", input, a, text, b);
+    // The same holds after simplification
+    let simple = format(&simplify(p.context_mut(), &e), Notation::Plain);
+    let twice = format(
+        &simplify(p.context_mut(), &parser(&simple).parse().unwrap().unwrap()),
+        Notation::Plain,
+    );
+    assert_eq!(twice, simple, "{}", input);
+    let c = eval(&simple);
+    assert!(
+        (a - c).abs() < 1e-9,
+        "{} = {}, {} = {}",
+        input,
+        a,
+        simple,
+        c
+    );
+}
+
+#[test]
+fn round_trips() {
+    round_trip("2x^2 - 3x + 1");
+    round_trip("-x^2");
+    round_trip("-(x^2)");
+    round_trip("2^3^2");
+    round_trip("2^(3^2)");
+    round_trip("x % -3");
+    round_trip("sin x^2");
+    round_trip("sin(x^2)");
+    round_trip("-(x + 1) * y");
+    round_trip("x / (2x)");
+    round_trip("x / 2x");
+    round_trip("20% of 150");
+    round_trip("100 + 15% - y");
+    round_trip("|x - 1| + abs y");
+    round_trip("3!^2 + x!");
+    round_trip("log(x, 2) + ln x");
+    round_trip("x^-1 - y^-2");
+    round_trip("sqrt(x)(x + 1)");
+    round_trip("(x + y)^3 / (x - y)");
+    round_trip("2pi x + e^x");
+    round_trip("sin(-x) + cos(-y)^2");
+    round_trip("1 / 3 * x");
+    round_trip("atan2(y, x) * 180°");
+}
+
+fn simplified(input: &str) -> String {
+    let mut p = parser(input);
+    let e = p.parse().unwrap().unwrap();
+    format(&simplify(p.context_mut(), &e), Notation::Plain)
+}
+
+#[test]
+fn identities() {
+    assert_eq!(simplified("x + x + y - x"), "x + y");
+    assert_eq!(simplified("(x + 1)(x - 1)"), "x^2 - 1");
+    assert_eq!(simplified("x y / (y x^2)"), "1 / x");
+    assert_eq!(simplified("3 sin(x)^2 + 3cos(x)^2"), "3");
+    assert_eq!(simplified("sin(2x) / cos(2x)"), "tan(2x)");
+    assert_eq!(simplified("exp(ln(x))"), "x");
+    assert_eq!(simplified("tan(-x) + tan(x)"), "0");
+    assert_eq!(simplified("sqrt(9) x + sqrt(3)"), "3x + sqrt(3)");
+    assert_eq!(simplified("x^0 + 0x"), "1");
+}
+
+#[test]
+fn notations() {
+    let mut p = parser("x^2 * sin(x) - 1 / sqrt(y)");
+    let e = p.parse().unwrap().unwrap();
+    assert_eq!(format(&e, Notation::Unicode), "x²·sin(x) − 1 / √y");
+    assert_eq!(
+        format(&e, Notation::Latex),
+        "x^{2} \\cdot \\sin\\left(x\\right) - \\frac{1}{\\sqrt{y}}"
+    );
+    assert_eq!(Notation::from_name("LaTeX"), Option::Some(Notation::Latex));
+    assert_eq!(Notation::from_name("ascii"), Option::None);
+}
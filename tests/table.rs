@@ -0,0 +1,129 @@
+use calc::parser::Parser;
+use calc::table::*;
+use calc::value::{Format, Locale, Value};
+
+fn eval(input: &str) -> String {
+    match Parser::new(input).last() {
+        Option::Some(Result::Ok(Value::Text(s))) => s,
+        v => panic!("{:?}", v),
+    }
+}
+
+#[test]
+fn ranges() {
+    let r = Range::parse("x=0:1:0.1").unwrap();
+    assert_eq!(r.count(), 11);
+    assert_eq!(r.values()[3], 0.3);
+    assert_eq!(r.values()[10], 1.0);
+    assert_eq!(
+        Range::parse("t = 5:-5:-2.5").unwrap().values(),
+        vec![5.0, 2.5, 0.0, -2.5, -5.0]
+    );
+    assert_eq!(Range::parse("n=1:3").unwrap().values(), vec![1.0, 2.0, 3.0]);
+    assert_eq!(Range::parse("x=0:1:0.3").unwrap().count(), 4);
+    assert!(Range::parse("x=0:1:0").is_err());
+    assert!(Range::parse("x=0:1:-1").is_err());
+    assert!(Range::parse("x=0").is_err());
+    assert!(Range::parse("=0:1").is_err());
+    assert!(Range::parse("x=a:b").is_err());
+    assert!(Range::parse("x=0:1,2").is_err());
+    let fi = Locale::from_name("fi").unwrap();
+    assert_eq!(Range::parse_with("x=0:1:0,25", &fi).unwrap().count(), 5);
+    assert_eq!(
+        Range::parse_with("x=0:1,5pi", &fi).unwrap().stop,
+        1.5 * std::f64::consts::PI
+    );
+    assert_eq!(Range::parse("t=-pi:2pi:pi").unwrap().count(), 4);
+    assert!(Range::parse("x=0:1E300")
+        .unwrap_err()
+        .contains("Too many values"));
+    // Plots only need the bounds
+    let x = Range::interval("x=0:1E300", &Locale::default()).unwrap();
+    assert_eq!((x.start, x.stop), (0.0, 1e300));
+    assert_eq!(Range::interval("x=0:0,5", &fi).unwrap().stop, 0.5);
+}
+
+#[test]
+fn tables() {
+    assert_eq!(
+        eval("table(x^2, x, 0, 1, 0.5)"),
+        "  x   x^2\n---  ----\n  0     0\n0.5  0.25\n  1     1"
+    );
+    assert_eq!(
+        eval("table(1/x, x, -1, 1, 1) in csv"),
+        "x,1 / x\n-1,-1\n0,\n1,1"
+    );
+    assert_eq!(
+        eval("table(x*y, x, 1, 2, 1, y, 0, 1, 1) in json"),
+        "[\n  {\"x\": 1, \"y\": 0, \"x * y\": 0},\n  {\"x\": 1, \"y\": 1, \"x * y\": 1},\n  \
+         {\"x\": 2, \"y\": 0, \"x * y\": 0},\n  {\"x\": 2, \"y\": 1, \"x * y\": 2}\n]"
+    );
+    // The variable is not a time unit
+    assert_eq!(
+        eval("table(3s, s, 0, 2, 1) in csv"),
+        "s,3 * s\n0,0\n1,3\n2,6"
+    );
+    // Variables are restored afterwards
+    let mut p = Parser::new("x = 5, table(x, x, 0, 1, 1), x");
+    p.nth(1);
+    assert_eq!(p.next(), Option::Some(Result::Ok(Value::Number(5.0))));
+    assert!(Parser::new("table(x, 2, 0, 1, 1)").next().unwrap().is_err());
+    assert!(Parser::new("table(x, x, 0, 1)").next().unwrap().is_err());
+    assert!(Parser::new("table(x, x, 0, 1, 1) in pdf")
+        .next()
+        .unwrap()
+        .is_err());
+    assert!(Parser::new("table(x, x, 1, 0, 1)").next().unwrap().is_err());
+    assert!(Parser::new("table(x, x, 0, 1E300, 1)")
+        .next()
+        .unwrap()
+        .is_err());
+    assert!(Parser::new("table(x, x, 0, 1000000, 1)")
+        .next()
+        .unwrap()
+        .is_err());
+    assert!(Parser::new("table(x, x, 0, 1000, 1, y, 0, 1000, 1)")
+        .next()
+        .unwrap()
+        .is_err());
+    // Undefined points are empty, but an expression that fails everywhere is an error
+    let e = Parser::new("table(foo, x, 0, 1, 1)").next().unwrap();
+    assert!(e
+        .unwrap_err()
+        .contains("Unknown variable or constant 'foo'"));
+}
+
+#[test]
+fn formats() {
+    let table = Table {
+        columns: vec!["x".to_string(), "atan2(\"a\", x)".to_string()],
+        rows: vec![vec![1000.0, 2.5], vec![2.0, f64::NAN]],
+    };
+    let grouped = Format {
+        thousands: Option::Some(','),
+        ..Format::default()
+    };
+    assert_eq!(
+        table.format(Style::Text, &grouped),
+        "    x  atan2(\"a\", x)\n-----  -------------\n1,000            2.5\n    2               "
+    );
+    assert_eq!(
+        table.format(Style::Csv, &grouped),
+        "x,\"atan2(\"\"a\"\", x)\"\n1000,2.5\n2,"
+    );
+    assert_eq!(
+        table.format(Style::Json, &grouped),
+        "[\n  {\"x\": 1000, \"atan2(\\\"a\\\", x)\": 2.5},\n  {\"x\": 2, \"atan2(\\\"a\\\", x)\": null}\n]"
+    );
+    assert_eq!(Style::from_name("CSV"), Option::Some(Style::Csv));
+    // Spreadsheets with a decimal comma use ; between cells
+    let comma = Format::with_locale(&Locale::from_name("fi").unwrap(), true);
+    assert_eq!(
+        table.format(Style::Csv, &comma),
+        "x;\"atan2(\"\"a\"\", x)\"\n1000;2,5\n2;"
+    );
+    assert_eq!(
+        table.format(Style::Text, &comma).lines().nth(2),
+        Option::Some("1\u{a0}000            2,5")
+    );
+}
@@ -0,0 +1,42 @@
+use calc::time::*;
+
+#[test]
+fn civil() {
+    assert_eq!(days_from_civil(1970, 1, 1), 0);
+    assert_eq!(days_from_civil(2000, 3, 1), 11017);
+    assert_eq!(days_from_civil(1969, 12, 31), -1);
+    for days in (-800000..800000).step_by(997) {
+        let (y, m, d) = civil_from_days(days);
+        assert_eq!(days_from_civil(y, m, d), days);
+    }
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        parse_date("2024-02-29"),
+        Option::Some((Option::Some(1709164800.0), 10))
+    );
+    assert_eq!(parse_date("2023-02-29"), Option::Some((Option::None, 10)));
+    assert_eq!(
+        parse_date("1970-01-01T00:01:40+"),
+        Option::Some((Option::Some(100.0), 19))
+    );
+    assert_eq!(
+        parse_date("1970-01-01T00:01"),
+        Option::Some((Option::Some(60.0), 16))
+    );
+    assert_eq!(parse_date("1970-01-011"), Option::None);
+    assert_eq!(parse_date("70-01-01"), Option::None);
+}
+
+#[test]
+fn format() {
+    assert_eq!(format_date(0.0), "1970-01-01");
+    assert_eq!(format_date(-1.0), "1969-12-31T23:59:59");
+    assert_eq!(format_duration(0.0), "0s");
+    assert_eq!(format_duration(90061.5), "1d 1h 1min 1.5s");
+    assert_eq!(format_duration(-7200.0), "-2h");
+    assert_eq!(unit("Hours"), Option::Some(3600.0));
+    assert_eq!(unit("fortnight"), Option::None);
+}
@@ -0,0 +1,45 @@
+use calc::value::*;
+
+#[test]
+fn grouping() {
+    let f = Format {
+        thousands: Option::Some(','),
+        ..Format::default()
+    };
+    assert_eq!(Value::Number(1234567.5).format(&f), "1,234,567.5");
+    assert_eq!(Value::Number(-1234.0).format(&f), "-1,234");
+    assert_eq!(Value::Number(123.0).format(&f), "123");
+    assert_eq!(Value::Number(f64::INFINITY).format(&f), "inf");
+    assert_eq!(
+        Value::Money(1234.5, "SEK".to_string()).format(&f),
+        "1,234.50 SEK"
+    );
+    assert_eq!(Value::Number(1234.0).format(&Format::default()), "1234");
+}
+
+#[test]
+fn locales() {
+    let fi = Locale::from_name("fi_FI.UTF-8").unwrap();
+    assert_eq!(
+        Value::Number(1234567.5).format(&Format::with_locale(&fi, false)),
+        "1234567,5"
+    );
+    assert_eq!(
+        Value::Number(-1234567.5).format(&Format::with_locale(&fi, true)),
+        "-1\u{a0}234\u{a0}567,5"
+    );
+    let de = Format::with_locale(&Locale::from_name("de-DE").unwrap(), true);
+    assert_eq!(Value::Number(1234.25).format(&de), "1.234,25");
+    assert_eq!(
+        Value::Money(1234.5, "EUR".to_string()).format(&de),
+        "1.234,50 EUR"
+    );
+    assert_eq!(Value::Duration(5400.0).format(&de), "1h 30min");
+    let en = Format::with_locale(&Locale::from_name("en").unwrap(), true);
+    assert_eq!(Value::Number(1234.25).format(&en), "1,234.25");
+    assert_eq!(de.written("f(x, 1.5)"), "f(x; 1,5)");
+    assert_eq!(en.written("f(x, 1.5)"), "f(x, 1.5)");
+    assert_eq!((de.separator(), en.separator()), ("; ", ", "));
+    assert_eq!(Locale::from_name("C"), Option::Some(Locale::default()));
+    assert_eq!(Locale::from_name("xx"), Option::None);
+}